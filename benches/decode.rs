@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use motec_i2::{ChannelMetadata, LDReader, Sample};
+use std::fs;
+use std::hint::black_box;
+use std::io::Cursor;
+
+fn read_decode(c: &mut Criterion) {
+    let mut reader = LDReader::new(Cursor::new(fs::read("./samples/Sample1.ld").unwrap()));
+    let channels = reader.read_channels().unwrap();
+    let data: Vec<(ChannelMetadata, Vec<Sample>, Vec<u8>)> = channels
+        .into_iter()
+        .map(|channel| {
+            let samples = reader.channel_data(&channel).unwrap();
+            let bytes = reader.channel_bytes(&channel).unwrap();
+            (channel, samples, bytes)
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("sample1 decode");
+    group.bench_function("decode_f64", |b| {
+        b.iter(|| {
+            for (channel, samples, _) in &data {
+                let values: Vec<f64> = samples.iter().map(|s| s.decode_f64(channel)).collect();
+                black_box(values);
+            }
+        })
+    });
+    group.bench_function("decode_samples", |b| {
+        b.iter(|| {
+            for (channel, samples, _) in &data {
+                black_box(channel.decode_samples(samples).unwrap());
+            }
+        })
+    });
+    group.bench_function("decode_bytes", |b| {
+        b.iter(|| {
+            for (channel, _, bytes) in &data {
+                black_box(channel.decode_bytes(bytes).unwrap());
+            }
+        })
+    });
+    group.bench_function("decode_bytes_f32", |b| {
+        b.iter(|| {
+            for (channel, _, bytes) in &data {
+                black_box(channel.decode_bytes_f32(bytes).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, read_decode);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use motec_i2::{ChannelMetadata, Datatype, I2Result, LDReader, LDWriter, Sample};
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::io::{Cursor, Read, Seek};
+
+/// Builds a log with 400 channels of 10 minutes at 50Hz
+fn large_log() -> Vec<u8> {
+    let header = LDReader::new(Cursor::new(fs::read("./samples/Sample1.ld").unwrap()))
+        .read_header()
+        .unwrap();
+
+    let mut writer = LDWriter::new(Cursor::new(Vec::new()), header);
+    for i in 0..400 {
+        let channel = ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            datatype: if i % 2 == 0 {
+                Datatype::I16
+            } else {
+                Datatype::F32
+            },
+            sample_rate: 50,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 1,
+            name: format!("Channel {}", i),
+            short_name: format!("Ch{}", i),
+            unit: "".to_string(),
+        };
+        let samples = (0..30_000)
+            .map(|n| match channel.datatype {
+                Datatype::I16 => Sample::I16((n % 1000) as i16),
+                _ => Sample::F32(n as f32 * 0.5),
+            })
+            .collect();
+        writer = writer.with_channel(channel, samples);
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn per_channel<S: Read + Seek>(mut reader: LDReader<S>) -> I2Result<Vec<Vec<Sample>>> {
+    let channels = reader.read_channels()?;
+    channels
+        .iter()
+        .map(|channel| reader.channel_data(channel))
+        .collect()
+}
+
+fn bulk<S: Read + Seek>(mut reader: LDReader<S>) -> I2Result<Vec<Vec<Sample>>> {
+    let channels = reader.read_channels()?;
+    reader.read_all_channel_data(&channels)
+}
+
+fn read_channel_data(c: &mut Criterion) {
+    let sample1 = fs::read("./samples/Sample1.ld").unwrap();
+    let large = large_log();
+
+    for (name, bytes) in [("sample1", &sample1), ("400 channels", &large)] {
+        let mut group = c.benchmark_group(name);
+        group.bench_function("channel_data", |b| {
+            b.iter(|| per_channel(LDReader::new(Cursor::new(black_box(bytes)))).unwrap())
+        });
+        group.bench_function("read_all_channel_data", |b| {
+            b.iter(|| bulk(LDReader::new(Cursor::new(black_box(bytes)))).unwrap())
+        });
+        group.finish();
+    }
+}
+
+/// Same as [read_channel_data], but through a buffered file to include the cost of seeking
+fn read_channel_data_from_file(c: &mut Criterion) {
+    let path = env::temp_dir().join(format!("motec_i2_bench_{}.ld", std::process::id()));
+    fs::write(&path, large_log()).unwrap();
+
+    let mut group = c.benchmark_group("400 channels file");
+    group.bench_function("channel_data", |b| {
+        b.iter(|| per_channel(LDReader::open(black_box(&path)).unwrap()).unwrap())
+    });
+    group.bench_function("read_all_channel_data", |b| {
+        b.iter(|| bulk(LDReader::open(black_box(&path)).unwrap()).unwrap())
+    });
+    group.finish();
+
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, read_channel_data, read_channel_data_from_file);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+fn main() {
+    // The C header is only generated for the C API, see src/capi.rs. It's written to OUT_DIR,
+    // tests/capi.rs checks that it matches the copy in include/.
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+            .expect("Failed to read cbindgen.toml");
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/capi.rs", crate_dir))
+            .generate()
+            .expect("Failed to generate the C header")
+            .write_to_file(format!("{}/motec_i2.h", out_dir));
+    }
+}
@@ -0,0 +1,23 @@
+use motec_i2::{DiffOptions, I2Result, LDReader};
+use std::env;
+
+fn main() -> I2Result<()> {
+    let mut args = env::args().skip(1);
+    let left = args
+        .next()
+        .expect("Usage: diff <left.ld> <right.ld> [tolerance]");
+    let right = args
+        .next()
+        .expect("Usage: diff <left.ld> <right.ld> [tolerance]");
+    let options = match args.next() {
+        Some(tolerance) => {
+            DiffOptions::with_tolerance(tolerance.parse().expect("Invalid tolerance"))
+        }
+        None => DiffOptions::default(),
+    };
+
+    let diff = LDReader::open(left)?.diff(&mut LDReader::open(right)?, &options)?;
+    print!("{}", diff);
+
+    Ok(())
+}
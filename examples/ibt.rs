@@ -0,0 +1,24 @@
+use motec_i2::{I2Result, IbtReader};
+use std::env;
+use std::fs::File;
+
+fn main() -> I2Result<()> {
+    let mut args = env::args().skip(1);
+    let input = args.next().expect("Usage: ibt <telemetry.ibt> <output.ld>");
+    let output = args.next().expect("Usage: ibt <telemetry.ibt> <output.ld>");
+    println!("Converting file: {}", input);
+
+    let mut reader = IbtReader::open(input)?;
+    let session = reader.read_session()?;
+    println!(
+        "{} channels, {:.1}s at {}",
+        session.channels.len(),
+        session.duration(),
+        session.header.venue
+    );
+
+    let mut output = File::create(output).expect("Failed to create file!");
+    session.write(&mut output)?;
+
+    Ok(())
+}
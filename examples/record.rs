@@ -0,0 +1,31 @@
+use motec_i2::{I2Result, Recorder, TelemetryProtocol};
+use std::env;
+use std::io::stdin;
+
+fn main() -> I2Result<()> {
+    let mut args = env::args().skip(1);
+    let protocol = match args.next().as_deref() {
+        Some("outgauge") => TelemetryProtocol::OutGauge,
+        Some("outsim") => TelemetryProtocol::OutSim,
+        Some("forza") => TelemetryProtocol::Forza,
+        Some("f1") => TelemetryProtocol::F1,
+        _ => panic!("Usage: record <outgauge|outsim|forza|f1> <addr> <output.ld>"),
+    };
+    let addr = args.next().unwrap_or("0.0.0.0:20777".into());
+    let output = args.next().unwrap_or("recording.ld".into());
+
+    let recorder = Recorder::start(&addr, protocol, &output)?;
+    println!(
+        "Recording on {}, press enter to stop",
+        recorder.local_addr()
+    );
+    stdin().read_line(&mut String::new())?;
+
+    let recording = recorder.stop()?;
+    for (stream, packets) in recording.packet_counts() {
+        println!("{}: {} packets", stream, packets);
+    }
+    println!("Written to {}", output);
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use motec_i2::{I2Result, LDReader};
+use std::env;
+use std::fs::File;
+
+fn main() -> I2Result<()> {
+    let mut args = env::args().skip(1);
+    let input = args
+        .next()
+        .expect("Usage: repair <damaged.ld> <repaired.ld>");
+    let output = args
+        .next()
+        .expect("Usage: repair <damaged.ld> <repaired.ld>");
+
+    let mut output = File::create(output).expect("Failed to create file!");
+    let report = LDReader::open(input)?.repair(&mut output)?;
+    print!("{}", report);
+
+    Ok(())
+}
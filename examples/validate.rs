@@ -0,0 +1,15 @@
+use motec_i2::{I2Result, LDReader};
+use std::env;
+
+fn main() -> I2Result<()> {
+    let path = env::args().nth(1).unwrap_or("./samples/Sample1.ld".into());
+    println!("Validating file: {}", path);
+
+    let report = LDReader::open(&path)?.validate()?;
+    print!("{}", report);
+
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
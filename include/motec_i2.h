@@ -0,0 +1,189 @@
+#ifndef MOTEC_I2_H
+#define MOTEC_I2_H
+
+/* Generated by cbindgen from src/capi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of a C API call
+typedef enum I2Status {
+  I2_STATUS_OK = 0,
+  // A required pointer argument was NULL
+  I2_STATUS_NULL_POINTER,
+  // An argument was out of range or a string wasn't valid UTF-8
+  I2_STATUS_INVALID_ARGUMENT,
+  I2_STATUS_IO,
+  I2_STATUS_INVALID_HEADER_MARKER,
+  I2_STATUS_UNRECOGNIZED_DATATYPE,
+  I2_STATUS_NON_UTF8_STRING,
+  I2_STATUS_CHANNEL_NOT_FOUND,
+  I2_STATUS_UNSUPPORTED_DATATYPE,
+  // Any other error of the library
+  I2_STATUS_OTHER,
+  // The library panicked, this is a bug
+  I2_STATUS_PANIC,
+} I2Status;
+
+// An ld file opened for reading
+typedef struct I2Reader I2Reader;
+
+// An ld file being built, written by [i2_writer_finish]
+typedef struct I2Writer I2Writer;
+
+// Header fields as NUL terminated strings, sized for the longest value the file can hold
+typedef struct I2Header {
+  uint32_t device_serial;
+  char device_type[9];
+  uint16_t device_version;
+  // Ignored when writing
+  uint32_t num_channels;
+  char date[17];
+  char time[17];
+  char driver[65];
+  char vehicle_id[65];
+  char venue[65];
+  char session[65];
+  char short_comment[65];
+} I2Header;
+
+// Metadata of a channel as NUL terminated strings
+typedef struct I2ChannelInfo {
+  char name[33];
+  char short_name[9];
+  char unit[13];
+  // Sample rate in Hz
+  uint16_t sample_rate;
+  uint32_t sample_count;
+} I2ChannelInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the message of the last error on this thread, or NULL if there was none
+//
+// The string is valid until the next failing call on the same thread.
+const char *i2_last_error_message(void);
+
+// Fills `header` with empty fields and the device of files written by this library
+//
+// # Safety
+//
+// `header` must be NULL or point to a writable [I2Header].
+enum I2Status i2_header_init(struct I2Header *header);
+
+// Opens the ld file at `path` and reads its header and channel list
+//
+// # Safety
+//
+// `path` must be NULL or a NUL terminated string, `reader` must be NULL or writable. The
+// handle stored in `reader` has to be released with [i2_reader_free].
+enum I2Status i2_reader_open(const char *path, struct I2Reader **reader);
+
+// Closes a reader, NULL is ignored
+//
+// # Safety
+//
+// `reader` must be NULL or a handle from [i2_reader_open] that hasn't been freed yet.
+void i2_reader_free(struct I2Reader *reader);
+
+// Copies the header of the file into `header`
+//
+// # Safety
+//
+// `reader` must be NULL or a valid handle, `header` must be NULL or writable.
+enum I2Status i2_reader_header(const struct I2Reader *reader, struct I2Header *header);
+
+// Number of channels in the file, 0 if `reader` is NULL
+//
+// # Safety
+//
+// `reader` must be NULL or a valid handle.
+size_t i2_reader_channel_count(const struct I2Reader *reader);
+
+// Copies the metadata of channel `index` into `info`
+//
+// # Safety
+//
+// `reader` must be NULL or a valid handle, `info` must be NULL or writable.
+enum I2Status i2_reader_channel_info(const struct I2Reader *reader,
+                                     size_t index,
+                                     struct I2ChannelInfo *info);
+
+// Stores the index of the channel called `name` in `index`
+//
+// # Safety
+//
+// `reader` must be NULL or a valid handle, `name` must be NULL or a NUL terminated string
+// and `index` must be NULL or writable.
+enum I2Status i2_reader_find_channel(const struct I2Reader *reader,
+                                     const char *name,
+                                     size_t *index);
+
+// Decodes up to `len` physical values of channel `index` into `values`
+//
+// The number of values written is stored in `written` if it isn't NULL.
+//
+// # Safety
+//
+// `reader` must be NULL or a valid handle, `values` must be NULL or point to `len` writable
+// doubles and `written` must be NULL or writable.
+enum I2Status i2_reader_read_values(struct I2Reader *reader,
+                                    size_t index,
+                                    double *values,
+                                    size_t len,
+                                    size_t *written);
+
+// Starts a new ld file at `path` with the fields of `header`
+//
+// Nothing is written until [i2_writer_finish].
+//
+// # Safety
+//
+// `path` must be NULL or a NUL terminated string, `header` must be NULL or a valid
+// [I2Header] and `writer` must be NULL or writable. The handle stored in `writer` has to be
+// released with [i2_writer_finish] or [i2_writer_free].
+enum I2Status i2_writer_create(const char *path,
+                               const struct I2Header *header,
+                               struct I2Writer **writer);
+
+// Adds a channel with `count` physical `values` sampled at `sample_rate` Hz
+//
+// The datatype and decimal places are chosen to fit the values. `short_name` and `unit` may
+// be NULL.
+//
+// # Safety
+//
+// `writer` must be NULL or a valid handle, the strings must be NULL or NUL terminated and
+// `values` must be NULL or point to `count` doubles.
+enum I2Status i2_writer_add_channel(struct I2Writer *writer,
+                                    const char *name,
+                                    const char *short_name,
+                                    const char *unit,
+                                    uint16_t sample_rate,
+                                    const double *values,
+                                    size_t count);
+
+// Writes the file and releases the writer, even if writing fails
+//
+// # Safety
+//
+// `writer` must be NULL or a handle from [i2_writer_create] that hasn't been released yet.
+enum I2Status i2_writer_finish(struct I2Writer *writer);
+
+// Releases a writer without writing the file, NULL is ignored
+//
+// # Safety
+//
+// `writer` must be NULL or a handle from [i2_writer_create] that hasn't been released yet.
+void i2_writer_free(struct I2Writer *writer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MOTEC_I2_H */
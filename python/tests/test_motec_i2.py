@@ -0,0 +1,74 @@
+from pathlib import Path
+
+import numpy as np
+import pytest
+
+import motec_i2
+
+SAMPLE1 = Path(__file__).parents[2] / "samples" / "Sample1.ld"
+
+
+@pytest.fixture
+def sample1():
+    return motec_i2.LdFile(SAMPLE1)
+
+
+def test_header(sample1):
+    header = sample1.header
+    assert header["venue"] == "Calder"
+    assert header["date"] == "23/11/2005"
+    assert header["time"] == "09:53:00"
+    assert header["vehicle_id"] == "11A"
+    assert header["num_channels"] == 78
+
+
+def test_event_venue_vehicle(sample1):
+    assert sample1.event["name"] == "i2 data day"
+    assert sample1.venue == {"name": "Calder"}
+    assert sample1.vehicle["id"] == "11A"
+
+
+def test_channels(sample1):
+    channels = sample1.channels
+    assert len(channels) == len(sample1) == 78
+    assert channels[0]["name"] == "Air Temp Inlet"
+    assert channels[0]["unit"] == "C"
+    assert channels[0]["sample_rate"] == 2
+
+
+def test_channel_values(sample1):
+    values, time = sample1.channel("Air Temp Inlet")
+    assert isinstance(values, np.ndarray)
+    assert values.dtype == np.float64
+    assert len(values) == len(time) == 908
+    np.testing.assert_allclose(values[:5], [19.9, 19.9, 20.1, 19.9, 19.9])
+    np.testing.assert_allclose(time[:3], [0.0, 0.5, 1.0])
+
+
+def test_missing_channel(sample1):
+    with pytest.raises(KeyError):
+        sample1.channel("Not a channel")
+
+
+def test_write_round_trip(sample1, tmp_path):
+    speed, _ = sample1.channel("Ground Speed")
+    rate = next(c["sample_rate"] for c in sample1.channels if c["name"] == "Ground Speed")
+
+    path = tmp_path / "out.ld"
+    writer = motec_i2.LdWriter(path, sample1.header)
+    writer.set_event("Round trip", "1")
+    writer.set_venue("Calder")
+    writer.add_channel("Ground Speed", speed, rate, "km/h")
+    writer.add_channel("Counter", np.arange(100), 10)
+    writer.write()
+
+    written = motec_i2.LdFile(path)
+    assert written.header["venue"] == "Calder"
+    assert written.event["name"] == "Round trip"
+    assert [c["name"] for c in written.channels] == ["Ground Speed", "Counter"]
+
+    values, time = written.channel("Ground Speed")
+    np.testing.assert_allclose(values, speed, atol=0.01)
+    assert time[1] == pytest.approx(1 / rate)
+    counter, _ = written.channel("Counter")
+    np.testing.assert_array_equal(counter, np.arange(100))
@@ -0,0 +1,536 @@
+use crate::resample::{resample, resampled_len};
+use crate::{
+    ChannelMetadata, ChannelWithSamples, Datatype, Header, I2Error, I2Result, LDReader, Sample,
+};
+use arrow_array::types::{Float32Type, Int16Type, Int32Type};
+use arrow_array::{
+    Array, ArrayRef, ArrowPrimitiveType, Float32Array, Float64Array, Int16Array, Int32Array,
+    PrimitiveArray, RecordBatch,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use parquet::file::reader::ChunkReader;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+
+/// Name of the column holding the time of each row in seconds
+pub const ARROW_TIME_COLUMN: &str = "Time";
+
+/// How channels are laid out into Arrow record batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrowLayout {
+    /// One record batch per sample rate with the raw (still scaled) samples of each channel
+    ///
+    /// Channels shorter than the longest channel of their batch are padded with nulls. This
+    /// layout can be converted back without any loss by [from_record_batches].
+    BySampleRate,
+    /// A single record batch with every channel decoded and resampled to the fastest sample
+    /// rate, see [crate::resample]
+    Resampled,
+}
+
+impl From<ArrowError> for I2Error {
+    fn from(e: ArrowError) -> Self {
+        I2Error::ArrowError(e.to_string())
+    }
+}
+
+impl From<ParquetError> for I2Error {
+    fn from(e: ParquetError) -> Self {
+        I2Error::ArrowError(e.to_string())
+    }
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Reads the whole file into Arrow record batches
+    ///
+    /// Every batch starts with a [ARROW_TIME_COLUMN] column followed by one column per channel.
+    /// The header is stored in the schema metadata and the channel metadata in the field
+    /// metadata, using keys prefixed with `motec.`.
+    pub fn read_record_batches(&mut self, layout: ArrowLayout) -> I2Result<Vec<RecordBatch>> {
+        let header = self.read_header()?;
+        let channels = self.read_channels()?;
+
+        let mut groups: BTreeMap<u16, Vec<(usize, &ChannelMetadata)>> = BTreeMap::new();
+        for (i, channel) in channels.iter().enumerate() {
+            if channel.datatype == Datatype::F16 {
+                return Err(I2Error::UnsupportedDatatype(channel.datatype.clone()));
+            }
+            let rate = match layout {
+                ArrowLayout::BySampleRate => channel.sample_rate,
+                ArrowLayout::Resampled => 0,
+            };
+            groups.entry(rate).or_default().push((i, channel));
+        }
+
+        let mut batches = vec![];
+        for (rate, group) in groups {
+            let batch = match layout {
+                ArrowLayout::BySampleRate => self.raw_batch(&header, rate, &group)?,
+                ArrowLayout::Resampled => self.resampled_batch(&header, &group)?,
+            };
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    fn raw_batch(
+        &mut self,
+        header: &Header,
+        sample_rate: u16,
+        channels: &[(usize, &ChannelMetadata)],
+    ) -> I2Result<RecordBatch> {
+        let len = channels
+            .iter()
+            .map(|(_, c)| c.data_count as usize)
+            .max()
+            .unwrap_or(0);
+
+        let mut fields = vec![time_field()];
+        let mut columns = vec![time_column(len, sample_rate)];
+        for (index, channel) in channels {
+            let samples = self.channel_data(channel)?;
+            let padding = len - samples.len();
+            let (data_type, column): (DataType, ArrayRef) = match channel.datatype.size() {
+                4 if channel.datatype == Datatype::F32 => (
+                    DataType::Float32,
+                    Arc::new(Float32Array::from(padded(&samples, padding, |s| match s {
+                        Sample::F32(v) => Some(*v),
+                        _ => None,
+                    }))),
+                ),
+                4 => (
+                    DataType::Int32,
+                    Arc::new(Int32Array::from(padded(&samples, padding, |s| match s {
+                        Sample::I32(v) => Some(*v),
+                        _ => None,
+                    }))),
+                ),
+                _ => (
+                    DataType::Int16,
+                    Arc::new(Int16Array::from(padded(&samples, padding, |s| match s {
+                        Sample::I16(v) => Some(*v),
+                        _ => None,
+                    }))),
+                ),
+            };
+
+            fields.push(channel_field(*index, channel, data_type, false));
+            columns.push(column);
+        }
+
+        let mut metadata = header_metadata(header);
+        metadata.insert("motec.sample_rate".into(), sample_rate.to_string());
+        let schema = Schema::new_with_metadata(fields, metadata);
+        Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+    }
+
+    fn resampled_batch(
+        &mut self,
+        header: &Header,
+        channels: &[(usize, &ChannelMetadata)],
+    ) -> I2Result<RecordBatch> {
+        let rate = channels
+            .iter()
+            .map(|(_, c)| c.sample_rate)
+            .max()
+            .unwrap_or(0);
+        let lens: Vec<usize> = channels
+            .iter()
+            .map(|(_, c)| resampled_len(c.data_count as usize, c.sample_rate, rate))
+            .collect();
+        let len = lens.iter().copied().max().unwrap_or(0);
+
+        let mut fields = vec![time_field()];
+        let mut columns = vec![time_column(len, rate)];
+        for ((index, channel), channel_len) in channels.iter().zip(lens) {
+            let values = self.channel_values(channel)?;
+            let mut values: Vec<Option<f64>> =
+                resample(&values, channel.sample_rate, rate, channel_len)
+                    .into_iter()
+                    .map(Some)
+                    .collect();
+            values.resize(len, None);
+
+            fields.push(channel_field(*index, channel, DataType::Float64, true));
+            columns.push(Arc::new(Float64Array::from(values)));
+        }
+
+        let mut metadata = header_metadata(header);
+        metadata.insert("motec.sample_rate".into(), rate.to_string());
+        let schema = Schema::new_with_metadata(fields, metadata);
+        Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+    }
+}
+
+/// Converts record batches created by [LDReader::read_record_batches] back into a header and
+/// channels that can be passed to [crate::LDWriter]
+///
+/// Channels are returned in their original order. Decoded columns (from
+/// [ArrowLayout::Resampled]) are encoded again with the original scaling of the channel, at the
+/// resampled rate.
+pub fn from_record_batches(batches: &[RecordBatch]) -> I2Result<(Header, Vec<ChannelWithSamples>)> {
+    let first = batches
+        .first()
+        .ok_or_else(|| I2Error::ArrowError("No record batches to convert".into()))?;
+    let header = header_from_metadata(first.schema().metadata())?;
+
+    // Batches with the same schema (such as those read from Parquet) are concatenated
+    let mut channels: BTreeMap<usize, ChannelWithSamples> = BTreeMap::new();
+    for batch in batches {
+        let schema = batch.schema();
+        let batch_rate: u16 = metadata_value(schema.metadata(), "motec.sample_rate")?;
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            let metadata = field.metadata();
+            if !metadata.contains_key("motec.datatype") {
+                continue;
+            }
+            let index: usize = metadata_value(metadata, "motec.index")?;
+            let decoded = metadata.get("motec.decoded").is_some_and(|d| d == "true");
+            let (channel, samples) = match channels.entry(index) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let mut channel = channel_from_metadata(field.name(), metadata)?;
+                    if decoded {
+                        channel.sample_rate = batch_rate;
+                    }
+                    entry.insert((channel, vec![]))
+                }
+            };
+
+            // Channels are padded with nulls at the end, so stop at the first null
+            match field.data_type() {
+                DataType::Float64 if decoded => {
+                    let values: Vec<f64> = column_values::<Float64Array>(column)?
+                        .iter()
+                        .map_while(|v| v)
+                        .collect();
+                    if channel.datatype != Datatype::Invalid {
+                        samples.extend(channel.encode_values(&values)?);
+                    }
+                }
+                DataType::Int16 => column_samples::<Int16Type>(column, samples, Sample::I16)?,
+                DataType::Int32 => column_samples::<Int32Type>(column, samples, Sample::I32)?,
+                DataType::Float32 => column_samples::<Float32Type>(column, samples, Sample::F32)?,
+                other => {
+                    return Err(I2Error::ArrowError(format!(
+                        "Unsupported column type {} for channel {}",
+                        other,
+                        field.name()
+                    )))
+                }
+            }
+            channel.data_count = samples.len() as u32;
+        }
+    }
+
+    Ok((header, channels.into_values().collect()))
+}
+
+/// Writes record batches that share a schema into a Parquet file
+///
+/// Batches from [ArrowLayout::BySampleRate] have a different schema per sample rate and have to
+/// be written into separate files.
+pub fn write_parquet<W: Write + Send>(batches: &[RecordBatch], sink: W) -> I2Result<()> {
+    let first = batches
+        .first()
+        .ok_or_else(|| I2Error::ArrowError("No record batches to write".into()))?;
+
+    let mut writer = ArrowWriter::try_new(sink, first.schema(), None)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// Reads all record batches from a Parquet file, including the schema and field metadata
+pub fn read_parquet<R: ChunkReader + 'static>(source: R) -> I2Result<Vec<RecordBatch>> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(source)?;
+    // The batches returned by the reader don't carry the schema metadata
+    let schema = builder.schema().clone();
+    builder
+        .build()?
+        .map(|batch| Ok(batch?.with_schema(schema.clone())?))
+        .collect()
+}
+
+fn time_field() -> Field {
+    Field::new(ARROW_TIME_COLUMN, DataType::Float64, false)
+}
+
+fn time_column(len: usize, sample_rate: u16) -> ArrayRef {
+    let rate = sample_rate.max(1) as f64;
+    Arc::new(Float64Array::from_iter_values(
+        (0..len).map(|i| i as f64 / rate),
+    ))
+}
+
+fn padded<T>(
+    samples: &[Sample],
+    padding: usize,
+    value: impl Fn(&Sample) -> Option<T>,
+) -> Vec<Option<T>> {
+    samples
+        .iter()
+        .map(value)
+        .chain(std::iter::repeat_with(|| None).take(padding))
+        .collect()
+}
+
+fn column_values<A: Array + 'static>(column: &ArrayRef) -> I2Result<&A> {
+    column.as_any().downcast_ref::<A>().ok_or_else(|| {
+        I2Error::ArrowError(format!("Unexpected column type {}", column.data_type()))
+    })
+}
+
+fn column_samples<T: ArrowPrimitiveType>(
+    column: &ArrayRef,
+    samples: &mut Vec<Sample>,
+    sample: impl Fn(T::Native) -> Sample,
+) -> I2Result<()> {
+    let values = column_values::<PrimitiveArray<T>>(column)?;
+    samples.extend(values.iter().map_while(|value| value.map(&sample)));
+    Ok(())
+}
+
+fn channel_field(
+    index: usize,
+    channel: &ChannelMetadata,
+    data_type: DataType,
+    decoded: bool,
+) -> Field {
+    let metadata = HashMap::from([
+        ("motec.index".to_string(), index.to_string()),
+        ("motec.short_name".to_string(), channel.short_name.clone()),
+        ("motec.unit".to_string(), channel.unit.clone()),
+        (
+            "motec.datatype".to_string(),
+            datatype_name(&channel.datatype).to_string(),
+        ),
+        (
+            "motec.sample_rate".to_string(),
+            channel.sample_rate.to_string(),
+        ),
+        (
+            "motec.data_count".to_string(),
+            channel.data_count.to_string(),
+        ),
+        ("motec.offset".to_string(), channel.offset.to_string()),
+        ("motec.mul".to_string(), channel.mul.to_string()),
+        ("motec.scale".to_string(), channel.scale.to_string()),
+        (
+            "motec.dec_places".to_string(),
+            channel.dec_places.to_string(),
+        ),
+        ("motec.decoded".to_string(), decoded.to_string()),
+    ]);
+    Field::new(&channel.name, data_type, true).with_metadata(metadata)
+}
+
+fn channel_from_metadata(
+    name: &str,
+    metadata: &HashMap<String, String>,
+) -> I2Result<ChannelMetadata> {
+    let text = |key: &str| metadata.get(key).cloned().unwrap_or_default();
+    Ok(ChannelMetadata {
+        prev_addr: 0,
+        next_addr: 0,
+        data_addr: 0,
+        data_count: metadata_value(metadata, "motec.data_count")?,
+        datatype: datatype_from_name(&text("motec.datatype"))?,
+        sample_rate: metadata_value(metadata, "motec.sample_rate")?,
+        offset: metadata_value(metadata, "motec.offset")?,
+        mul: metadata_value(metadata, "motec.mul")?,
+        scale: metadata_value(metadata, "motec.scale")?,
+        dec_places: metadata_value(metadata, "motec.dec_places")?,
+        name: name.to_string(),
+        short_name: text("motec.short_name"),
+        unit: text("motec.unit"),
+    })
+}
+
+fn header_metadata(header: &Header) -> HashMap<String, String> {
+    HashMap::from([
+        (
+            "motec.channel_meta_ptr".to_string(),
+            header.channel_meta_ptr.to_string(),
+        ),
+        (
+            "motec.channel_data_ptr".to_string(),
+            header.channel_data_ptr.to_string(),
+        ),
+        ("motec.event_ptr".to_string(), header.event_ptr.to_string()),
+        (
+            "motec.device_serial".to_string(),
+            header.device_serial.to_string(),
+        ),
+        ("motec.device_type".to_string(), header.device_type.clone()),
+        (
+            "motec.device_version".to_string(),
+            header.device_version.to_string(),
+        ),
+        (
+            "motec.num_channels".to_string(),
+            header.num_channels.to_string(),
+        ),
+        ("motec.date".to_string(), header.date_string.clone()),
+        ("motec.time".to_string(), header.time_string.clone()),
+        ("motec.driver".to_string(), header.driver.clone()),
+        ("motec.vehicle_id".to_string(), header.vehicleid.clone()),
+        ("motec.venue".to_string(), header.venue.clone()),
+        ("motec.session".to_string(), header.session.clone()),
+        (
+            "motec.short_comment".to_string(),
+            header.short_comment.clone(),
+        ),
+    ])
+}
+
+fn header_from_metadata(metadata: &HashMap<String, String>) -> I2Result<Header> {
+    let text = |key: &str| metadata.get(key).cloned().unwrap_or_default();
+    Ok(Header {
+        channel_meta_ptr: metadata_value(metadata, "motec.channel_meta_ptr")?,
+        channel_data_ptr: metadata_value(metadata, "motec.channel_data_ptr")?,
+        event_ptr: metadata_value(metadata, "motec.event_ptr")?,
+        device_serial: metadata_value(metadata, "motec.device_serial")?,
+        device_type: text("motec.device_type"),
+        device_version: metadata_value(metadata, "motec.device_version")?,
+        num_channels: metadata_value(metadata, "motec.num_channels")?,
+        date_string: text("motec.date"),
+        time_string: text("motec.time"),
+        driver: text("motec.driver"),
+        vehicleid: text("motec.vehicle_id"),
+        venue: text("motec.venue"),
+        session: text("motec.session"),
+        short_comment: text("motec.short_comment"),
+    })
+}
+
+fn metadata_value<T: std::str::FromStr>(
+    metadata: &HashMap<String, String>,
+    key: &str,
+) -> I2Result<T> {
+    metadata
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| I2Error::ArrowError(format!("Missing or invalid metadata key {}", key)))
+}
+
+fn datatype_name(datatype: &Datatype) -> &'static str {
+    match datatype {
+        Datatype::Beacon16 => "Beacon16",
+        Datatype::Beacon32 => "Beacon32",
+        Datatype::I16 => "I16",
+        Datatype::I32 => "I32",
+        Datatype::F16 => "F16",
+        Datatype::F32 => "F32",
+        Datatype::Invalid => "Invalid",
+    }
+}
+
+fn datatype_from_name(name: &str) -> I2Result<Datatype> {
+    Ok(match name {
+        "Beacon16" => Datatype::Beacon16,
+        "Beacon32" => Datatype::Beacon32,
+        "I16" => Datatype::I16,
+        "I32" => Datatype::I32,
+        "F16" => Datatype::F16,
+        "F32" => Datatype::F32,
+        "Invalid" => Datatype::Invalid,
+        _ => return Err(I2Error::ArrowError(format!("Unknown datatype {}", name))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        from_record_batches, read_parquet, write_parquet, ArrowLayout, LDReader, LDWriter,
+        ARROW_TIME_COLUMN,
+    };
+    use std::fs;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn sample1_round_trips_by_sample_rate() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let header = reader.read_header().unwrap();
+        let channels = reader.read_channels().unwrap();
+        let batches = reader
+            .read_record_batches(ArrowLayout::BySampleRate)
+            .unwrap();
+
+        let mut rates: Vec<u16> = channels.iter().map(|c| c.sample_rate).collect();
+        rates.sort_unstable();
+        rates.dedup();
+        assert_eq!(batches.len(), rates.len());
+        let columns: usize = batches.iter().map(|b| b.num_columns() - 1).sum();
+        assert_eq!(columns, channels.len());
+        assert_eq!(batches[0].schema().field(0).name(), ARROW_TIME_COLUMN);
+        assert_eq!(batches[0].schema().metadata()["motec.venue"], "Calder");
+
+        let (converted_header, converted) = from_record_batches(&batches).unwrap();
+        assert_eq!(converted_header, header);
+        assert_eq!(converted.len(), channels.len());
+        for (original, (channel, samples)) in channels.iter().zip(&converted) {
+            assert_eq!(channel.name, original.name);
+            assert_eq!(channel.unit, original.unit);
+            assert_eq!(channel.datatype, original.datatype);
+            assert_eq!(channel.data_count, original.data_count);
+            assert_eq!(channel.dec_places, original.dec_places);
+            assert_eq!(samples, &reader.channel_data(original).unwrap());
+        }
+
+        let mut output = Cursor::new(Vec::new());
+        let mut writer = LDWriter::new(&mut output, converted_header);
+        for (channel, samples) in converted {
+            writer = writer.with_channel(channel, samples);
+        }
+        writer.write().unwrap();
+    }
+
+    #[test]
+    fn sample1_resampled_to_parquet() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let batches = reader.read_record_batches(ArrowLayout::Resampled).unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_columns(), channels.len() + 1);
+        assert_eq!(batch.num_rows(), 45400);
+
+        let path = std::env::temp_dir().join(format!(
+            "motec-i2-sample1-resampled-{}.parquet",
+            std::process::id()
+        ));
+        write_parquet(&batches, File::create(&path).unwrap()).unwrap();
+        let read = read_parquet(File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let rows: usize = read.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 45400);
+        let schema = read[0].schema();
+        let speed = schema.field_with_name("Ground Speed").unwrap();
+        assert_eq!(speed.metadata()["motec.unit"], "km/h");
+        assert_eq!(speed.metadata()["motec.sample_rate"], "10");
+
+        let (_, converted) = from_record_batches(&read).unwrap();
+        let (speed, samples) = converted
+            .iter()
+            .find(|(c, _)| c.name == "Ground Speed")
+            .unwrap();
+        assert_eq!(speed.sample_rate, 100);
+        assert_eq!(samples.len(), 45400);
+    }
+}
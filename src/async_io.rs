@@ -0,0 +1,288 @@
+use crate::reader::{
+    check_in_source, decode_samples, parse_event, parse_header, parse_raw_channel_metadata,
+    parse_vehicle, parse_venue, EVENT_SIZE, HEADER_SIZE, VEHICLE_SIZE, VENUE_SIZE,
+};
+use crate::{
+    ChannelMetadata, Datatype, Event, Header, I2Result, LDWriter, Sample, Session, SessionDocument,
+    UnitSystem, Vehicle, Venue,
+};
+use std::io::{Cursor, SeekFrom};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// Async version of [LDReader](crate::LDReader) for sources implementing tokio's [AsyncRead]
+/// and [AsyncSeek]
+///
+/// Every block is read into memory with a single read and parsed by the same code as the
+/// sync reader. The reader owns its source, pass `&mut source` to keep using the source
+/// afterwards.
+#[derive(Debug)]
+pub struct AsyncLDReader<S: AsyncRead + AsyncSeek + Unpin> {
+    source: S,
+    header: Option<Header>,
+}
+
+impl<S: AsyncRead + AsyncSeek + Unpin> AsyncLDReader<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            header: None,
+        }
+    }
+
+    /// Returns the source, consuming the reader
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    pub async fn read_header(&mut self) -> I2Result<Header> {
+        // Header is always at start
+        let block = self.read_block(0, HEADER_SIZE).await?;
+        let header = parse_header(&mut &block[..])?;
+        self.header = Some(header.clone());
+        Ok(header)
+    }
+
+    pub async fn read_event(&mut self) -> I2Result<Option<Event>> {
+        if self.header.is_none() {
+            self.read_header().await?;
+        }
+
+        let event_ptr = self.header.as_ref().unwrap().event_ptr;
+        if event_ptr == 0 {
+            return Ok(None);
+        }
+
+        let block = self.read_block(event_ptr, EVENT_SIZE).await?;
+        Ok(Some(parse_event(&mut &block[..])?))
+    }
+
+    pub async fn read_venue(&mut self) -> I2Result<Option<Venue>> {
+        let venue_addr = match self.read_event().await? {
+            Some(event) if event.venue_addr != 0 => event.venue_addr,
+            _ => return Ok(None),
+        };
+
+        let block = self.read_block(venue_addr as u32, VENUE_SIZE).await?;
+        Ok(Some(parse_venue(&mut &block[..])?))
+    }
+
+    pub async fn read_vehicle(&mut self) -> I2Result<Option<Vehicle>> {
+        let vehicle_addr = match self.read_venue().await? {
+            Some(venue) if venue.vehicle_addr != 0 => venue.vehicle_addr,
+            _ => return Ok(None),
+        };
+
+        let block = self.read_block(vehicle_addr as u32, VEHICLE_SIZE).await?;
+        Ok(Some(parse_vehicle(&mut &block[..])?))
+    }
+
+    /// Read the channel meta data blocks inside the ld file, see
+    /// [LDReader::read_channels](crate::LDReader::read_channels)
+    pub async fn read_channels(&mut self) -> I2Result<Vec<ChannelMetadata>> {
+        if self.header.is_none() {
+            self.read_header().await?;
+        }
+
+        let mut channels = vec![];
+
+        let mut next_ptr = self.header.as_ref().unwrap().channel_meta_ptr;
+        // A 0 addr means we are done searching this list
+        while next_ptr != 0 {
+            let block = self
+                .read_block(next_ptr, ChannelMetadata::ENTRY_SIZE as usize)
+                .await?;
+            let (mut channel, (_type, size)) = parse_raw_channel_metadata(&mut &block[..])?;
+            channel.datatype = Datatype::from_type_and_size(_type, size)?;
+            next_ptr = channel.next_addr;
+            channels.push(channel);
+        }
+        Ok(channels)
+    }
+
+    /// Reads all samples of a channel
+    pub async fn channel_data(&mut self, channel: &ChannelMetadata) -> I2Result<Vec<Sample>> {
+        let block = self
+            .read_block(channel.data_addr, channel.data_size() as usize)
+            .await?;
+        decode_samples(&block, channel)
+    }
+
+    /// Reads the channel data and decodes every sample into its final value
+    pub async fn channel_values(&mut self, channel: &ChannelMetadata) -> I2Result<Vec<f64>> {
+        let block = self
+            .read_block(channel.data_addr, channel.data_size() as usize)
+            .await?;
+        channel.decode_bytes(&block)
+    }
+
+    /// Reads the header, event, venue, vehicle and channel metadata without any samples
+    pub async fn read_session_document(&mut self) -> I2Result<SessionDocument> {
+        Ok(SessionDocument {
+            header: self.read_header().await?,
+            event: self.read_event().await?,
+            venue: self.read_venue().await?,
+            vehicle: self.read_vehicle().await?,
+            channels: self.read_channels().await?,
+        })
+    }
+
+    /// Reads the whole file, including the samples of every channel, into a [Session]
+    pub async fn read_session(&mut self) -> I2Result<Session> {
+        let document = self.read_session_document().await?;
+        let mut channels = Vec::with_capacity(document.channels.len());
+        for channel in document.channels {
+            let samples = self.channel_data(&channel).await?;
+            channels.push((channel, samples));
+        }
+
+        Ok(Session {
+            header: document.header,
+            event: document.event,
+            venue: document.venue,
+            vehicle: document.vehicle,
+            channels,
+        })
+    }
+
+    /// Fails without allocating if the block doesn't fit in the source
+    async fn read_block(&mut self, addr: u32, size: usize) -> I2Result<Vec<u8>> {
+        let source_len = self.source.seek(SeekFrom::End(0)).await?;
+        check_in_source(addr, size as u64, source_len)?;
+        self.source.seek(SeekFrom::Start(addr as u64)).await?;
+        let mut bytes = vec![0u8; size];
+        self.source.read_exact(&mut bytes).await?;
+        Ok(bytes)
+    }
+}
+
+/// Async version of [LDWriter] for sinks implementing tokio's [AsyncWrite] and [AsyncSeek]
+///
+/// The file is laid out in memory by [LDWriter] and written to the sink in one go. The writer
+/// owns its sink, pass `&mut sink` to keep using the sink afterwards.
+#[derive(Debug)]
+pub struct AsyncLDWriter<S: AsyncWrite + AsyncSeek + Unpin> {
+    sink: S,
+    header: Header,
+    channels: Vec<(ChannelMetadata, Vec<Sample>)>,
+    unit_system: Option<UnitSystem>,
+    event: Option<Event>,
+    venue: Option<Venue>,
+    vehicle: Option<Vehicle>,
+}
+
+impl<S: AsyncWrite + AsyncSeek + Unpin> AsyncLDWriter<S> {
+    pub fn new(sink: S, header: Header) -> Self {
+        Self {
+            sink,
+            header,
+            channels: Vec::new(),
+            unit_system: None,
+            event: None,
+            venue: None,
+            vehicle: None,
+        }
+    }
+
+    pub fn with_channel(mut self, channel: ChannelMetadata, data: Vec<Sample>) -> Self {
+        self.channels.push((channel, data));
+        self
+    }
+
+    /// See [LDWriter::with_unit_system]
+    pub fn with_unit_system(mut self, system: UnitSystem) -> Self {
+        self.unit_system = Some(system);
+        self
+    }
+
+    /// See [LDWriter::with_event]
+    pub fn with_event(mut self, event: Event) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    /// See [LDWriter::with_venue]
+    pub fn with_venue(mut self, venue: Venue) -> Self {
+        self.venue = Some(venue);
+        self
+    }
+
+    /// See [LDWriter::with_vehicle]
+    pub fn with_vehicle(mut self, vehicle: Vehicle) -> Self {
+        self.vehicle = Some(vehicle);
+        self
+    }
+
+    pub async fn write(self) -> I2Result<()> {
+        self.finish().await?;
+        Ok(())
+    }
+
+    /// Writes the file like [AsyncLDWriter::write] and returns the sink
+    pub async fn finish(mut self) -> I2Result<S> {
+        let mut writer = LDWriter::new(Cursor::new(Vec::new()), self.header);
+        for (channel, samples) in self.channels {
+            writer = writer.with_channel(channel, samples);
+        }
+        if let Some(system) = self.unit_system {
+            writer = writer.with_unit_system(system);
+        }
+        if let Some(event) = self.event {
+            writer = writer.with_event(event);
+        }
+        if let Some(venue) = self.venue {
+            writer = writer.with_venue(venue);
+        }
+        if let Some(vehicle) = self.vehicle {
+            writer = writer.with_vehicle(vehicle);
+        }
+        let file = writer.finish()?;
+
+        self.sink.seek(SeekFrom::Start(0)).await?;
+        self.sink.write_all(file.get_ref()).await?;
+        self.sink.flush().await?;
+        Ok(self.sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsyncLDReader, AsyncLDWriter, LDReader};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn read_sample1_async() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let expected = LDReader::new(&mut Cursor::new(bytes.clone()))
+            .read_session()
+            .unwrap();
+
+        let mut reader = AsyncLDReader::new(Cursor::new(bytes));
+        assert_eq!(reader.read_header().await.unwrap(), expected.header);
+        assert_eq!(reader.read_vehicle().await.unwrap(), expected.vehicle);
+        assert_eq!(reader.read_session().await.unwrap(), expected);
+        assert!(reader.into_inner().position() > 0);
+    }
+
+    #[tokio::test]
+    async fn write_sample1_async() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let session = LDReader::new(&mut Cursor::new(bytes))
+            .read_session()
+            .unwrap();
+
+        let mut expected = Cursor::new(Vec::new());
+        session.write(&mut expected).unwrap();
+
+        let mut writer = AsyncLDWriter::new(Cursor::new(Vec::new()), session.header.clone())
+            .with_event(session.event.clone().unwrap())
+            .with_venue(session.venue.clone().unwrap())
+            .with_vehicle(session.vehicle.clone().unwrap());
+        for (channel, samples) in &session.channels {
+            writer = writer.with_channel(channel.clone(), samples.clone());
+        }
+        let sink = writer.finish().await.unwrap();
+
+        assert_eq!(sink.into_inner(), expected.into_inner());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{ChannelMetadata, Filter, I2Result, Sample};
+
+/// Derivative of `values` recorded at `sample_rate` Hz, in units per second
+///
+/// Uses central differences, with one sided differences for the first and last sample.
+pub fn derivative(values: &[f64], sample_rate: u16) -> Vec<f64> {
+    let n = values.len();
+    if sample_rate == 0 {
+        return vec![0.0; n];
+    }
+    let rate = sample_rate as f64;
+
+    (0..n)
+        .map(|i| match (i.checked_sub(1), i + 1 < n) {
+            (Some(prev), true) => (values[i + 1] - values[prev]) * rate / 2.0,
+            (None, true) => (values[i + 1] - values[i]) * rate,
+            (Some(prev), false) => (values[i] - values[prev]) * rate,
+            (None, false) => 0.0,
+        })
+        .collect()
+}
+
+/// Same as [derivative] but smoothing the values with `smoothing` first
+///
+/// Differentiating amplifies noise, so noisy channels (such as damper positions) should usually
+/// be low pass filtered first.
+pub fn smoothed_derivative(
+    values: &[f64],
+    sample_rate: u16,
+    smoothing: &Filter,
+) -> I2Result<Vec<f64>> {
+    let smoothed = smoothing.apply(values, sample_rate)?;
+    Ok(derivative(&smoothed, sample_rate))
+}
+
+/// Running integral of `values` recorded at `sample_rate` Hz using the trapezoidal rule
+///
+/// The integral is reset to 0 at each of the times (in seconds) in `resets`, for example at
+/// the start of every lap.
+pub fn integral(values: &[f64], sample_rate: u16, resets: &[f64]) -> Vec<f64> {
+    if sample_rate == 0 {
+        return vec![0.0; values.len()];
+    }
+    let rate = sample_rate as f64;
+
+    let mut resets: Vec<usize> = resets
+        .iter()
+        .map(|time| (time * rate).round().max(0.0) as usize)
+        .collect();
+    resets.sort_unstable();
+    let mut resets = resets.into_iter().peekable();
+
+    let mut total = 0.0;
+    let mut prev: Option<f64> = None;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let mut reset = false;
+            while resets.next_if(|r| *r <= i).is_some() {
+                reset = true;
+            }
+
+            match prev {
+                _ if reset => total = 0.0,
+                Some(prev) => total += (prev + value) / 2.0 / rate,
+                None => {}
+            }
+            prev = Some(*value);
+            total
+        })
+        .collect()
+}
+
+/// Unit of the derivative of a channel with unit `unit`, e.g. `mm` becomes `mm/s`
+pub fn derivative_unit(unit: &str) -> String {
+    match unit.strip_suffix("*s") {
+        Some(unit) => unit.to_string(),
+        None => format!("{}/s", unit),
+    }
+}
+
+/// Unit of the integral of a channel with unit `unit`, and the factor that the integrated
+/// values have to be multiplied with to be in that unit
+///
+/// Rates per hour and per minute are converted, so `l/h` becomes `l` with a factor of
+/// `1 / 3600`. Units that aren't rates get `*s` appended.
+pub fn integral_unit(unit: &str) -> (String, f64) {
+    let rates = [("/s", 1.0), ("/min", 60.0), ("/h", 3600.0), ("/hr", 3600.0)];
+    for (suffix, seconds) in rates {
+        if let Some(unit) = unit.strip_suffix(suffix) {
+            return (unit.to_string(), 1.0 / seconds);
+        }
+    }
+    (format!("{}*s", unit), 1.0)
+}
+
+/// Builds a derivative channel of `channel`, ready to be passed to
+/// [crate::LDWriter::with_channel]
+///
+/// The unit is derived with [derivative_unit] and the encoding is chosen with
+/// [ChannelMetadata::fit_to_values].
+pub fn derivative_channel(
+    channel: &ChannelMetadata,
+    values: &[f64],
+    name: &str,
+    short_name: &str,
+    smoothing: Option<&Filter>,
+) -> I2Result<(ChannelMetadata, Vec<Sample>)> {
+    let derived = match smoothing {
+        Some(filter) => smoothed_derivative(values, channel.sample_rate, filter)?,
+        None => derivative(values, channel.sample_rate),
+    };
+
+    let mut metadata = channel.derive(name, short_name);
+    metadata.unit = derivative_unit(&channel.unit);
+    metadata.fit_to_values(&derived);
+
+    let samples = metadata.encode_values(&derived)?;
+    Ok((metadata, samples))
+}
+
+/// Builds a running integral channel of `channel`, ready to be passed to
+/// [crate::LDWriter::with_channel]
+///
+/// See [integral] for `resets`. The unit is derived with [integral_unit] and the encoding is
+/// chosen with [ChannelMetadata::fit_to_values].
+pub fn integral_channel(
+    channel: &ChannelMetadata,
+    values: &[f64],
+    name: &str,
+    short_name: &str,
+    resets: &[f64],
+) -> I2Result<(ChannelMetadata, Vec<Sample>)> {
+    let (unit, factor) = integral_unit(&channel.unit);
+    let integrated: Vec<f64> = integral(values, channel.sample_rate, resets)
+        .into_iter()
+        .map(|value| value * factor)
+        .collect();
+
+    let mut metadata = channel.derive(name, short_name);
+    metadata.unit = unit;
+    metadata.fit_to_values(&integrated);
+
+    let samples = metadata.encode_values(&integrated)?;
+    Ok((metadata, samples))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        derivative, derivative_channel, derivative_unit, integral, integral_channel, integral_unit,
+        Datatype, Filter, LDReader,
+    };
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn central_difference_derivative() {
+        let values = [0.0, 1.0, 4.0, 9.0, 16.0];
+        assert_eq!(derivative(&values, 2), vec![2.0, 4.0, 8.0, 12.0, 14.0]);
+        assert_eq!(derivative(&[5.0], 2), vec![0.0]);
+        assert!(derivative(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn trapezoidal_integral_with_resets() {
+        let values = [1.0, 1.0, 3.0, 3.0, 1.0];
+        assert_eq!(integral(&values, 2, &[]), vec![0.0, 0.5, 1.5, 3.0, 4.0]);
+        assert_eq!(integral(&values, 2, &[1.0]), vec![0.0, 0.5, 0.0, 1.5, 2.5]);
+        assert_eq!(
+            integral(&values, 2, &[0.0, 0.5, 1.0]),
+            vec![0.0, 0.0, 0.0, 1.5, 2.5]
+        );
+    }
+
+    #[test]
+    fn derived_units() {
+        assert_eq!(derivative_unit("mm"), "mm/s");
+        assert_eq!(derivative_unit("km/h"), "km/h/s");
+        assert_eq!(derivative_unit("m*s"), "m");
+        assert_eq!(integral_unit("l/h"), ("l".to_string(), 1.0 / 3600.0));
+        assert_eq!(integral_unit("m/s"), ("m".to_string(), 1.0));
+        assert_eq!(integral_unit("g/min"), ("g".to_string(), 1.0 / 60.0));
+        assert_eq!(integral_unit("mm"), ("mm*s".to_string(), 1.0));
+    }
+
+    #[test]
+    fn sample1_damper_velocity_and_distance() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let find = |name: &str| channels.iter().find(|c| c.name == name).unwrap();
+
+        let susp = find("Susp Pos FL");
+        let values = reader.channel_values(susp).unwrap();
+        let smoothing = Filter::MovingAverage { window: 0.05 };
+        let (velocity, samples) =
+            derivative_channel(susp, &values, "Damper Vel FL", "DampVFL", Some(&smoothing))
+                .unwrap();
+        assert_eq!(velocity.unit, "mm/s");
+        assert_eq!(velocity.sample_rate, 100);
+        assert_eq!(samples.len(), values.len());
+
+        let speed = find("Ground Speed");
+        let values = reader.channel_values(speed).unwrap();
+        let laps = reader.read_laps().unwrap();
+        let resets: Vec<f64> = laps.iter().map(|lap| lap.start).collect();
+        let (distance, samples) =
+            integral_channel(speed, &values, "Lap Dist Calc", "LapDist", &resets).unwrap();
+        assert_eq!(distance.unit, "km");
+        assert_eq!(distance.datatype, Datatype::I16);
+        assert_eq!(distance.dec_places, 3);
+
+        // A lap of Calder Park is about 2.3km
+        let decoded: Vec<f64> = samples.iter().map(|s| s.decode_f64(&distance)).collect();
+        let lap = &laps[2];
+        let end = (lap.end * 10.0) as usize - 1;
+        assert!(decoded[end] > 2.0 && decoded[end] < 2.6, "{}", decoded[end]);
+    }
+}
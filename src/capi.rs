@@ -0,0 +1,526 @@
+//! C API, see `include/motec_i2.h`
+//!
+//! Files are accessed through opaque handles. Every function that can fail returns an
+//! [I2Status], the message of the last error on the calling thread is available through
+//! [i2_last_error_message].
+
+use crate::{ChannelMetadata, ChannelWithSamples, Datatype, Header, I2Error, LDReader, LDWriter};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::{ptr, slice};
+
+/// Result of a C API call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2Status {
+    Ok = 0,
+    /// A required pointer argument was NULL
+    NullPointer,
+    /// An argument was out of range or a string wasn't valid UTF-8
+    InvalidArgument,
+    Io,
+    InvalidHeaderMarker,
+    UnrecognizedDatatype,
+    NonUtf8String,
+    ChannelNotFound,
+    UnsupportedDatatype,
+    /// Any other error of the library
+    Other,
+    /// The library panicked, this is a bug
+    Panic,
+}
+
+/// Header fields as NUL terminated strings, sized for the longest value the file can hold
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct I2Header {
+    pub device_serial: u32,
+    pub device_type: [c_char; 9],
+    pub device_version: u16,
+    /// Ignored when writing
+    pub num_channels: u32,
+    pub date: [c_char; 17],
+    pub time: [c_char; 17],
+    pub driver: [c_char; 65],
+    pub vehicle_id: [c_char; 65],
+    pub venue: [c_char; 65],
+    pub session: [c_char; 65],
+    pub short_comment: [c_char; 65],
+}
+
+/// Metadata of a channel as NUL terminated strings
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct I2ChannelInfo {
+    pub name: [c_char; 33],
+    pub short_name: [c_char; 9],
+    pub unit: [c_char; 13],
+    /// Sample rate in Hz
+    pub sample_rate: u16,
+    pub sample_count: u32,
+}
+
+/// An ld file opened for reading
+#[derive(Debug)]
+pub struct I2Reader {
+    reader: LDReader<BufReader<File>>,
+    header: Header,
+    channels: Vec<ChannelMetadata>,
+}
+
+/// An ld file being built, written by [i2_writer_finish]
+#[derive(Debug)]
+pub struct I2Writer {
+    path: PathBuf,
+    header: Header,
+    channels: Vec<ChannelWithSamples>,
+}
+
+struct Failure {
+    status: I2Status,
+    message: String,
+}
+
+impl Failure {
+    fn new(status: I2Status, message: &str) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<I2Error> for Failure {
+    fn from(error: I2Error) -> Self {
+        let status = match error {
+            I2Error::IOError(_) => I2Status::Io,
+            I2Error::InvalidHeaderMarker { .. } => I2Status::InvalidHeaderMarker,
+            I2Error::UnrecognizedDatatype { .. } => I2Status::UnrecognizedDatatype,
+            I2Error::NonUtf8String(_) => I2Status::NonUtf8String,
+            I2Error::ChannelNotFound(_) => I2Status::ChannelNotFound,
+            I2Error::UnsupportedDatatype(_) => I2Status::UnsupportedDatatype,
+            _ => I2Status::Other,
+        };
+        Self {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, storing its error for [i2_last_error_message] and catching panics
+fn run(f: impl FnOnce() -> Result<(), Failure>) -> I2Status {
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(Failure::new(I2Status::Panic, "motec-i2 panicked")));
+    match result {
+        Ok(()) => I2Status::Ok,
+        Err(failure) => {
+            let message = CString::new(failure.message.replace('\0', "")).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+            failure.status
+        }
+    }
+}
+
+unsafe fn reference<'a, T>(ptr: *const T) -> Result<&'a T, Failure> {
+    ptr.as_ref()
+        .ok_or_else(|| Failure::new(I2Status::NullPointer, "Unexpected NULL pointer"))
+}
+
+unsafe fn reference_mut<'a, T>(ptr: *mut T) -> Result<&'a mut T, Failure> {
+    ptr.as_mut()
+        .ok_or_else(|| Failure::new(I2Status::NullPointer, "Unexpected NULL pointer"))
+}
+
+unsafe fn string<'a>(ptr: *const c_char) -> Result<&'a str, Failure> {
+    if ptr.is_null() {
+        return Err(Failure::new(
+            I2Status::NullPointer,
+            "Unexpected NULL string",
+        ));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| Failure::new(I2Status::InvalidArgument, "String is not valid UTF-8"))
+}
+
+/// Copies `value` into `field`, truncated to a character boundary so it stays NUL terminated
+fn copy_string(field: &mut [c_char], value: &str) {
+    let mut len = value.len().min(field.len() - 1);
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    for (c, b) in field.iter_mut().zip(&value.as_bytes()[..len]) {
+        *c = *b as c_char;
+    }
+    field[len..].fill(0);
+}
+
+fn field_string(field: &[c_char]) -> Result<String, Failure> {
+    let bytes: Vec<u8> = field
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+    String::from_utf8(bytes)
+        .map_err(|_| Failure::new(I2Status::InvalidArgument, "Header field is not valid UTF-8"))
+}
+
+fn channel_at(channels: &[ChannelMetadata], index: usize) -> Result<&ChannelMetadata, Failure> {
+    channels
+        .get(index)
+        .ok_or_else(|| Failure::new(I2Status::InvalidArgument, "Channel index out of range"))
+}
+
+/// Returns the message of the last error on this thread, or NULL if there was none
+///
+/// The string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn i2_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Fills `header` with empty fields and the device of files written by this library
+///
+/// # Safety
+///
+/// `header` must be NULL or point to a writable [I2Header].
+#[no_mangle]
+pub unsafe extern "C" fn i2_header_init(header: *mut I2Header) -> I2Status {
+    run(|| {
+        let header = reference_mut(header)?;
+        *header = I2Header {
+            device_serial: 0,
+            device_type: [0; 9],
+            device_version: 420,
+            num_channels: 0,
+            date: [0; 17],
+            time: [0; 17],
+            driver: [0; 65],
+            vehicle_id: [0; 65],
+            venue: [0; 65],
+            session: [0; 65],
+            short_comment: [0; 65],
+        };
+        copy_string(&mut header.device_type, "ADL");
+        Ok(())
+    })
+}
+
+/// Opens the ld file at `path` and reads its header and channel list
+///
+/// # Safety
+///
+/// `path` must be NULL or a NUL terminated string, `reader` must be NULL or writable. The
+/// handle stored in `reader` has to be released with [i2_reader_free].
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_open(
+    path: *const c_char,
+    reader: *mut *mut I2Reader,
+) -> I2Status {
+    run(|| {
+        let path = string(path)?;
+        let out = reference_mut(reader)?;
+
+        let mut reader = LDReader::open(path)?;
+        let header = reader.read_header()?;
+        let channels = reader.read_channels()?;
+        *out = Box::into_raw(Box::new(I2Reader {
+            reader,
+            header,
+            channels,
+        }));
+        Ok(())
+    })
+}
+
+/// Closes a reader, NULL is ignored
+///
+/// # Safety
+///
+/// `reader` must be NULL or a handle from [i2_reader_open] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_free(reader: *mut I2Reader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+/// Copies the header of the file into `header`
+///
+/// # Safety
+///
+/// `reader` must be NULL or a valid handle, `header` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_header(
+    reader: *const I2Reader,
+    header: *mut I2Header,
+) -> I2Status {
+    run(|| {
+        let source = &reference(reader)?.header;
+        let header = reference_mut(header)?;
+        header.device_serial = source.device_serial;
+        copy_string(&mut header.device_type, &source.device_type);
+        header.device_version = source.device_version;
+        header.num_channels = source.num_channels;
+        copy_string(&mut header.date, &source.date_string);
+        copy_string(&mut header.time, &source.time_string);
+        copy_string(&mut header.driver, &source.driver);
+        copy_string(&mut header.vehicle_id, &source.vehicleid);
+        copy_string(&mut header.venue, &source.venue);
+        copy_string(&mut header.session, &source.session);
+        copy_string(&mut header.short_comment, &source.short_comment);
+        Ok(())
+    })
+}
+
+/// Number of channels in the file, 0 if `reader` is NULL
+///
+/// # Safety
+///
+/// `reader` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_channel_count(reader: *const I2Reader) -> usize {
+    reader.as_ref().map_or(0, |reader| reader.channels.len())
+}
+
+/// Copies the metadata of channel `index` into `info`
+///
+/// # Safety
+///
+/// `reader` must be NULL or a valid handle, `info` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_channel_info(
+    reader: *const I2Reader,
+    index: usize,
+    info: *mut I2ChannelInfo,
+) -> I2Status {
+    run(|| {
+        let channel = channel_at(&reference(reader)?.channels, index)?;
+        let info = reference_mut(info)?;
+        copy_string(&mut info.name, &channel.name);
+        copy_string(&mut info.short_name, &channel.short_name);
+        copy_string(&mut info.unit, &channel.unit);
+        info.sample_rate = channel.sample_rate;
+        info.sample_count = channel.data_count;
+        Ok(())
+    })
+}
+
+/// Stores the index of the channel called `name` in `index`
+///
+/// # Safety
+///
+/// `reader` must be NULL or a valid handle, `name` must be NULL or a NUL terminated string
+/// and `index` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_find_channel(
+    reader: *const I2Reader,
+    name: *const c_char,
+    index: *mut usize,
+) -> I2Status {
+    run(|| {
+        let channels = &reference(reader)?.channels;
+        let name = string(name)?;
+        let index = reference_mut(index)?;
+        *index = channels
+            .iter()
+            .position(|channel| channel.name == name)
+            .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))?;
+        Ok(())
+    })
+}
+
+/// Decodes up to `len` physical values of channel `index` into `values`
+///
+/// The number of values written is stored in `written` if it isn't NULL.
+///
+/// # Safety
+///
+/// `reader` must be NULL or a valid handle, `values` must be NULL or point to `len` writable
+/// doubles and `written` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn i2_reader_read_values(
+    reader: *mut I2Reader,
+    index: usize,
+    values: *mut f64,
+    len: usize,
+    written: *mut usize,
+) -> I2Status {
+    run(|| {
+        let reader = reference_mut(reader)?;
+        let channel = channel_at(&reader.channels, index)?.clone();
+        if values.is_null() {
+            return Err(Failure::new(
+                I2Status::NullPointer,
+                "Unexpected NULL buffer",
+            ));
+        }
+
+        let count = len.min(channel.data_count as usize);
+        let values = slice::from_raw_parts_mut(values, count);
+        let bytes = reader.reader.channel_bytes(&channel)?;
+        channel.decode_bytes_into(&bytes, values)?;
+        if let Some(written) = written.as_mut() {
+            *written = count;
+        }
+        Ok(())
+    })
+}
+
+/// Starts a new ld file at `path` with the fields of `header`
+///
+/// Nothing is written until [i2_writer_finish].
+///
+/// # Safety
+///
+/// `path` must be NULL or a NUL terminated string, `header` must be NULL or a valid
+/// [I2Header] and `writer` must be NULL or writable. The handle stored in `writer` has to be
+/// released with [i2_writer_finish] or [i2_writer_free].
+#[no_mangle]
+pub unsafe extern "C" fn i2_writer_create(
+    path: *const c_char,
+    header: *const I2Header,
+    writer: *mut *mut I2Writer,
+) -> I2Status {
+    run(|| {
+        let path = PathBuf::from(string(path)?);
+        let header = reference(header)?;
+        let out = reference_mut(writer)?;
+
+        let header = Header {
+            channel_meta_ptr: 0,
+            channel_data_ptr: 0,
+            event_ptr: 0,
+            device_serial: header.device_serial,
+            device_type: field_string(&header.device_type)?,
+            device_version: header.device_version,
+            num_channels: 0,
+            date_string: field_string(&header.date)?,
+            time_string: field_string(&header.time)?,
+            driver: field_string(&header.driver)?,
+            vehicleid: field_string(&header.vehicle_id)?,
+            venue: field_string(&header.venue)?,
+            session: field_string(&header.session)?,
+            short_comment: field_string(&header.short_comment)?,
+        };
+        *out = Box::into_raw(Box::new(I2Writer {
+            path,
+            header,
+            channels: vec![],
+        }));
+        Ok(())
+    })
+}
+
+/// Adds a channel with `count` physical `values` sampled at `sample_rate` Hz
+///
+/// The datatype and decimal places are chosen to fit the values. `short_name` and `unit` may
+/// be NULL.
+///
+/// # Safety
+///
+/// `writer` must be NULL or a valid handle, the strings must be NULL or NUL terminated and
+/// `values` must be NULL or point to `count` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn i2_writer_add_channel(
+    writer: *mut I2Writer,
+    name: *const c_char,
+    short_name: *const c_char,
+    unit: *const c_char,
+    sample_rate: u16,
+    values: *const f64,
+    count: usize,
+) -> I2Status {
+    run(|| {
+        let writer = reference_mut(writer)?;
+        let name = string(name)?;
+        let short_name = match short_name.is_null() {
+            true => name.chars().take(8).collect(),
+            false => string(short_name)?.to_string(),
+        };
+        let unit = match unit.is_null() {
+            true => "",
+            false => string(unit)?,
+        };
+        if values.is_null() && count > 0 {
+            return Err(Failure::new(
+                I2Status::NullPointer,
+                "Unexpected NULL values",
+            ));
+        }
+        if sample_rate == 0 {
+            return Err(Failure::new(
+                I2Status::InvalidArgument,
+                "Sample rate must be at least 1 Hz",
+            ));
+        }
+        let values = match count {
+            0 => &[][..],
+            _ => slice::from_raw_parts(values, count),
+        };
+
+        let mut channel = ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: count as u32,
+            datatype: Datatype::I16,
+            sample_rate,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 0,
+            name: name.to_string(),
+            short_name,
+            unit: unit.to_string(),
+        };
+        channel.fit_to_values(values);
+        let samples = channel.encode_values(values)?;
+        writer.channels.push((channel, samples));
+        Ok(())
+    })
+}
+
+/// Writes the file and releases the writer, even if writing fails
+///
+/// # Safety
+///
+/// `writer` must be NULL or a handle from [i2_writer_create] that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn i2_writer_finish(writer: *mut I2Writer) -> I2Status {
+    run(|| {
+        reference(writer)?;
+        let writer = Box::from_raw(writer);
+
+        let mut ld = LDWriter::create(&writer.path, writer.header)?;
+        for (channel, samples) in writer.channels {
+            ld = ld.with_channel(channel, samples);
+        }
+        ld.write()?;
+        Ok(())
+    })
+}
+
+/// Releases a writer without writing the file, NULL is ignored
+///
+/// # Safety
+///
+/// `writer` must be NULL or a handle from [i2_writer_create] that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn i2_writer_free(writer: *mut I2Writer) {
+    if !writer.is_null() {
+        drop(Box::from_raw(writer));
+    }
+}
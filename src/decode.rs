@@ -0,0 +1,175 @@
+use crate::{ChannelMetadata, Datatype, I2Error, I2Result, Sample};
+use std::io;
+
+impl ChannelMetadata {
+    /// Factor that turns a raw sample of this channel into its final value
+    ///
+    /// This is the `scale`, `dec_places` and `mul` part of [Sample::decode_f64] folded into a
+    /// single multiplication. Channels with an `offset` aren't supported yet and return
+    /// [I2Error::UnsupportedOffset].
+    pub fn value_factor(&self) -> I2Result<f64> {
+        // TODO: Offset not yet supported
+        if self.offset != 0 {
+            return Err(I2Error::UnsupportedOffset(self.offset));
+        }
+        Ok(self.mul as f64 / self.scale as f64 * 10.0f64.powi(-self.dec_places as i32))
+    }
+
+    /// Decodes the raw little endian data of this channel into final values
+    ///
+    /// `bytes` is the data section of the channel, or a part of it starting at a sample
+    /// boundary. Gives the same values as [Sample::decode_f64] up to rounding, but without
+    /// going through [Sample] for every value.
+    pub fn decode_bytes(&self, bytes: &[u8]) -> I2Result<Vec<f64>> {
+        let mut values = vec![0.0; self.samples_in(bytes)];
+        self.decode_bytes_into(bytes, &mut values)?;
+        Ok(values)
+    }
+
+    /// Decodes the first `values.len()` samples of `bytes` into `values`, see
+    /// [ChannelMetadata::decode_bytes]
+    pub fn decode_bytes_into(&self, bytes: &[u8], values: &mut [f64]) -> I2Result<()> {
+        self.decode_raw(bytes, values, |value| value)
+    }
+
+    /// Decodes the raw data of this channel into `f32` values, see
+    /// [ChannelMetadata::decode_bytes]
+    pub fn decode_bytes_f32(&self, bytes: &[u8]) -> I2Result<Vec<f32>> {
+        let mut values = vec![0.0; self.samples_in(bytes)];
+        self.decode_bytes_f32_into(bytes, &mut values)?;
+        Ok(values)
+    }
+
+    /// Decodes the first `values.len()` samples of `bytes` into `values`, see
+    /// [ChannelMetadata::decode_bytes]
+    pub fn decode_bytes_f32_into(&self, bytes: &[u8], values: &mut [f32]) -> I2Result<()> {
+        self.decode_raw(bytes, values, |value| value as f32)
+    }
+
+    /// Decodes already read samples of this channel into final values
+    pub fn decode_samples(&self, samples: &[Sample]) -> I2Result<Vec<f64>> {
+        let factor = self.value_factor()?;
+        Ok(samples
+            .iter()
+            .map(|sample| match sample {
+                Sample::I16(v) => *v as f64 * factor,
+                Sample::I32(v) => *v as f64 * factor,
+                Sample::F32(v) => *v as f64 * factor,
+            })
+            .collect())
+    }
+
+    fn samples_in(&self, bytes: &[u8]) -> usize {
+        match self.datatype.size() {
+            0 => 0,
+            size => bytes.len() / size as usize,
+        }
+    }
+
+    /// Shared loop of the decode functions
+    ///
+    /// Every datatype gets its own loop over fixed size chunks, so the compiler can vectorize
+    /// the conversion.
+    fn decode_raw<T>(
+        &self,
+        bytes: &[u8],
+        out: &mut [T],
+        convert: impl Fn(f64) -> T,
+    ) -> I2Result<()> {
+        let factor = self.value_factor()?;
+        let size = self.datatype.size() as usize;
+        let bytes = bytes
+            .get(..out.len() * size)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+        match self.datatype {
+            Datatype::Beacon16 | Datatype::I16 => {
+                for (value, b) in out.iter_mut().zip(bytes.chunks_exact(2)) {
+                    *value = convert(i16::from_le_bytes([b[0], b[1]]) as f64 * factor);
+                }
+            }
+            Datatype::Beacon32 | Datatype::I32 => {
+                for (value, b) in out.iter_mut().zip(bytes.chunks_exact(4)) {
+                    *value = convert(i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 * factor);
+                }
+            }
+            Datatype::F32 => {
+                for (value, b) in out.iter_mut().zip(bytes.chunks_exact(4)) {
+                    *value = convert(f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 * factor);
+                }
+            }
+            Datatype::F16 | Datatype::Invalid if out.is_empty() => {}
+            Datatype::F16 | Datatype::Invalid => {
+                return Err(I2Error::UnsupportedDatatype(self.datatype.clone()))
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{I2Error, LDReader};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn decode_sample1_bytes() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader = LDReader::new(Cursor::new(bytes));
+
+        for channel in reader.read_channels().unwrap() {
+            let samples = reader.channel_data(&channel).unwrap();
+            let raw = reader.channel_bytes(&channel).unwrap();
+            let expected: Vec<f64> = samples.iter().map(|s| s.decode_f64(&channel)).collect();
+
+            let values = channel.decode_bytes(&raw).unwrap();
+            let values_f32 = channel.decode_bytes_f32(&raw).unwrap();
+            assert_eq!(values.len(), expected.len());
+            for ((value, value_f32), expected) in values.iter().zip(&values_f32).zip(&expected) {
+                assert!((value - expected).abs() <= expected.abs() * 1e-12);
+                assert!((*value_f32 as f64 - expected).abs() <= expected.abs() * 1e-6);
+            }
+            assert_eq!(channel.decode_samples(&samples).unwrap(), values);
+            assert_eq!(reader.channel_values(&channel).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn decode_bytes_into_slice() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader = LDReader::new(Cursor::new(bytes));
+        let channel = reader.read_channels().unwrap().remove(0);
+        let raw = reader.channel_bytes(&channel).unwrap();
+
+        let mut values = [0.0; 3];
+        channel.decode_bytes_into(&raw, &mut values).unwrap();
+        for (value, expected) in values.iter().zip([19.9, 19.9, 20.1]) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+
+        let mut values = vec![0.0; channel.data_count as usize + 1];
+        assert!(matches!(
+            channel.decode_bytes_into(&raw, &mut values),
+            Err(I2Error::IOError(_))
+        ));
+    }
+
+    #[test]
+    fn decode_offset_channel() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader = LDReader::new(Cursor::new(bytes));
+        let mut channel = reader.read_channels().unwrap().remove(0);
+        channel.offset = 5;
+
+        assert!(matches!(
+            reader.channel_values(&channel),
+            Err(I2Error::UnsupportedOffset(5))
+        ));
+        let samples = reader.channel_data(&channel).unwrap();
+        assert!(matches!(
+            channel.decode_samples(&samples),
+            Err(I2Error::UnsupportedOffset(5))
+        ));
+    }
+}
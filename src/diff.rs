@@ -0,0 +1,621 @@
+use crate::{ChannelMetadata, Event, Header, I2Result, LDReader, Sample, Session, Vehicle, Venue};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Read, Seek};
+
+/// Options for [diff_sessions]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffOptions {
+    /// Largest difference in physical units for two samples to be considered equal
+    ///
+    /// When this is `None` the raw samples have to match exactly. Channels with an `offset`
+    /// can't be decoded yet and are always compared exactly.
+    pub tolerance: Option<f64>,
+}
+
+impl DiffOptions {
+    /// Compares samples by their decoded value, allowing differences up to `tolerance`
+    pub fn with_tolerance(tolerance: f64) -> Self {
+        DiffOptions {
+            tolerance: Some(tolerance),
+        }
+    }
+}
+
+/// A field that has a different value in the two files, values are formatted with [fmt::Debug]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldDifference {
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+/// Where the samples of a channel first diverge
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataDifference {
+    /// Index of the first sample that differs
+    pub first_index: usize,
+    /// Time of the first sample that differs in seconds
+    pub first_time: f64,
+    /// Decoded values at `first_index`, `None` if that channel has fewer samples or can't be
+    /// decoded
+    pub left: Option<f64>,
+    pub right: Option<f64>,
+    /// Number of samples present in both channels that differ
+    pub differing_samples: usize,
+    /// Largest difference between two decoded samples, infinite if only one of them is NaN or
+    /// they can't be decoded
+    pub max_difference: f64,
+}
+
+/// The differences of a channel that exists in both files
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelDiff {
+    /// Name in the left file
+    pub name: String,
+    /// Name in the right file, differs from `name` if the channel was renamed
+    pub right_name: String,
+    pub metadata: Vec<FieldDifference>,
+    /// `None` if the samples are equal or the sample rates differ
+    pub data: Option<DataDifference>,
+}
+
+impl ChannelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.name == self.right_name && self.metadata.is_empty() && self.data.is_none()
+    }
+}
+
+/// The result of comparing two files with [diff_sessions] or [LDReader::diff]
+///
+/// The [fmt::Display] implementation prints a human readable report.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionDiff {
+    pub header: Vec<FieldDifference>,
+    pub event: Vec<FieldDifference>,
+    pub venue: Vec<FieldDifference>,
+    pub vehicle: Vec<FieldDifference>,
+    /// Channels only present in the right file
+    pub added: Vec<String>,
+    /// Channels only present in the left file
+    pub removed: Vec<String>,
+    /// Channels present in both files that differ, including renamed channels
+    pub channels: Vec<ChannelDiff>,
+}
+
+impl SessionDiff {
+    /// Returns true if no differences were found
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty()
+            && self.event.is_empty()
+            && self.venue.is_empty()
+            && self.vehicle.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.channels.is_empty()
+    }
+
+    /// Channels that were matched by their short name, as pairs of left and right name
+    pub fn renamed(&self) -> Vec<(&str, &str)> {
+        self.channels
+            .iter()
+            .filter(|channel| channel.name != channel.right_name)
+            .map(|channel| (channel.name.as_str(), channel.right_name.as_str()))
+            .collect()
+    }
+}
+
+impl fmt::Display for SessionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        for (block, fields) in [
+            ("Header", &self.header),
+            ("Event", &self.event),
+            ("Venue", &self.venue),
+            ("Vehicle", &self.vehicle),
+        ] {
+            if !fields.is_empty() {
+                writeln!(f, "{}:", block)?;
+                for field in fields {
+                    writeln!(f, "  {}: {} -> {}", field.field, field.left, field.right)?;
+                }
+            }
+        }
+
+        for name in &self.removed {
+            writeln!(f, "Removed channel \"{}\"", name)?;
+        }
+        for name in &self.added {
+            writeln!(f, "Added channel \"{}\"", name)?;
+        }
+
+        for channel in &self.channels {
+            if channel.name != channel.right_name {
+                writeln!(
+                    f,
+                    "Channel \"{}\" renamed to \"{}\":",
+                    channel.name, channel.right_name
+                )?;
+            } else {
+                writeln!(f, "Channel \"{}\":", channel.name)?;
+            }
+            for field in &channel.metadata {
+                writeln!(f, "  {}: {} -> {}", field.field, field.left, field.right)?;
+            }
+            if let Some(data) = &channel.data {
+                let value = |value: Option<f64>| match value {
+                    Some(value) => value.to_string(),
+                    None => "missing".to_string(),
+                };
+                writeln!(
+                    f,
+                    "  data differs from {:.3}s (sample {}): {} -> {}, {} samples differ, max difference {}",
+                    data.first_time,
+                    data.first_index,
+                    value(data.left),
+                    value(data.right),
+                    data.differing_samples,
+                    data.max_difference
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares two sessions
+///
+/// Channels are matched by name, channels without a match are then matched by short name and
+/// reported as renamed. Addresses and other layout fields are not compared, since they change
+/// whenever anything else in the file does. Samples are only compared if the sample rates match.
+pub fn diff_sessions(left: &Session, right: &Session, options: &DiffOptions) -> SessionDiff {
+    let mut diff = SessionDiff {
+        header: diff_header(&left.header, &right.header),
+        event: diff_block(left.event.as_ref(), right.event.as_ref(), diff_event),
+        venue: diff_block(left.venue.as_ref(), right.venue.as_ref(), diff_venue),
+        vehicle: diff_block(left.vehicle.as_ref(), right.vehicle.as_ref(), diff_vehicle),
+        ..Default::default()
+    };
+
+    let mut pairs = vec![];
+    let mut unmatched_left = vec![];
+    let mut matched_right = HashSet::new();
+    for (i, (channel, _)) in left.channels.iter().enumerate() {
+        match right
+            .channels
+            .iter()
+            .position(|(other, _)| other.name == channel.name)
+        {
+            Some(j) if matched_right.insert(j) => pairs.push((i, j)),
+            _ => unmatched_left.push(i),
+        }
+    }
+    for i in unmatched_left {
+        let channel = &left.channels[i].0;
+        let renamed = right
+            .channels
+            .iter()
+            .enumerate()
+            .position(|(j, (other, _))| {
+                !channel.short_name.is_empty()
+                    && other.short_name == channel.short_name
+                    && !matched_right.contains(&j)
+                    && !left.channels.iter().any(|(c, _)| c.name == other.name)
+            });
+        match renamed {
+            Some(j) => {
+                matched_right.insert(j);
+                pairs.push((i, j));
+            }
+            None => diff.removed.push(channel.name.clone()),
+        }
+    }
+    diff.added = right
+        .channels
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !matched_right.contains(j))
+        .map(|(_, (channel, _))| channel.name.clone())
+        .collect();
+
+    pairs.sort();
+    for (i, j) in pairs {
+        let (left_channel, left_samples) = &left.channels[i];
+        let (right_channel, right_samples) = &right.channels[j];
+        let channel = ChannelDiff {
+            name: left_channel.name.clone(),
+            right_name: right_channel.name.clone(),
+            metadata: diff_channel(left_channel, right_channel),
+            data: if left_channel.sample_rate == right_channel.sample_rate {
+                diff_data(
+                    (left_channel, left_samples),
+                    (right_channel, right_samples),
+                    options,
+                )
+            } else {
+                None
+            },
+        };
+        if !channel.is_empty() {
+            diff.channels.push(channel);
+        }
+    }
+
+    diff
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Reads both files completely and compares them with [diff_sessions]
+    pub fn diff<T: Read + Seek>(
+        &mut self,
+        other: &mut LDReader<T>,
+        options: &DiffOptions,
+    ) -> I2Result<SessionDiff> {
+        let left = self.read_session()?;
+        let right = other.read_session()?;
+        Ok(diff_sessions(&left, &right, options))
+    }
+}
+
+fn compare<T: PartialEq + fmt::Debug>(
+    differences: &mut Vec<FieldDifference>,
+    field: &str,
+    left: &T,
+    right: &T,
+) {
+    if left != right {
+        differences.push(FieldDifference {
+            field: field.to_string(),
+            left: format!("{:?}", left),
+            right: format!("{:?}", right),
+        });
+    }
+}
+
+fn diff_block<T>(
+    left: Option<&T>,
+    right: Option<&T>,
+    fields: fn(&T, &T) -> Vec<FieldDifference>,
+) -> Vec<FieldDifference> {
+    match (left, right) {
+        (Some(left), Some(right)) => fields(left, right),
+        (None, None) => vec![],
+        (left, right) => {
+            let present = |block: Option<&T>| match block {
+                Some(_) => "present".to_string(),
+                None => "missing".to_string(),
+            };
+            vec![FieldDifference {
+                field: "block".to_string(),
+                left: present(left),
+                right: present(right),
+            }]
+        }
+    }
+}
+
+fn diff_header(left: &Header, right: &Header) -> Vec<FieldDifference> {
+    let mut differences = vec![];
+    compare(
+        &mut differences,
+        "device_serial",
+        &left.device_serial,
+        &right.device_serial,
+    );
+    compare(
+        &mut differences,
+        "device_type",
+        &left.device_type,
+        &right.device_type,
+    );
+    compare(
+        &mut differences,
+        "device_version",
+        &left.device_version,
+        &right.device_version,
+    );
+    compare(
+        &mut differences,
+        "num_channels",
+        &left.num_channels,
+        &right.num_channels,
+    );
+    compare(
+        &mut differences,
+        "date_string",
+        &left.date_string,
+        &right.date_string,
+    );
+    compare(
+        &mut differences,
+        "time_string",
+        &left.time_string,
+        &right.time_string,
+    );
+    compare(&mut differences, "driver", &left.driver, &right.driver);
+    compare(
+        &mut differences,
+        "vehicleid",
+        &left.vehicleid,
+        &right.vehicleid,
+    );
+    compare(&mut differences, "venue", &left.venue, &right.venue);
+    compare(&mut differences, "session", &left.session, &right.session);
+    compare(
+        &mut differences,
+        "short_comment",
+        &left.short_comment,
+        &right.short_comment,
+    );
+    differences
+}
+
+fn diff_event(left: &Event, right: &Event) -> Vec<FieldDifference> {
+    let mut differences = vec![];
+    compare(&mut differences, "name", &left.name, &right.name);
+    compare(&mut differences, "session", &left.session, &right.session);
+    compare(&mut differences, "comment", &left.comment, &right.comment);
+    differences
+}
+
+fn diff_venue(left: &Venue, right: &Venue) -> Vec<FieldDifference> {
+    let mut differences = vec![];
+    compare(&mut differences, "name", &left.name, &right.name);
+    differences
+}
+
+fn diff_vehicle(left: &Vehicle, right: &Vehicle) -> Vec<FieldDifference> {
+    let mut differences = vec![];
+    compare(&mut differences, "id", &left.id, &right.id);
+    compare(&mut differences, "weight", &left.weight, &right.weight);
+    compare(&mut differences, "type", &left._type, &right._type);
+    compare(&mut differences, "comment", &left.comment, &right.comment);
+    differences
+}
+
+fn diff_channel(left: &ChannelMetadata, right: &ChannelMetadata) -> Vec<FieldDifference> {
+    let mut differences = vec![];
+    compare(
+        &mut differences,
+        "data_count",
+        &left.data_count,
+        &right.data_count,
+    );
+    compare(
+        &mut differences,
+        "datatype",
+        &left.datatype,
+        &right.datatype,
+    );
+    compare(
+        &mut differences,
+        "sample_rate",
+        &left.sample_rate,
+        &right.sample_rate,
+    );
+    compare(&mut differences, "offset", &left.offset, &right.offset);
+    compare(&mut differences, "mul", &left.mul, &right.mul);
+    compare(&mut differences, "scale", &left.scale, &right.scale);
+    compare(
+        &mut differences,
+        "dec_places",
+        &left.dec_places,
+        &right.dec_places,
+    );
+    compare(
+        &mut differences,
+        "short_name",
+        &left.short_name,
+        &right.short_name,
+    );
+    compare(&mut differences, "unit", &left.unit, &right.unit);
+    differences
+}
+
+fn diff_data(
+    (left_channel, left): (&ChannelMetadata, &[Sample]),
+    (right_channel, right): (&ChannelMetadata, &[Sample]),
+    options: &DiffOptions,
+) -> Option<DataDifference> {
+    let mut first_index = None;
+    let mut differing_samples = 0;
+    let mut max_difference: f64 = 0.0;
+    let decodable = decode_supported(left_channel) && decode_supported(right_channel);
+    for (i, (l, r)) in left.iter().zip(right).enumerate() {
+        let difference = || match (decode(l, left_channel), decode(r, right_channel)) {
+            (Some(l), Some(r)) => value_difference(l, r),
+            _ => f64::INFINITY,
+        };
+        // Only decode samples when comparing with a tolerance or when they already differ
+        let differs = match options.tolerance {
+            Some(tolerance) if decodable => Some(difference()).filter(|d| *d > tolerance),
+            _ => (!same_sample(l, r)).then(difference),
+        };
+        if let Some(difference) = differs {
+            first_index.get_or_insert(i);
+            differing_samples += 1;
+            max_difference = max_difference.max(difference);
+        }
+    }
+
+    if first_index.is_none() && left.len() != right.len() {
+        first_index = Some(left.len().min(right.len()));
+    }
+    let first_index = first_index?;
+    let value = |samples: &[Sample], channel| {
+        samples
+            .get(first_index)
+            .and_then(|sample| decode(sample, channel))
+    };
+    Some(DataDifference {
+        first_index,
+        first_time: first_index as f64 / left_channel.sample_rate.max(1) as f64,
+        left: value(left, left_channel),
+        right: value(right, right_channel),
+        differing_samples,
+        max_difference,
+    })
+}
+
+fn decode_supported(channel: &ChannelMetadata) -> bool {
+    // TODO: Offset not yet supported by Sample::decode_f64
+    channel.offset == 0
+}
+
+fn decode(sample: &Sample, channel: &ChannelMetadata) -> Option<f64> {
+    decode_supported(channel).then(|| sample.decode_f64(channel))
+}
+
+/// Compares raw samples, F32 samples are compared bitwise so that NaN equals itself
+fn same_sample(left: &Sample, right: &Sample) -> bool {
+    match (left, right) {
+        (Sample::F32(l), Sample::F32(r)) => l.to_bits() == r.to_bits(),
+        (l, r) => l == r,
+    }
+}
+
+/// Difference between two decoded values where NaN only matches NaN
+fn value_difference(left: f64, right: f64) -> f64 {
+    match (left.is_nan(), right.is_nan()) {
+        (true, true) => 0.0,
+        (true, false) | (false, true) => f64::INFINITY,
+        _ if left == right => 0.0,
+        _ => (left - right).abs(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_sessions, DiffOptions};
+    use crate::{Datatype, LDReader, Sample};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn diff_sample1() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes.clone());
+        let mut other = Cursor::new(bytes);
+        let diff = LDReader::new(&mut cursor)
+            .diff(&mut LDReader::new(&mut other), &DiffOptions::default())
+            .unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+
+        let left = LDReader::new(&mut cursor).read_session().unwrap();
+        let mut right = left.clone();
+        right.header.driver = "Someone Else".to_string();
+        right.venue = None;
+        let (channel, _) = right.channels.remove(0);
+        right.channels[0].0.name = "Renamed".to_string();
+        right.channels[1].0.unit = "furlong".to_string();
+
+        let (rpm, samples) = right
+            .channels
+            .iter_mut()
+            .find(|(channel, _)| channel.name == "Engine RPM")
+            .unwrap();
+        let Sample::I16(value) = samples[50] else {
+            panic!("Engine RPM should be stored as I16");
+        };
+        samples[50] = Sample::I16(value + 1);
+        let original = left.channel("Engine RPM").unwrap().1[50].decode_f64(rpm);
+        let step = (samples[50].decode_f64(rpm) - original).abs();
+        samples.truncate(100);
+        rpm.data_count = 100;
+
+        let diff = diff_sessions(&left, &right, &DiffOptions::default());
+        assert_eq!(diff.header.len(), 1);
+        assert_eq!(diff.header[0].field, "driver");
+        assert_eq!(diff.venue[0].right, "missing");
+        assert_eq!(diff.removed, vec![channel.name.clone()]);
+        assert!(diff.added.is_empty());
+        assert_eq!(
+            diff.renamed(),
+            vec![(left.channels[1].0.name.as_str(), "Renamed")]
+        );
+
+        let units = &diff.channels[1];
+        assert_eq!(units.metadata[0].field, "unit");
+        assert!(units.data.is_none());
+
+        let rpm = diff
+            .channels
+            .iter()
+            .find(|channel| channel.name == "Engine RPM")
+            .unwrap();
+        let data = rpm.data.as_ref().unwrap();
+        assert_eq!(data.first_index, 50);
+        assert_eq!(data.differing_samples, 1);
+        assert!((data.max_difference - step).abs() < 1e-9);
+        assert_eq!(rpm.metadata[0].field, "data_count");
+
+        let tolerant = diff_sessions(&left, &right, &DiffOptions::with_tolerance(step * 2.0));
+        let data = tolerant
+            .channels
+            .iter()
+            .find(|channel| channel.name == "Engine RPM")
+            .and_then(|channel| channel.data.clone())
+            .unwrap();
+        assert_eq!(data.first_index, 100);
+        assert_eq!(data.right, None);
+
+        let report = diff.to_string();
+        assert!(report.contains("driver: "));
+        assert!(report.contains(&format!("Removed channel \"{}\"", channel.name)));
+        assert!(report.contains("renamed to \"Renamed\""));
+        assert!(report.contains("data differs from "));
+    }
+
+    #[test]
+    fn diff_nan_samples() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut left = LDReader::new(&mut Cursor::new(bytes))
+            .read_session()
+            .unwrap();
+        let (channel, samples) = &mut left.channels[0];
+        channel.datatype = Datatype::F32;
+        channel.data_count = 3;
+        *samples = vec![Sample::F32(1.0), Sample::F32(f32::NAN), Sample::F32(3.0)];
+
+        assert!(diff_sessions(&left, &left, &DiffOptions::default()).is_empty());
+        assert!(diff_sessions(&left, &left, &DiffOptions::with_tolerance(0.1)).is_empty());
+
+        let mut right = left.clone();
+        right.channels[0].1[1] = Sample::F32(1.0);
+        for options in [DiffOptions::default(), DiffOptions::with_tolerance(0.1)] {
+            let diff = diff_sessions(&left, &right, &options);
+            let data = diff.channels[0].data.as_ref().unwrap();
+            assert_eq!(data.first_index, 1);
+            assert_eq!(data.differing_samples, 1);
+            assert_eq!(data.max_difference, f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn diff_offset_channel() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut left = LDReader::new(&mut Cursor::new(bytes))
+            .read_session()
+            .unwrap();
+        left.channels[0].0.offset = 10;
+        for options in [DiffOptions::default(), DiffOptions::with_tolerance(0.1)] {
+            assert!(diff_sessions(&left, &left, &options).is_empty());
+        }
+
+        let mut right = left.clone();
+        right.channels[0].1[2] = Sample::I16(1000);
+        let diff = diff_sessions(&left, &right, &DiffOptions::with_tolerance(0.1));
+        let data = diff.channels[0].data.as_ref().unwrap();
+        assert_eq!(data.first_index, 2);
+        assert_eq!(data.left, None);
+        assert_eq!(data.max_difference, f64::INFINITY);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    ChannelMetadata, ChannelWithSamples, Event, Header, I2Result, LDReader, LDWriter, Vehicle,
+    Venue,
+};
+use std::io::{Read, Seek, Write};
+
+/// All the metadata of a session, without any channel data
+///
+/// With the `serde` feature this can be stored as JSON and used as a template for new files with
+/// [SessionDocument::writer].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionDocument {
+    pub header: Header,
+    pub event: Option<Event>,
+    pub venue: Option<Venue>,
+    pub vehicle: Option<Vehicle>,
+    pub channels: Vec<ChannelMetadata>,
+}
+
+impl SessionDocument {
+    /// Finds the metadata of a channel by name
+    pub fn channel(&self, name: &str) -> Option<&ChannelMetadata> {
+        self.channels.iter().find(|channel| channel.name == name)
+    }
+
+    /// Creates a writer with the header, event, venue and vehicle of this document
+    ///
+    /// Channels are not added, since the document doesn't hold any samples. Use
+    /// [SessionDocument::channel] to get the metadata for [LDWriter::with_channel].
+    pub fn writer<S: Write + Seek>(&self, sink: S) -> LDWriter<S> {
+        let mut writer = LDWriter::new(sink, self.header.clone());
+        if let Some(event) = &self.event {
+            writer = writer.with_event(event.clone());
+        }
+        if let Some(venue) = &self.venue {
+            writer = writer.with_venue(venue.clone());
+        }
+        if let Some(vehicle) = &self.vehicle {
+            writer = writer.with_vehicle(vehicle.clone());
+        }
+        writer
+    }
+
+    /// Serializes the document as pretty printed JSON
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> I2Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parses a document from JSON
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> I2Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// A whole session loaded into memory, including the samples of every channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub header: Header,
+    pub event: Option<Event>,
+    pub venue: Option<Venue>,
+    pub vehicle: Option<Vehicle>,
+    pub channels: Vec<ChannelWithSamples>,
+}
+
+impl Session {
+    /// Finds a channel and its samples by name
+    pub fn channel(&self, name: &str) -> Option<&ChannelWithSamples> {
+        self.channels
+            .iter()
+            .find(|(channel, _)| channel.name == name)
+    }
+
+    /// Duration of the longest channel in seconds
+    pub fn duration(&self) -> f64 {
+        self.channels
+            .iter()
+            .filter(|(channel, _)| channel.sample_rate > 0)
+            .map(|(channel, samples)| samples.len() as f64 / channel.sample_rate as f64)
+            .fold(0.0, f64::max)
+    }
+
+    /// Writes the session with [LDWriter]
+    pub fn write<S: Write + Seek>(&self, sink: &mut S) -> I2Result<()> {
+        let mut writer = LDWriter::new(sink, self.header.clone());
+        if let Some(event) = &self.event {
+            writer = writer.with_event(event.clone());
+        }
+        if let Some(venue) = &self.venue {
+            writer = writer.with_venue(venue.clone());
+        }
+        if let Some(vehicle) = &self.vehicle {
+            writer = writer.with_vehicle(vehicle.clone());
+        }
+        for (channel, samples) in &self.channels {
+            writer = writer.with_channel(channel.clone(), samples.clone());
+        }
+        writer.write()
+    }
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Reads the header, event, venue, vehicle and channel metadata into a [SessionDocument]
+    pub fn read_session_document(&mut self) -> I2Result<SessionDocument> {
+        Ok(SessionDocument {
+            header: self.read_header()?,
+            event: self.read_event()?,
+            venue: self.read_venue()?,
+            vehicle: self.read_vehicle()?,
+            channels: self.read_channels()?,
+        })
+    }
+
+    /// Reads the whole file, including the samples of every channel, into a [Session]
+    pub fn read_session(&mut self) -> I2Result<Session> {
+        let document = self.read_session_document()?;
+        let data = self.read_all_channel_data(&document.channels)?;
+        let channels = document.channels.into_iter().zip(data).collect();
+
+        Ok(Session {
+            header: document.header,
+            event: document.event,
+            venue: document.venue,
+            vehicle: document.vehicle,
+            channels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LDReader;
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn sample1_document_as_template() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let mut document = reader.read_session_document().unwrap();
+        assert_eq!(document.channels.len(), 78);
+        assert_eq!(document.venue.as_ref().unwrap().name, "Calder");
+
+        let rpm = document.channel("Engine RPM").unwrap().clone();
+        let samples = reader.channel_data(&rpm).unwrap();
+
+        document.event.as_mut().unwrap().name = "Template Day".to_string();
+        let mut output = Cursor::new(Vec::new());
+        document
+            .writer(&mut output)
+            .with_channel(rpm.clone(), samples.clone())
+            .write()
+            .unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        let written = reader.read_session_document().unwrap();
+        assert_eq!(written.event, document.event);
+        assert_eq!(written.venue, document.venue);
+        assert_eq!(written.vehicle, document.vehicle);
+        assert_eq!(written.channels[0].name, "Engine RPM");
+        assert_eq!(reader.channel_data(&written.channels[0]).unwrap(), samples);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sample1_document_json_round_trip() {
+        use crate::SessionDocument;
+
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let document = reader.read_session_document().unwrap();
+        let json = document.to_json().unwrap();
+        assert!(json.contains("\"venue\": \"Calder\""));
+        assert!(json.contains("\"type\": "));
+
+        let parsed = SessionDocument::from_json(&json).unwrap();
+        assert_eq!(parsed, document);
+    }
+}
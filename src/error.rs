@@ -1,3 +1,4 @@
+use crate::Datatype;
 use std::error::Error;
 use std::fmt;
 use std::io;
@@ -13,6 +14,42 @@ pub enum I2Error {
     InvalidHeaderMarker { found: u32, expected: u32 },
     UnrecognizedDatatype { _type: u16, size: u16 },
     NonUtf8String(Utf8Error),
+
+    // Channel Errors
+    ChannelNotFound(String),
+    UnsupportedDatatype(Datatype),
+    UnsupportedOffset(u16),
+
+    // Math Channel Errors
+    InvalidExpression { position: usize, message: String },
+
+    // Filter Errors
+    InvalidFilter(String),
+
+    // Spectral Analysis Errors
+    InvalidSpectrum(String),
+
+    // Unit Errors
+    UnknownUnit(String),
+    IncompatibleUnits { from: String, to: String },
+
+    // Export Errors
+    ArrowError(String),
+    SerializationError(String),
+
+    // Template Errors
+    InvalidTemplate(Vec<String>),
+
+    // Merge Errors
+    InvalidMerge(String),
+    ChannelNameCollision(String),
+    InvalidTimeRange { start: f64, end: f64 },
+
+    // Import Errors
+    InvalidIbtFile(String),
+
+    // Journal Errors
+    InvalidJournal(String),
 }
 
 impl fmt::Display for I2Error {
@@ -30,6 +67,40 @@ impl fmt::Display for I2Error {
                 _type, size
             ),
             I2Error::NonUtf8String(e) => write!(f, "Attempted to decode non utf8 string: {}", e),
+            I2Error::ChannelNotFound(name) => write!(f, "Channel not found: {}", name),
+            I2Error::UnsupportedDatatype(datatype) => {
+                write!(f, "Unsupported datatype for this operation: {:?}", datatype)
+            }
+            I2Error::UnsupportedOffset(offset) => {
+                write!(f, "Channel offsets are not supported, found {}", offset)
+            }
+            I2Error::InvalidExpression { position, message } => write!(
+                f,
+                "Invalid math expression at position {}: {}",
+                position, message
+            ),
+            I2Error::InvalidFilter(message) => write!(f, "Invalid filter: {}", message),
+            I2Error::InvalidSpectrum(message) => {
+                write!(f, "Invalid spectral analysis: {}", message)
+            }
+            I2Error::UnknownUnit(unit) => write!(f, "Unknown unit: \"{}\"", unit),
+            I2Error::IncompatibleUnits { from, to } => {
+                write!(f, "Cannot convert from \"{}\" to \"{}\"", from, to)
+            }
+            I2Error::ArrowError(message) => write!(f, "Arrow error: {}", message),
+            I2Error::SerializationError(message) => write!(f, "Serialization error: {}", message),
+            I2Error::InvalidTemplate(problems) => {
+                write!(f, "Invalid template: {}", problems.join("; "))
+            }
+            I2Error::InvalidMerge(message) => write!(f, "Invalid merge: {}", message),
+            I2Error::ChannelNameCollision(name) => {
+                write!(f, "Channel name \"{}\" is used by more than one file", name)
+            }
+            I2Error::InvalidTimeRange { start, end } => {
+                write!(f, "Invalid time range from {}s to {}s", start, end)
+            }
+            I2Error::InvalidIbtFile(message) => write!(f, "Invalid ibt file: {}", message),
+            I2Error::InvalidJournal(message) => write!(f, "Invalid journal: {}", message),
         }
     }
 }
@@ -47,3 +118,10 @@ impl From<Utf8Error> for I2Error {
         I2Error::NonUtf8String(e)
     }
 }
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for I2Error {
+    fn from(e: serde_json::Error) -> Self {
+        I2Error::SerializationError(e.to_string())
+    }
+}
@@ -0,0 +1,472 @@
+use crate::{ChannelMetadata, I2Error, I2Result, LDReader};
+use std::f64::consts::PI;
+use std::io::{Read, Seek};
+
+/// A digital filter that can be applied to decoded channel values
+///
+/// Window lengths and time constants are in seconds and cutoff frequencies in Hz, they are
+/// converted to samples using the sample rate of the channel.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Filter {
+    /// Centered moving average over a window of `window` seconds
+    MovingAverage { window: f64 },
+    /// First order exponential smoothing with a time constant of `time_constant` seconds
+    Exponential { time_constant: f64 },
+    /// Butterworth filter of the given order
+    ///
+    /// When `zero_phase` is set the filter is run forwards and backwards over the data, this
+    /// removes the phase delay and doubles the effective order of the filter.
+    Butterworth {
+        band: FilterBand,
+        order: usize,
+        zero_phase: bool,
+    },
+    /// Centered median over a window of `window` seconds, useful to remove spikes
+    Median { window: f64 },
+}
+
+/// Frequency band of a [Filter::Butterworth] filter, frequencies are in Hz
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FilterBand {
+    LowPass(f64),
+    HighPass(f64),
+    /// Implemented as a high-pass at the lower frequency followed by a low-pass at the
+    /// upper frequency
+    BandPass(f64, f64),
+}
+
+impl Filter {
+    /// Filters `values` recorded at `sample_rate` Hz
+    pub fn apply(&self, values: &[f64], sample_rate: u16) -> I2Result<Vec<f64>> {
+        if sample_rate == 0 {
+            return Err(I2Error::InvalidFilter(
+                "Channel has a sample rate of 0 Hz".into(),
+            ));
+        }
+        let rate = sample_rate as f64;
+
+        Ok(match self {
+            Filter::MovingAverage { window } => {
+                moving_average(values, window_samples(*window, rate)?)
+            }
+            Filter::Exponential { time_constant } => {
+                if *time_constant < 0.0 {
+                    return Err(I2Error::InvalidFilter(
+                        "Time constant must not be negative".into(),
+                    ));
+                }
+                exponential(values, 1.0 - (-1.0 / (rate * time_constant)).exp())
+            }
+            Filter::Butterworth {
+                band,
+                order,
+                zero_phase,
+            } => {
+                let sections = butterworth_sections(*band, *order, rate)?;
+                if *zero_phase {
+                    filter_forward_backward(&sections, values)
+                } else {
+                    filter_sections(&sections, values)
+                }
+            }
+            Filter::Median { window } => median(values, window_samples(*window, rate)?),
+        })
+    }
+
+    /// Reads a channel from `reader` and filters it
+    pub fn apply_channel<S: Read + Seek>(
+        &self,
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+    ) -> I2Result<Vec<f64>> {
+        let values = reader.channel_values(channel)?;
+        self.apply(&values, channel.sample_rate)
+    }
+}
+
+fn window_samples(window: f64, rate: f64) -> I2Result<usize> {
+    if window < 0.0 || !window.is_finite() {
+        return Err(I2Error::InvalidFilter(format!(
+            "Invalid window length: {}",
+            window
+        )));
+    }
+    Ok(((window * rate).round() as usize).max(1))
+}
+
+/// Centered moving average over `samples` samples, the window shrinks near the edges
+pub(crate) fn moving_average(values: &[f64], samples: usize) -> Vec<f64> {
+    let half = samples / 2;
+    let n = values.len();
+    (0..n)
+        .map(|i| {
+            let start = i.saturating_sub(half);
+            let end = (i + half + 1).min(n);
+            values[start..end].iter().sum::<f64>() / (end - start) as f64
+        })
+        .collect()
+}
+
+fn exponential(values: &[f64], alpha: f64) -> Vec<f64> {
+    let mut state = None;
+    values
+        .iter()
+        .map(|value| {
+            let filtered = match state {
+                Some(prev) => prev + alpha * (value - prev),
+                None => *value,
+            };
+            state = Some(filtered);
+            filtered
+        })
+        .collect()
+}
+
+fn median(values: &[f64], samples: usize) -> Vec<f64> {
+    let half = samples / 2;
+    let n = values.len();
+    let mut window = Vec::with_capacity(2 * half + 1);
+    (0..n)
+        .map(|i| {
+            let start = i.saturating_sub(half);
+            let end = (i + half + 1).min(n);
+            window.clear();
+            window.extend_from_slice(&values[start..end]);
+            window.sort_by(f64::total_cmp);
+
+            let mid = window.len() / 2;
+            if window.len() % 2 == 0 {
+                (window[mid - 1] + window[mid]) / 2.0
+            } else {
+                window[mid]
+            }
+        })
+        .collect()
+}
+
+/// A second order IIR filter section in transposed direct form II
+///
+/// First order sections have `b2` and `a2` set to 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad {
+    /// Gain of this section for a constant input
+    fn dc_gain(&self) -> f64 {
+        (self.b0 + self.b1 + self.b2) / (1.0 + self.a1 + self.a2)
+    }
+
+    /// Filters `values` starting from the steady state for the first input value, this avoids
+    /// a large transient at the start of the channel.
+    fn filter(&self, values: &[f64]) -> Vec<f64> {
+        let Some(first) = values.first() else {
+            return vec![];
+        };
+        let steady = first * self.dc_gain();
+        let mut z1 = steady - self.b0 * first;
+        let mut z2 = self.b2 * first - self.a2 * steady;
+
+        values
+            .iter()
+            .map(|x| {
+                let y = self.b0 * x + z1;
+                z1 = self.b1 * x - self.a1 * y + z2;
+                z2 = self.b2 * x - self.a2 * y;
+                y
+            })
+            .collect()
+    }
+}
+
+fn butterworth_sections(band: FilterBand, order: usize, rate: f64) -> I2Result<Vec<Biquad>> {
+    if order == 0 {
+        return Err(I2Error::InvalidFilter(
+            "Filter order must be at least 1".into(),
+        ));
+    }
+
+    let check = |frequency: f64| {
+        if frequency > 0.0 && frequency < rate / 2.0 {
+            Ok(())
+        } else {
+            Err(I2Error::InvalidFilter(format!(
+                "Cutoff frequency {} Hz must be between 0 Hz and the Nyquist frequency ({} Hz)",
+                frequency,
+                rate / 2.0
+            )))
+        }
+    };
+
+    Ok(match band {
+        FilterBand::LowPass(cutoff) => {
+            check(cutoff)?;
+            butterworth_pass(cutoff, order, rate, false)
+        }
+        FilterBand::HighPass(cutoff) => {
+            check(cutoff)?;
+            butterworth_pass(cutoff, order, rate, true)
+        }
+        FilterBand::BandPass(low, high) => {
+            check(low)?;
+            check(high)?;
+            if low >= high {
+                return Err(I2Error::InvalidFilter(format!(
+                    "Band pass lower frequency {} Hz must be below the upper frequency {} Hz",
+                    low, high
+                )));
+            }
+            let mut sections = butterworth_pass(low, order, rate, true);
+            sections.extend(butterworth_pass(high, order, rate, false));
+            sections
+        }
+    })
+}
+
+/// Designs a low or high pass Butterworth filter as a cascade of biquads using the bilinear
+/// transform with frequency pre-warping
+fn butterworth_pass(cutoff: f64, order: usize, rate: f64, high_pass: bool) -> Vec<Biquad> {
+    let w0 = 2.0 * PI * cutoff / rate;
+    let (sin, cos) = w0.sin_cos();
+
+    let mut sections: Vec<Biquad> = (0..order / 2)
+        .map(|k| {
+            // Each pair of complex conjugate analog poles becomes one section with its own Q
+            let q = 1.0 / (2.0 * (PI * (2 * k + 1) as f64 / (2 * order) as f64).sin());
+            let alpha = sin / (2.0 * q);
+            let a0 = 1.0 + alpha;
+            let (b0, b1) = if high_pass {
+                ((1.0 + cos) / 2.0, -(1.0 + cos))
+            } else {
+                ((1.0 - cos) / 2.0, 1.0 - cos)
+            };
+            Biquad {
+                b0: b0 / a0,
+                b1: b1 / a0,
+                b2: b0 / a0,
+                a1: -2.0 * cos / a0,
+                a2: (1.0 - alpha) / a0,
+            }
+        })
+        .collect();
+
+    if order % 2 == 1 {
+        let k = (w0 / 2.0).tan();
+        let a1 = (k - 1.0) / (k + 1.0);
+        let (b0, b1) = if high_pass {
+            (1.0 / (1.0 + k), -1.0 / (1.0 + k))
+        } else {
+            (k / (1.0 + k), k / (1.0 + k))
+        };
+        sections.push(Biquad {
+            b0,
+            b1,
+            b2: 0.0,
+            a1,
+            a2: 0.0,
+        });
+    }
+
+    sections
+}
+
+fn filter_sections(sections: &[Biquad], values: &[f64]) -> Vec<f64> {
+    sections
+        .iter()
+        .fold(values.to_vec(), |values, section| section.filter(&values))
+}
+
+/// Runs the filter forwards and then backwards over the data for zero phase delay
+///
+/// The data is extended at both ends with a point reflection to reduce edge transients.
+fn filter_forward_backward(sections: &[Biquad], values: &[f64]) -> Vec<f64> {
+    let n = values.len();
+    if n < 2 {
+        return filter_sections(sections, values);
+    }
+
+    let pad = (6 * sections.len()).min(n - 1);
+    let (first, last) = (values[0], values[n - 1]);
+    let mut extended = Vec::with_capacity(n + 2 * pad);
+    extended.extend((1..=pad).rev().map(|i| 2.0 * first - values[i]));
+    extended.extend_from_slice(values);
+    extended.extend((1..=pad).map(|i| 2.0 * last - values[n - 1 - i]));
+
+    let mut filtered = filter_sections(sections, &extended);
+    filtered.reverse();
+    let mut filtered = filter_sections(sections, &filtered);
+    filtered.reverse();
+
+    filtered[pad..pad + n].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Filter, FilterBand, I2Error, LDReader, LDWriter};
+    use std::f64::consts::PI;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn sine(frequency: f64, rate: u16, seconds: usize) -> Vec<f64> {
+        (0..rate as usize * seconds)
+            .map(|i| (2.0 * PI * frequency * i as f64 / rate as f64).sin())
+            .collect()
+    }
+
+    fn rms(values: &[f64]) -> f64 {
+        (values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn moving_average_and_median() {
+        let values = [0.0, 0.0, 9.0, 0.0, 3.0, 3.0];
+
+        let average = Filter::MovingAverage { window: 1.5 }
+            .apply(&values, 2)
+            .unwrap();
+        assert_eq!(average, vec![0.0, 3.0, 3.0, 4.0, 2.0, 3.0]);
+
+        let median = Filter::Median { window: 1.5 }.apply(&values, 2).unwrap();
+        assert_eq!(median, vec![0.0, 0.0, 0.0, 3.0, 3.0, 3.0]);
+    }
+
+    #[test]
+    fn exponential_smoothing() {
+        let values = [0.0, 1.0, 1.0, 1.0];
+        let filtered = Filter::Exponential { time_constant: 0.0 }
+            .apply(&values, 10)
+            .unwrap();
+        assert_eq!(filtered, values.to_vec());
+
+        let filtered = Filter::Exponential { time_constant: 1.0 }
+            .apply(&values, 1)
+            .unwrap();
+        let alpha = 1.0 - (-1.0f64).exp();
+        assert!((filtered[1] - alpha).abs() < 1e-12);
+        assert!(filtered[3] < 1.0 && filtered[3] > filtered[2]);
+    }
+
+    #[test]
+    fn butterworth_attenuates_out_of_band() {
+        let rate = 100;
+        let low = sine(1.0, rate, 10);
+        let high = sine(30.0, rate, 10);
+        let mixed: Vec<f64> = low.iter().zip(&high).map(|(a, b)| a + b).collect();
+
+        for order in 1..=4 {
+            let filter = Filter::Butterworth {
+                band: FilterBand::LowPass(5.0),
+                order,
+                zero_phase: true,
+            };
+            let filtered = filter.apply(&mixed, rate).unwrap();
+            let error: Vec<f64> = filtered.iter().zip(&low).map(|(a, b)| a - b).collect();
+            assert!(rms(&error) < 0.1, "order {} error {}", order, rms(&error));
+        }
+
+        let filter = Filter::Butterworth {
+            band: FilterBand::HighPass(10.0),
+            order: 4,
+            zero_phase: false,
+        };
+        let filtered = filter.apply(&mixed, rate).unwrap();
+        assert!(rms(&filtered[100..]) > 0.65 && rms(&filtered[100..]) < 0.75);
+
+        let filter = Filter::Butterworth {
+            band: FilterBand::BandPass(0.5, 2.0),
+            order: 2,
+            zero_phase: true,
+        };
+        let filtered = filter.apply(&mixed, rate).unwrap();
+        let error: Vec<f64> = filtered.iter().zip(&low).map(|(a, b)| a - b).collect();
+        assert!(rms(&error[100..900]) < 0.2);
+    }
+
+    #[test]
+    fn butterworth_cutoff_gain() {
+        // At the cutoff frequency a Butterworth filter has a gain of 1/sqrt(2)
+        let rate = 200;
+        let values = sine(10.0, rate, 20);
+        for order in 1..=5 {
+            let filter = Filter::Butterworth {
+                band: FilterBand::LowPass(10.0),
+                order,
+                zero_phase: false,
+            };
+            let filtered = filter.apply(&values, rate).unwrap();
+            let gain = rms(&filtered[rate as usize * 5..]) / rms(&values[rate as usize * 5..]);
+            assert!(
+                (gain - 0.5f64.sqrt()).abs() < 0.01,
+                "order {} gain {}",
+                order,
+                gain
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_filters() {
+        let lowpass = |cutoff| Filter::Butterworth {
+            band: FilterBand::LowPass(cutoff),
+            order: 2,
+            zero_phase: false,
+        };
+        assert!(matches!(
+            lowpass(50.0).apply(&[0.0], 100),
+            Err(I2Error::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            lowpass(5.0).apply(&[0.0], 0),
+            Err(I2Error::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            Filter::Median { window: -1.0 }.apply(&[0.0], 10),
+            Err(I2Error::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn filter_sample1_and_write() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let header = reader.read_header().unwrap();
+        let channels = reader.read_channels().unwrap();
+        let channel = channels.iter().find(|c| c.name == "Susp Pos FL").unwrap();
+
+        let filter = Filter::Butterworth {
+            band: FilterBand::LowPass(10.0),
+            order: 2,
+            zero_phase: true,
+        };
+        let filtered = filter.apply_channel(&mut reader, channel).unwrap();
+        assert_eq!(filtered.len(), channel.data_count as usize);
+
+        let filtered_channel = channel.derive("Susp Pos FL Filt", "SuspFLF");
+        let samples = filtered_channel.encode_values(&filtered).unwrap();
+
+        let mut output = Cursor::new(Vec::new());
+        LDWriter::new(&mut output, header)
+            .with_channel(filtered_channel, samples)
+            .write()
+            .unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        let channels = reader.read_channels().unwrap();
+        assert_eq!(channels[0].name, "Susp Pos FL Filt");
+        assert_eq!(channels[0].unit, "mm");
+        assert_eq!(channels[0].sample_rate, 100);
+        let values = reader.channel_values(&channels[0]).unwrap();
+        for (written, expected) in values.iter().zip(&filtered) {
+            assert!((written - expected).abs() <= 0.05 + 1e-9);
+        }
+    }
+}
@@ -840,3 +840,15 @@ pub(crate) const FULL_HEADER: [u8; 13384] = [
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
+
+/// Address of the channel metadata following [FULL_HEADER]
+pub(crate) const CHANNEL_META_ADDR: u32 = FULL_HEADER.len() as u32;
+
+/// Address of the event block inside [FULL_HEADER]
+pub(crate) const EVENT_ADDR: u32 = 0x6E2;
+
+/// Address of the venue block inside [FULL_HEADER]
+pub(crate) const VENUE_ADDR: u16 = 0x1336;
+
+/// Address of the vehicle block inside [FULL_HEADER]
+pub(crate) const VEHICLE_ADDR: u16 = 0x1F54;
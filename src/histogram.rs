@@ -0,0 +1,395 @@
+use crate::resample::{resample, resampled_len};
+use crate::{ChannelMetadata, I2Result, LDReader};
+use std::io::{Read, Seek};
+
+/// How the bins of a [Histogram] or [Histogram2D] axis are chosen
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinWidth {
+    /// Bins of a fixed width, with edges aligned to multiples of the width
+    ///
+    /// The width is increased when it would need more than [MAX_BINS] bins, or more than
+    /// [MAX_CELLS] cells in a [Histogram2D].
+    Fixed(f64),
+    /// Bin width chosen from the data using the Freedman–Diaconis rule, falling back to
+    /// Sturges' rule when the interquartile range is 0
+    Auto,
+}
+
+/// Largest number of bins on a histogram axis
+pub const MAX_BINS: usize = 100_000;
+
+/// Largest number of cells in a [Histogram2D]
+pub const MAX_CELLS: usize = 1_000_000;
+
+/// A time weighted histogram of a channel
+///
+/// Every sample contributes `1 / sample_rate` seconds to its bin.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    /// Bin edges, there is one more edge than there are bins
+    pub edges: Vec<f64>,
+    /// Number of samples in each bin
+    pub counts: Vec<usize>,
+    /// Time in seconds spent in each bin
+    pub time: Vec<f64>,
+}
+
+/// A time weighted 2D histogram of two channels, used for scatter plots and heat maps
+///
+/// Cells are indexed as `[y][x]`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram2D {
+    pub x_edges: Vec<f64>,
+    pub y_edges: Vec<f64>,
+    /// Number of samples in each cell
+    pub counts: Vec<Vec<usize>>,
+    /// Time in seconds spent in each cell
+    pub time: Vec<Vec<f64>>,
+}
+
+impl Histogram {
+    /// Builds a histogram from decoded channel values, NaN and infinite values are ignored
+    pub fn from_values(values: &[f64], sample_rate: u16, bins: BinWidth) -> Self {
+        let edges = bin_edges(values, bins, MAX_BINS);
+        let mut counts = vec![0; bin_count(&edges)];
+        for value in values {
+            if let Some(i) = bin_index(&edges, *value) {
+                counts[i] += 1;
+            }
+        }
+
+        Self {
+            time: counts_to_time(&counts, sample_rate),
+            edges,
+            counts,
+        }
+    }
+
+    /// Reads a channel from `reader` and builds its histogram
+    pub fn read<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        bins: BinWidth,
+    ) -> I2Result<Self> {
+        let values = reader.channel_values(channel)?;
+        Ok(Self::from_values(&values, channel.sample_rate, bins))
+    }
+
+    /// Total time in seconds covered by the histogram
+    pub fn total_time(&self) -> f64 {
+        self.time.iter().sum()
+    }
+
+    /// Fraction of the total time spent in each bin
+    pub fn time_fraction(&self) -> Vec<f64> {
+        let total = self.total_time();
+        self.time
+            .iter()
+            .map(|t| if total > 0.0 { t / total } else { 0.0 })
+            .collect()
+    }
+}
+
+impl Histogram2D {
+    /// Builds a 2D histogram from two channels recorded at the same `sample_rate`
+    ///
+    /// Samples are paired by index, extra samples in the longer channel are ignored. When the
+    /// bins would need more than [MAX_CELLS] cells, the axis with more bins is widened first.
+    pub fn from_values(
+        x: &[f64],
+        y: &[f64],
+        sample_rate: u16,
+        x_bins: BinWidth,
+        y_bins: BinWidth,
+    ) -> Self {
+        let len = x.len().min(y.len());
+        let (x, y) = (&x[..len], &y[..len]);
+
+        let mut x_edges = bin_edges(x, x_bins, MAX_BINS);
+        let mut y_edges = bin_edges(y, y_bins, MAX_BINS);
+        let (nx, ny) = (bin_count(&x_edges), bin_count(&y_edges));
+        if nx * ny > MAX_CELLS {
+            // The smaller axis keeps its bins if it fits in a square grid
+            let side = MAX_CELLS.isqrt();
+            if nx >= ny {
+                y_edges = bin_edges(y, y_bins, ny.min(side));
+                x_edges = bin_edges(x, x_bins, MAX_CELLS / bin_count(&y_edges));
+            } else {
+                x_edges = bin_edges(x, x_bins, nx.min(side));
+                y_edges = bin_edges(y, y_bins, MAX_CELLS / bin_count(&x_edges));
+            }
+        }
+        let mut counts = vec![vec![0; bin_count(&x_edges)]; bin_count(&y_edges)];
+        for (x, y) in x.iter().zip(y) {
+            if let (Some(xi), Some(yi)) = (bin_index(&x_edges, *x), bin_index(&y_edges, *y)) {
+                counts[yi][xi] += 1;
+            }
+        }
+
+        Self {
+            time: counts
+                .iter()
+                .map(|row| counts_to_time(row, sample_rate))
+                .collect(),
+            x_edges,
+            y_edges,
+            counts,
+        }
+    }
+
+    /// Builds a 2D histogram from two channels with possibly different sample rates
+    ///
+    /// Both channels are resampled to the rate of the faster channel before pairing samples.
+    pub fn from_channels(
+        x: (&ChannelMetadata, &[f64]),
+        y: (&ChannelMetadata, &[f64]),
+        x_bins: BinWidth,
+        y_bins: BinWidth,
+    ) -> Self {
+        let (x_channel, x_values) = x;
+        let (y_channel, y_values) = y;
+        let rate = x_channel.sample_rate.max(y_channel.sample_rate);
+        let len = resampled_len(x_values.len(), x_channel.sample_rate, rate).min(resampled_len(
+            y_values.len(),
+            y_channel.sample_rate,
+            rate,
+        ));
+
+        let x_values = resample(x_values, x_channel.sample_rate, rate, len);
+        let y_values = resample(y_values, y_channel.sample_rate, rate, len);
+        Self::from_values(&x_values, &y_values, rate, x_bins, y_bins)
+    }
+
+    /// Reads two channels from `reader` and builds their 2D histogram
+    ///
+    /// See [Histogram2D::from_channels]
+    pub fn read<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        x: &ChannelMetadata,
+        y: &ChannelMetadata,
+        x_bins: BinWidth,
+        y_bins: BinWidth,
+    ) -> I2Result<Self> {
+        let x_values = reader.channel_values(x)?;
+        let y_values = reader.channel_values(y)?;
+        Ok(Self::from_channels(
+            (x, &x_values),
+            (y, &y_values),
+            x_bins,
+            y_bins,
+        ))
+    }
+}
+
+fn counts_to_time(counts: &[usize], sample_rate: u16) -> Vec<f64> {
+    counts
+        .iter()
+        .map(|count| {
+            if sample_rate == 0 {
+                0.0
+            } else {
+                *count as f64 / sample_rate as f64
+            }
+        })
+        .collect()
+}
+
+fn bin_count(edges: &[f64]) -> usize {
+    edges.len().saturating_sub(1)
+}
+
+/// Computes evenly spaced bin edges covering all finite values with at most `max_bins` bins
+fn bin_edges(values: &[f64], bins: BinWidth, max_bins: usize) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return vec![];
+    }
+    sorted.sort_by(f64::total_cmp);
+    let min = sorted[0];
+    let max = sorted[sorted.len() - 1];
+
+    let width = match bins {
+        BinWidth::Fixed(width) if width > 0.0 => width,
+        BinWidth::Fixed(_) | BinWidth::Auto => auto_width(&sorted),
+    };
+    let width = width.max((max - min) / (max_bins - 1) as f64);
+    if !width.is_finite() {
+        return vec![];
+    }
+
+    let start = (min / width).floor() * width;
+    let count = (((max - start) / width).floor() as usize + 1).clamp(1, max_bins);
+    (0..=count).map(|i| start + i as f64 * width).collect()
+}
+
+fn auto_width(sorted: &[f64]) -> f64 {
+    let n = sorted.len() as f64;
+    let range = sorted[sorted.len() - 1] - sorted[0];
+
+    let quantile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+    let iqr = quantile(0.75) - quantile(0.25);
+
+    let width = if iqr > 0.0 {
+        2.0 * iqr / n.cbrt()
+    } else {
+        range / (n.log2() + 1.0)
+    };
+
+    if width > 0.0 {
+        width
+    } else {
+        1.0
+    }
+}
+
+fn bin_index(edges: &[f64], value: f64) -> Option<usize> {
+    if edges.len() < 2 || value.is_nan() {
+        return None;
+    }
+    let width = edges[1] - edges[0];
+    let index = ((value - edges[0]) / width).floor();
+    if index < 0.0 {
+        return None;
+    }
+    let index = index as usize;
+    (index < edges.len() - 1).then_some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BinWidth, ChannelMetadata, Datatype, Histogram, Histogram2D, LDReader, MAX_BINS, MAX_CELLS,
+    };
+    use std::fs;
+    use std::io::Cursor;
+
+    fn channel(sample_rate: u16) -> ChannelMetadata {
+        ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            datatype: Datatype::I16,
+            sample_rate,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 0,
+            name: "".to_string(),
+            short_name: "".to_string(),
+            unit: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn fixed_width_histogram() {
+        let values = [-1.5, -0.5, 0.0, 0.2, 0.9, 1.0, f64::NAN];
+        let histogram = Histogram::from_values(&values, 2, BinWidth::Fixed(1.0));
+
+        assert_eq!(histogram.edges, vec![-2.0, -1.0, 0.0, 1.0, 2.0]);
+        assert_eq!(histogram.counts, vec![1, 1, 3, 1]);
+        assert_eq!(histogram.time, vec![0.5, 0.5, 1.5, 0.5]);
+        assert_eq!(histogram.total_time(), 3.0);
+        assert_eq!(histogram.time_fraction()[2], 0.5);
+    }
+
+    #[test]
+    fn infinite_values_are_ignored() {
+        let values = [1.0, 2.0, f64::INFINITY, f64::NEG_INFINITY];
+        let histogram = Histogram::from_values(&values, 10, BinWidth::Fixed(1.0));
+        assert_eq!(histogram.edges, vec![1.0, 2.0, 3.0]);
+        assert_eq!(histogram.counts, vec![1, 1]);
+
+        let histogram = Histogram::from_values(&[f64::INFINITY], 10, BinWidth::Auto);
+        assert!(histogram.counts.is_empty());
+    }
+
+    #[test]
+    fn tiny_fixed_width_is_capped() {
+        let histogram = Histogram::from_values(&[0.0, 1e9], 10, BinWidth::Fixed(1e-9));
+        assert!(histogram.counts.len() <= MAX_BINS);
+        assert_eq!(histogram.counts.iter().sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn wide_2d_histogram_is_capped() {
+        let x: Vec<f64> = (0..1000).map(|i| i as f64 * 1e6).collect();
+        let y: Vec<f64> = (0..1000).map(|i| i as f64 * -1e6).collect();
+        let histogram =
+            Histogram2D::from_values(&x, &y, 10, BinWidth::Fixed(1.0), BinWidth::Fixed(1.0));
+        let cells = histogram.counts.len() * histogram.counts[0].len();
+        assert!(cells <= MAX_CELLS);
+        assert_eq!(histogram.counts.iter().flatten().sum::<usize>(), 1000);
+
+        // A narrow axis keeps its bins
+        let y = vec![0.0; 1000];
+        let histogram =
+            Histogram2D::from_values(&x, &y, 10, BinWidth::Fixed(1.0), BinWidth::Fixed(1.0));
+        assert_eq!(histogram.counts.len(), 1);
+        assert_eq!(histogram.counts[0].len(), MAX_BINS);
+    }
+
+    #[test]
+    fn auto_width_histogram() {
+        let values: Vec<f64> = (0..1000).map(|i| (i % 100) as f64).collect();
+        let histogram = Histogram::from_values(&values, 100, BinWidth::Auto);
+        assert!(histogram.counts.len() > 5 && histogram.counts.len() < 100);
+        assert_eq!(histogram.counts.iter().sum::<usize>(), 1000);
+
+        let constant = Histogram::from_values(&[3.0; 10], 10, BinWidth::Auto);
+        assert_eq!(constant.edges, vec![3.0, 4.0]);
+        assert_eq!(constant.counts, vec![10]);
+
+        assert!(Histogram::from_values(&[], 10, BinWidth::Auto)
+            .counts
+            .is_empty());
+    }
+
+    #[test]
+    fn histogram_2d_resamples_channels() {
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let y = [0.0, 10.0];
+        let histogram = Histogram2D::from_channels(
+            (&channel(2), &x),
+            (&channel(1), &y),
+            BinWidth::Fixed(2.0),
+            BinWidth::Fixed(5.0),
+        );
+
+        assert_eq!(histogram.x_edges, vec![0.0, 2.0, 4.0]);
+        assert_eq!(histogram.y_edges, vec![0.0, 5.0, 10.0, 15.0]);
+        // Resampled y is [0, 5, 10, 10]
+        assert_eq!(histogram.counts, vec![vec![1, 0], vec![1, 0], vec![0, 2]]);
+        assert_eq!(histogram.time[2], vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn read_sample1_histograms() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let find = |name: &str| channels.iter().find(|c| c.name == name).unwrap();
+
+        let susp = find("Susp Pos FL");
+        let histogram = Histogram::read(&mut reader, susp, BinWidth::Auto).unwrap();
+        assert!((histogram.total_time() - 454.0).abs() < 1e-6);
+
+        let throttle = find("Throttle Pos");
+        let rpm = find("Engine RPM");
+        let map = Histogram2D::read(
+            &mut reader,
+            throttle,
+            rpm,
+            BinWidth::Fixed(10.0),
+            BinWidth::Fixed(500.0),
+        )
+        .unwrap();
+        let total: f64 = map.time.iter().flatten().sum();
+        assert!((total - 454.0).abs() < 1e-6);
+    }
+}
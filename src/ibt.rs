@@ -0,0 +1,875 @@
+use crate::{
+    convert_unit, ChannelMetadata, Datatype, Event, Header, I2Error, I2Result, Sample, Session,
+    Unit, Vehicle, Venue,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufReader;
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Only version of the iRacing telemetry format this importer understands
+pub const IBT_VERSION: i32 = 2;
+
+/// Size of a variable header in an .ibt file
+const VAR_HEADER_SIZE: u64 = 144;
+
+/// iRacing variables that have a common name in i2, with the unit they are converted to
+const CHANNEL_NAMES: &[(&str, &str, &str)] = &[
+    ("SessionTime", "Session Time", "s"),
+    ("Lap", "Lap Number", ""),
+    ("LapDist", "Lap Distance", "m"),
+    ("LapDistPct", "Lap Distance Pct", "%"),
+    ("LapCurrentLapTime", "Running Lap Time", "s"),
+    ("Speed", "Ground Speed", "km/h"),
+    ("RPM", "Engine RPM", "rpm"),
+    ("Gear", "Gear", ""),
+    ("Throttle", "Throttle Pos", "%"),
+    ("Brake", "Brake Pos", "%"),
+    ("Clutch", "Clutch Pos", "%"),
+    ("SteeringWheelAngle", "Steered Angle", "deg"),
+    ("LatAccel", "G Force Lat", "G"),
+    ("LongAccel", "G Force Long", "G"),
+    ("VertAccel", "G Force Vert", "G"),
+    ("YawRate", "Yaw Rate", "deg/s"),
+    ("Lat", "GPS Latitude", "deg"),
+    ("Lon", "GPS Longitude", "deg"),
+    ("Alt", "GPS Altitude", "m"),
+    ("FuelLevel", "Fuel Level", "l"),
+    ("FuelPress", "Fuel Pres", "kPa"),
+    ("WaterTemp", "Engine Temp", "C"),
+    ("OilTemp", "Eng Oil Temp", "C"),
+    ("OilPress", "Eng Oil Pres", "kPa"),
+    ("Voltage", "Battery Volts", "V"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IbtVarType {
+    Char,
+    Bool,
+    Int,
+    BitField,
+    Float,
+    Double,
+}
+
+impl IbtVarType {
+    pub fn from_i32(value: i32) -> I2Result<Self> {
+        match value {
+            0 => Ok(IbtVarType::Char),
+            1 => Ok(IbtVarType::Bool),
+            2 => Ok(IbtVarType::Int),
+            3 => Ok(IbtVarType::BitField),
+            4 => Ok(IbtVarType::Float),
+            5 => Ok(IbtVarType::Double),
+            _ => Err(I2Error::InvalidIbtFile(format!(
+                "unknown variable type {}",
+                value
+            ))),
+        }
+    }
+
+    /// Size in bytes of a single value
+    pub fn size(&self) -> usize {
+        match self {
+            IbtVarType::Char | IbtVarType::Bool => 1,
+            IbtVarType::Int | IbtVarType::BitField | IbtVarType::Float => 4,
+            IbtVarType::Double => 8,
+        }
+    }
+}
+
+/// The file header and disk sub header of an .ibt file
+#[derive(Debug, Clone, PartialEq)]
+pub struct IbtHeader {
+    pub version: i32,
+    /// Samples per second
+    pub tick_rate: i32,
+    pub session_info_len: i32,
+    pub session_info_offset: i32,
+    pub num_vars: i32,
+    pub var_header_offset: i32,
+    /// Size of a sample row in bytes
+    pub buf_len: i32,
+    /// Offset of the first sample row
+    pub buf_offset: i32,
+
+    /// Unix timestamp of the start of the session
+    pub session_start_date: i64,
+    pub session_start_time: f64,
+    pub session_end_time: f64,
+    pub session_lap_count: i32,
+    /// Number of sample rows, 0 if the file wasn't closed properly
+    pub session_record_count: i32,
+}
+
+/// A telemetry variable, every sample row holds `count` values of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct IbtVariable {
+    pub var_type: IbtVarType,
+    /// Offset of the first value in a sample row
+    pub offset: usize,
+    pub count: usize,
+    pub count_as_time: bool,
+    pub name: String,
+    pub description: String,
+    pub unit: String,
+}
+
+/// The session info YAML of an .ibt file
+///
+/// Only scalar values are kept, addressed by their path with list items as indices, such as
+/// `DriverInfo.Drivers.0.UserName`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionInfo {
+    pub entries: Vec<(String, String)>,
+}
+
+impl SessionInfo {
+    /// Parses the subset of YAML written by iRacing: nested maps, lists of maps and scalars
+    pub fn parse(yaml: &str) -> Self {
+        // (indent, path segment, is a list item)
+        let mut stack: Vec<(usize, String, bool)> = vec![];
+        let mut counters: Vec<(String, usize)> = vec![];
+        let mut entries = vec![];
+
+        for line in yaml.lines() {
+            let content = line.trim();
+            if content.is_empty()
+                || content.starts_with('#')
+                || content == "---"
+                || content == "..."
+            {
+                continue;
+            }
+            let mut indent = line.len() - line.trim_start().len();
+            let mut content = content;
+
+            if let Some(item) = content.strip_prefix('-') {
+                // Leave the previous item of this list and anything nested in it
+                while let Some((i, _, is_item)) = stack.last() {
+                    if *i < indent || (*i == indent && !is_item) {
+                        break;
+                    }
+                    stack.pop();
+                }
+                let parent = path(&stack);
+                let index = match counters.iter_mut().find(|(p, _)| *p == parent) {
+                    Some((_, count)) => {
+                        *count += 1;
+                        *count - 1
+                    }
+                    None => {
+                        counters.push((parent, 1));
+                        0
+                    }
+                };
+                stack.push((indent, index.to_string(), true));
+                indent += content.len() - item.trim_start().len();
+                content = item.trim_start();
+                if content.is_empty() {
+                    continue;
+                }
+            }
+
+            let (key, value) = match content.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            while matches!(stack.last(), Some((i, _, _)) if *i >= indent) {
+                stack.pop();
+            }
+            if value.is_empty() {
+                stack.push((indent, key.to_string(), false));
+            } else {
+                let mut key_path = path(&stack);
+                if !key_path.is_empty() {
+                    key_path.push('.');
+                }
+                key_path.push_str(key);
+                entries.push((key_path, value.trim_matches('"').to_string()));
+            }
+        }
+        SessionInfo { entries }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The entry of the driver of the car the telemetry was recorded in
+    pub fn driver(&self, key: &str) -> Option<&str> {
+        let index = self.get("DriverInfo.DriverCarIdx")?;
+        (0..)
+            .map_while(|i| {
+                self.get(&format!("DriverInfo.Drivers.{}.CarIdx", i))
+                    .map(|car| (i, car))
+            })
+            .find(|(_, car)| *car == index)
+            .and_then(|(i, _)| self.get(&format!("DriverInfo.Drivers.{}.{}", i, key)))
+    }
+}
+
+fn path(stack: &[(usize, String, bool)]) -> String {
+    stack
+        .iter()
+        .map(|(_, segment, _)| segment.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Reads iRacing .ibt telemetry files and converts them into a [Session]
+///
+/// The reader owns its source, pass `&mut source` to keep using the source afterwards.
+#[derive(Debug)]
+pub struct IbtReader<S: Read + Seek> {
+    source: S,
+    header: Option<IbtHeader>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl IbtReader<BufReader<File>> {
+    /// Opens the file at `path` for buffered reading
+    pub fn open<P: AsRef<Path>>(path: P) -> I2Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<S: Read + Seek> IbtReader<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            header: None,
+        }
+    }
+
+    /// Returns the source, consuming the reader
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    pub fn read_header(&mut self) -> I2Result<IbtHeader> {
+        self.source.seek(SeekFrom::Start(0))?;
+
+        let version = self.source.read_i32::<LittleEndian>()?;
+        if version != IBT_VERSION {
+            return Err(I2Error::InvalidIbtFile(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let _status = self.source.read_i32::<LittleEndian>()?;
+        let tick_rate = self.source.read_i32::<LittleEndian>()?;
+        let _session_info_update = self.source.read_i32::<LittleEndian>()?;
+        let session_info_len = self.source.read_i32::<LittleEndian>()?;
+        let session_info_offset = self.source.read_i32::<LittleEndian>()?;
+        let num_vars = self.source.read_i32::<LittleEndian>()?;
+        let var_header_offset = self.source.read_i32::<LittleEndian>()?;
+        let _num_buf = self.source.read_i32::<LittleEndian>()?;
+        let buf_len = self.source.read_i32::<LittleEndian>()?;
+        let _pad = self.source.read_u64::<LittleEndian>()?;
+
+        // Files on disk only use the first of the 4 buffers
+        let _tick_count = self.source.read_i32::<LittleEndian>()?;
+        let buf_offset = self.source.read_i32::<LittleEndian>()?;
+        let _pad = self.read_bytes(8 + 3 * 16)?;
+
+        let session_start_date = self.source.read_i64::<LittleEndian>()?;
+        let session_start_time = self.source.read_f64::<LittleEndian>()?;
+        let session_end_time = self.source.read_f64::<LittleEndian>()?;
+        let session_lap_count = self.source.read_i32::<LittleEndian>()?;
+        let session_record_count = self.source.read_i32::<LittleEndian>()?;
+
+        if tick_rate <= 0 || tick_rate > u16::MAX as i32 || buf_len <= 0 || num_vars < 0 {
+            return Err(I2Error::InvalidIbtFile(format!(
+                "invalid header (tick rate {}, row length {}, {} variables)",
+                tick_rate, buf_len, num_vars
+            )));
+        }
+
+        let header = IbtHeader {
+            version,
+            tick_rate,
+            session_info_len,
+            session_info_offset,
+            num_vars,
+            var_header_offset,
+            buf_len,
+            buf_offset,
+            session_start_date,
+            session_start_time,
+            session_end_time,
+            session_lap_count,
+            session_record_count,
+        };
+        self.header = Some(header.clone());
+        Ok(header)
+    }
+
+    /// Calls [IbtReader::read_header] if it hasn't been called before
+    pub fn read_variables(&mut self) -> I2Result<Vec<IbtVariable>> {
+        let header = self.header()?;
+
+        let mut variables = vec![];
+        for i in 0..header.num_vars as u64 {
+            self.source.seek(SeekFrom::Start(
+                header.var_header_offset as u64 + i * VAR_HEADER_SIZE,
+            ))?;
+            let var_type = IbtVarType::from_i32(self.source.read_i32::<LittleEndian>()?)?;
+            let offset = self.source.read_i32::<LittleEndian>()?;
+            let count = self.source.read_i32::<LittleEndian>()?;
+            let count_as_time = self.read_bytes(4)?[0] != 0;
+            let name = self.read_string(32)?;
+            let description = self.read_string(64)?;
+            let unit = self.read_string(32)?;
+
+            let (offset, count) = match (usize::try_from(offset), usize::try_from(count)) {
+                (Ok(offset), Ok(count)) => (offset, count),
+                _ => {
+                    return Err(I2Error::InvalidIbtFile(format!(
+                        "variable {} has a negative offset or count",
+                        name
+                    )))
+                }
+            };
+            let end = count
+                .checked_mul(var_type.size())
+                .and_then(|size| size.checked_add(offset));
+            if end.is_none_or(|end| end > header.buf_len as usize) {
+                return Err(I2Error::InvalidIbtFile(format!(
+                    "variable {} is outside of the sample row",
+                    name
+                )));
+            }
+            variables.push(IbtVariable {
+                var_type,
+                offset,
+                count,
+                count_as_time,
+                name,
+                description,
+                unit,
+            });
+        }
+        Ok(variables)
+    }
+
+    /// Calls [IbtReader::read_header] if it hasn't been called before
+    pub fn read_session_info(&mut self) -> I2Result<SessionInfo> {
+        let header = self.header()?;
+        self.source
+            .seek(SeekFrom::Start(header.session_info_offset as u64))?;
+        let bytes = self.read_bytes(header.session_info_len.max(0) as usize)?;
+        Ok(SessionInfo::parse(&latin1(&bytes)))
+    }
+
+    /// Reads every value of every variable, arrays are returned as one column per element
+    ///
+    /// Rows are read until `session_record_count` is reached, or until the end of the file for
+    /// files that weren't closed properly.
+    pub fn read_values(&mut self, variables: &[IbtVariable]) -> I2Result<Vec<Vec<f64>>> {
+        let header = self.header()?;
+        let mut columns = vec![vec![]; variables.iter().map(|v| v.count).sum()];
+
+        self.source
+            .seek(SeekFrom::Start(header.buf_offset as u64))?;
+        let mut row = vec![0u8; header.buf_len as usize];
+        let mut rows = 0;
+        while header.session_record_count <= 0 || rows < header.session_record_count {
+            match self.source.read_exact(&mut row) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+
+            let mut column = columns.iter_mut();
+            for variable in variables {
+                for i in 0..variable.count {
+                    let start = variable.offset + i * variable.var_type.size();
+                    let bytes = &row[start..start + variable.var_type.size()];
+                    let value = match variable.var_type {
+                        IbtVarType::Char => bytes[0] as f64,
+                        IbtVarType::Bool => (bytes[0] != 0) as u8 as f64,
+                        IbtVarType::Int => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                        IbtVarType::BitField => {
+                            u32::from_le_bytes(bytes.try_into().unwrap()) as f64
+                        }
+                        IbtVarType::Float => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                        IbtVarType::Double => f64::from_le_bytes(bytes.try_into().unwrap()),
+                    };
+                    column.next().unwrap().push(value);
+                }
+            }
+            rows += 1;
+        }
+        Ok(columns)
+    }
+
+    /// Converts the whole file into a [Session]
+    ///
+    /// Common variables are renamed to the names i2 uses (see the `Speed` variable, which
+    /// becomes `Ground Speed` in km/h), the others keep their iRacing name. iRacing stores
+    /// percentages as fractions, these are scaled to 0-100. Header, event, venue and vehicle
+    /// are filled from the session info.
+    pub fn read_session(&mut self) -> I2Result<Session> {
+        let header = self.read_header()?;
+        let variables = self.read_variables()?;
+        let info = self.read_session_info()?;
+        let columns = self.read_values(&variables)?;
+
+        let mut columns = columns.into_iter();
+        let mut channels = vec![];
+        for variable in &variables {
+            for i in 0..variable.count {
+                let values = columns.next().unwrap();
+                channels.push(convert_variable(
+                    variable,
+                    i,
+                    values,
+                    header.tick_rate as u16,
+                )?);
+            }
+        }
+
+        let get = |path: &str| info.get(path).unwrap_or_default().to_string();
+        let venue = match info.get("WeekendInfo.TrackDisplayName") {
+            Some(venue) => venue.to_string(),
+            None => get("WeekendInfo.TrackName"),
+        };
+        let session = get("SessionInfo.Sessions.0.SessionType");
+        let vehicle_id = info.driver("CarScreenName").unwrap_or_default().to_string();
+        let (date, time) = match header.session_start_date {
+            0 => (
+                date_from_yaml(info.get("WeekendInfo.WeekendOptions.Date")),
+                String::new(),
+            ),
+            timestamp => date_time_from_unix(timestamp),
+        };
+
+        Ok(Session {
+            header: Header {
+                channel_meta_ptr: 0,
+                channel_data_ptr: 0,
+                event_ptr: 0,
+                device_serial: 0,
+                device_type: "ADL".to_string(),
+                device_version: 420,
+                num_channels: channels.len() as u32,
+                date_string: date,
+                time_string: time,
+                driver: info.driver("UserName").unwrap_or_default().to_string(),
+                vehicleid: vehicle_id.clone(),
+                venue: venue.clone(),
+                session: session.clone(),
+                short_comment: String::new(),
+            },
+            event: Some(Event {
+                name: get("WeekendInfo.EventType"),
+                session,
+                comment: get("WeekendInfo.TrackConfigName"),
+                venue_addr: 0,
+            }),
+            venue: Some(Venue {
+                name: venue,
+                vehicle_addr: 0,
+            }),
+            vehicle: Some(Vehicle {
+                id: vehicle_id,
+                weight: 0,
+                _type: info
+                    .driver("CarClassShortName")
+                    .unwrap_or_default()
+                    .to_string(),
+                comment: String::new(),
+            }),
+            channels,
+        })
+    }
+
+    /// Converts the file with [IbtReader::read_session] and writes it as an .ld file
+    pub fn write_ld<W: Write + Seek>(&mut self, sink: &mut W) -> I2Result<()> {
+        self.read_session()?.write(sink)
+    }
+
+    fn header(&mut self) -> I2Result<IbtHeader> {
+        match &self.header {
+            Some(header) => Ok(header.clone()),
+            None => self.read_header(),
+        }
+    }
+
+    fn read_bytes(&mut self, size: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; size];
+        self.source.read_exact(&mut bytes[0..size])?;
+        Ok(bytes)
+    }
+
+    /// Reads a latin-1 string with a fixed size trimming null bytes
+    fn read_string(&mut self, size: usize) -> I2Result<String> {
+        let bytes = self.read_bytes(size)?;
+        let str_size = bytes.iter().position(|c| *c == b'\0').unwrap_or(size);
+        Ok(latin1(&bytes[0..str_size]))
+    }
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// Creates the channel for element `index` of `variable`
+fn convert_variable(
+    variable: &IbtVariable,
+    index: usize,
+    mut values: Vec<f64>,
+    sample_rate: u16,
+) -> I2Result<(ChannelMetadata, Vec<Sample>)> {
+    let known = CHANNEL_NAMES
+        .iter()
+        .find(|(ibt_name, _, _)| *ibt_name == variable.name);
+
+    let mut unit = match variable.unit.as_str() {
+        "revs/min" => "rpm".to_string(),
+        unit => unit.to_string(),
+    };
+    if unit == "%" {
+        values.iter_mut().for_each(|value| *value *= 100.0);
+    }
+    if let Some((_, _, target)) = known {
+        if unit != *target && Unit::parse(&unit).is_ok() {
+            if let Ok(converted) = values
+                .iter()
+                .map(|value| convert_unit(*value, &unit, target))
+                .collect::<I2Result<Vec<_>>>()
+            {
+                values = converted;
+                unit = target.to_string();
+            }
+        }
+    }
+
+    let mut name = match known {
+        Some((_, name, _)) => name.to_string(),
+        None => variable.name.clone(),
+    };
+    if variable.count > 1 {
+        name = format!("{} {}", name, index + 1);
+    }
+    let short_name: String = variable.name.chars().take(8).collect();
+
+    let converted = variable.unit != unit || unit == "%";
+    let (datatype, dec_places) = match variable.var_type {
+        _ if converted => (Datatype::F32, 0),
+        IbtVarType::Char | IbtVarType::Bool => (Datatype::I16, 0),
+        IbtVarType::Int | IbtVarType::BitField => (Datatype::I32, 0),
+        IbtVarType::Float => (Datatype::F32, 0),
+        // F32 would quantize GPS coordinates to about half a meter
+        IbtVarType::Double => (Datatype::I32, double_dec_places(&values)),
+    };
+
+    let channel = ChannelMetadata {
+        prev_addr: 0,
+        next_addr: 0,
+        data_addr: 0,
+        data_count: values.len() as u32,
+        datatype,
+        sample_rate,
+        offset: 0,
+        mul: 1,
+        scale: 1,
+        dec_places,
+        name,
+        short_name,
+        unit: unit.chars().take(12).collect(),
+    };
+    let samples = if variable.var_type == IbtVarType::BitField && !converted {
+        // Keep the bits of the field rather than saturating large values
+        values
+            .iter()
+            .map(|value| Sample::I32(*value as u32 as i32))
+            .collect()
+    } else {
+        channel.encode_values(&values)?
+    };
+    Ok((channel, samples))
+}
+
+/// Most decimal places, up to 9, with which all `values` fit into an I32 sample
+fn double_dec_places(values: &[f64]) -> i16 {
+    let max = values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold(0.0f64, |max, v| max.max(v.abs()));
+    (0..=9)
+        .rev()
+        .find(|dec_places| max * 10.0f64.powi(*dec_places as i32) <= i32::MAX as f64)
+        .unwrap_or(0)
+}
+
+/// Formats a unix timestamp as the `dd/mm/yyyy` date and `hh:mm:ss` time of [Header]
+pub(crate) fn date_time_from_unix(timestamp: i64) -> (String, String) {
+    let days = timestamp.div_euclid(86400);
+    let seconds = timestamp.rem_euclid(86400);
+
+    // Days to civil date, from Howard Hinnant's chrono-compatible algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    (
+        format!("{:02}/{:02}/{:04}", day, month, year),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        ),
+    )
+}
+
+/// Converts the `yyyy-mm-dd` date of the session info into `dd/mm/yyyy`
+fn date_from_yaml(date: Option<&str>) -> String {
+    let date = date.unwrap_or_default();
+    match date.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day] => format!("{}/{}/{}", day, month, year),
+        _ => date.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_variable, date_time_from_unix, SessionInfo};
+    use crate::{Datatype, I2Error, IbtReader, IbtVarType, IbtVariable, LDReader};
+    use std::io::Cursor;
+
+    const SESSION_INFO: &str = "---
+WeekendInfo:
+ TrackName: spa 2017
+ TrackDisplayName: Circuit de Spa-Francorchamps
+ TrackConfigName: Grand Prix Pits
+ EventType: Test
+ WeekendOptions:
+  Date: 2023-05-01
+
+SessionInfo:
+ Sessions:
+ - SessionNum: 0
+   SessionType: Offline Testing
+
+DriverInfo:
+ DriverCarIdx: 1
+ Drivers:
+ - CarIdx: 0
+   UserName: Pace Car
+ - CarIdx: 1
+   UserName: Jane Doe
+   CarScreenName: Mazda MX-5 Cup
+   CarClassShortName: MX5
+...
+";
+
+    /// Writes an .ibt file with `Speed` (float, m/s), `RPM` (float), `Throttle` (float, %),
+    /// `Lap` (int) and `TireTemp` (float[2]) at 60 Hz
+    fn sample_ibt(rows: usize) -> Vec<u8> {
+        let vars: [(i32, i32, i32, &str, &str); 5] = [
+            (4, 0, 1, "Speed", "m/s"),
+            (4, 4, 1, "RPM", "revs/min"),
+            (4, 8, 1, "Throttle", "%"),
+            (2, 12, 1, "Lap", ""),
+            (4, 16, 2, "TireTemp", "C"),
+        ];
+        let buf_len = 24;
+        let var_header_offset = 144;
+        let session_info_offset = var_header_offset + vars.len() * 144;
+        let buf_offset = session_info_offset + SESSION_INFO.len();
+
+        let mut bytes = vec![];
+        for value in [
+            2,
+            1,
+            60,
+            1,
+            SESSION_INFO.len() as i32,
+            session_info_offset as i32,
+            vars.len() as i32,
+            var_header_offset as i32,
+            1,
+            buf_len,
+            0,
+            0,
+            rows as i32,
+            buf_offset as i32,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(112, 0);
+        bytes.extend_from_slice(&1_682_935_200i64.to_le_bytes());
+        bytes.extend_from_slice(&0.0f64.to_le_bytes());
+        bytes.extend_from_slice(&(rows as f64 / 60.0).to_le_bytes());
+        bytes.extend_from_slice(&2i32.to_le_bytes());
+        bytes.extend_from_slice(&(rows as i32).to_le_bytes());
+
+        for (var_type, offset, count, name, unit) in vars {
+            for value in [var_type, offset, count, 0] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for (string, size) in [(name, 32), ("", 64), (unit, 32)] {
+                let mut field = string.as_bytes().to_vec();
+                field.resize(size, 0);
+                bytes.extend_from_slice(&field);
+            }
+        }
+        bytes.extend_from_slice(SESSION_INFO.as_bytes());
+
+        for row in 0..rows {
+            let t = row as f32 / 60.0;
+            bytes.extend_from_slice(&(10.0 * t).to_le_bytes());
+            bytes.extend_from_slice(&(3000.0 + 100.0 * t).to_le_bytes());
+            bytes.extend_from_slice(&0.5f32.to_le_bytes());
+            bytes.extend_from_slice(&((row / 60) as i32).to_le_bytes());
+            bytes.extend_from_slice(&80.0f32.to_le_bytes());
+            bytes.extend_from_slice(&85.0f32.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_session_info() {
+        let info = SessionInfo::parse(SESSION_INFO);
+        assert_eq!(info.get("WeekendInfo.TrackName"), Some("spa 2017"));
+        assert_eq!(
+            info.get("WeekendInfo.WeekendOptions.Date"),
+            Some("2023-05-01")
+        );
+        assert_eq!(
+            info.get("SessionInfo.Sessions.0.SessionType"),
+            Some("Offline Testing")
+        );
+        assert_eq!(info.get("DriverInfo.Drivers.0.UserName"), Some("Pace Car"));
+        assert_eq!(info.driver("UserName"), Some("Jane Doe"));
+        assert_eq!(info.driver("CarClassShortName"), Some("MX5"));
+    }
+
+    #[test]
+    fn unix_date_time() {
+        assert_eq!(
+            date_time_from_unix(1_682_935_200),
+            ("01/05/2023".to_string(), "10:00:00".to_string())
+        );
+        assert_eq!(
+            date_time_from_unix(951_782_400),
+            ("29/02/2000".to_string(), "00:00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn convert_ibt_to_ld() {
+        let mut source = Cursor::new(sample_ibt(150));
+        let mut output = Cursor::new(Vec::new());
+        IbtReader::new(&mut source).write_ld(&mut output).unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        assert!(reader.validate().unwrap().findings.is_empty());
+        let session = reader.read_session().unwrap();
+        assert_eq!(session.header.date_string, "01/05/2023");
+        assert_eq!(session.header.time_string, "10:00:00");
+        assert_eq!(session.header.driver, "Jane Doe");
+        assert_eq!(session.header.vehicleid, "Mazda MX-5 Cup");
+        assert_eq!(session.venue.unwrap().name, "Circuit de Spa-Francorchamps");
+        assert_eq!(session.event.unwrap().session, "Offline Testing");
+        assert_eq!(session.vehicle.unwrap()._type, "MX5");
+
+        let names: Vec<_> = session
+            .channels
+            .iter()
+            .map(|(c, _)| c.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Ground Speed",
+                "Engine RPM",
+                "Throttle Pos",
+                "Lap Number",
+                "TireTemp 1",
+                "TireTemp 2"
+            ]
+        );
+
+        let (speed, samples) = &session.channels[0];
+        assert_eq!(speed.unit, "km/h");
+        assert_eq!(speed.sample_rate, 60);
+        assert_eq!(samples.len(), 150);
+        assert!((samples[60].decode_f64(speed) - 36.0).abs() < 1e-4);
+
+        let (rpm, samples) = &session.channels[1];
+        assert_eq!(rpm.unit, "rpm");
+        assert!((samples[60].decode_f64(rpm) - 3100.0).abs() < 1e-3);
+
+        let (throttle, samples) = &session.channels[2];
+        assert_eq!(samples[0].decode_f64(throttle), 50.0);
+
+        let laps = reader.read_laps().unwrap();
+        assert_eq!(laps.len(), 3);
+        assert_eq!(laps[1].start, 1.0);
+    }
+
+    #[test]
+    fn reject_variables_outside_of_row() {
+        // Offset and count of the first variable header
+        for (field, value) in [(148, -1), (152, -1), (152, i32::MAX), (148, i32::MAX)] {
+            let mut bytes = sample_ibt(10);
+            bytes[field..field + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(matches!(
+                IbtReader::new(&mut Cursor::new(bytes)).read_variables(),
+                Err(I2Error::InvalidIbtFile(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn double_gps_keeps_precision() {
+        let variable = IbtVariable {
+            var_type: IbtVarType::Double,
+            offset: 0,
+            count: 1,
+            count_as_time: false,
+            name: "Lat".to_string(),
+            description: "".to_string(),
+            unit: "deg".to_string(),
+        };
+        let values = vec![50.437_123_456, -50.437_123_467, 0.0];
+        let (channel, samples) = convert_variable(&variable, 0, values.clone(), 60).unwrap();
+        assert_eq!(channel.name, "GPS Latitude");
+        assert_eq!(channel.datatype, Datatype::I32);
+        assert_eq!(channel.dec_places, 7);
+        for (sample, value) in samples.iter().zip(&values) {
+            // About 1 cm at the equator
+            assert!((sample.decode_f64(&channel) - value).abs() < 1e-7);
+        }
+    }
+
+    #[test]
+    fn read_unfinished_ibt() {
+        // Files that weren't closed have no record count, rows are read until the end
+        let mut bytes = sample_ibt(90);
+        bytes[140..144].copy_from_slice(&0i32.to_le_bytes());
+        bytes.truncate(bytes.len() - 10);
+
+        let mut source = Cursor::new(bytes);
+        let session = IbtReader::new(&mut source).read_session().unwrap();
+        assert_eq!(session.channels[0].1.len(), 89);
+    }
+}
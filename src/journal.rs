@@ -0,0 +1,409 @@
+use crate::reader::read_sample;
+use crate::structs::sample_matches;
+use crate::{ChannelWithSamples, I2Error, I2Result, LDReader, Sample, Session, SessionDocument};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io;
+use std::io::{Cursor, Read, Seek, Write};
+use std::time::{Duration, Instant};
+
+/// First bytes of every journal
+pub const JOURNAL_MAGIC: &[u8; 8] = b"I2JRNL01";
+
+const RECORD_METADATA: u8 = 1;
+const RECORD_SAMPLES: u8 = 2;
+const RECORD_END: u8 = 3;
+
+/// Writes samples to an append only journal as they are produced
+///
+/// [LDWriter](crate::LDWriter) can only lay out a file once all samples are known, so a crash
+/// while logging loses the whole session. This writer appends every flushed batch of samples as
+/// a checksummed record to `sink`. If the process dies, [recover_journal] rebuilds a session
+/// from every complete record, losing at most the samples since the last flush. Once logging is
+/// done, [JournalWriter::finish] writes the .ld file.
+#[derive(Debug)]
+pub struct JournalWriter<S: Write> {
+    sink: S,
+    document: SessionDocument,
+    samples: Vec<Vec<Sample>>,
+    /// Number of samples of each channel that have been written to the journal
+    flushed: Vec<usize>,
+    flush_interval: Option<Duration>,
+    last_flush: Instant,
+}
+
+impl<S: Write> JournalWriter<S> {
+    /// Starts a journal for the header, event, venue, vehicle and channels of `document`
+    pub fn new(mut sink: S, document: SessionDocument) -> I2Result<Self> {
+        let mut metadata = Cursor::new(Vec::new());
+        let mut writer = document.writer(&mut metadata);
+        for channel in &document.channels {
+            let mut channel = channel.clone();
+            channel.data_count = 0;
+            writer = writer.with_channel(channel, vec![]);
+        }
+        writer.write()?;
+
+        sink.write_all(JOURNAL_MAGIC)?;
+        write_record(&mut sink, RECORD_METADATA, &metadata.into_inner())?;
+        sink.flush()?;
+
+        let channels = document.channels.len();
+        Ok(Self {
+            sink,
+            document,
+            samples: vec![vec![]; channels],
+            flushed: vec![0; channels],
+            flush_interval: None,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Flushes automatically on the first append after `interval` has passed
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Adds samples to the end of channel `channel`, in the order of the document's channels
+    ///
+    /// The samples have to match the datatype of the channel.
+    pub fn append(&mut self, channel: usize, samples: &[Sample]) -> I2Result<()> {
+        let metadata = self
+            .document
+            .channels
+            .get(channel)
+            .ok_or_else(|| I2Error::ChannelNotFound(format!("channel index {}", channel)))?;
+        if !samples
+            .iter()
+            .all(|sample| sample_matches(sample, &metadata.datatype))
+        {
+            return Err(I2Error::UnsupportedDatatype(metadata.datatype.clone()));
+        }
+        self.samples[channel].extend_from_slice(samples);
+
+        if let Some(interval) = self.flush_interval {
+            if self.last_flush.elapsed() >= interval {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes final values and adds them to channel `channel`, see [JournalWriter::append]
+    pub fn append_values(&mut self, channel: usize, values: &[f64]) -> I2Result<()> {
+        let metadata = self
+            .document
+            .channels
+            .get(channel)
+            .ok_or_else(|| I2Error::ChannelNotFound(format!("channel index {}", channel)))?;
+        let samples = metadata.encode_values(values)?;
+        self.append(channel, &samples)
+    }
+
+    /// Writes all samples appended since the last flush to the journal
+    pub fn flush(&mut self) -> I2Result<()> {
+        for (channel, samples) in self.samples.iter().enumerate() {
+            let pending = &samples[self.flushed[channel]..];
+            if pending.is_empty() {
+                continue;
+            }
+
+            let mut payload = Vec::with_capacity(2 + pending.len() * 4);
+            payload.write_u16::<LittleEndian>(channel as u16)?;
+            for sample in pending {
+                match sample {
+                    Sample::I16(v) => payload.write_i16::<LittleEndian>(*v)?,
+                    Sample::I32(v) => payload.write_i32::<LittleEndian>(*v)?,
+                    Sample::F32(v) => payload.write_f32::<LittleEndian>(*v)?,
+                }
+            }
+            write_record(&mut self.sink, RECORD_SAMPLES, &payload)?;
+            self.flushed[channel] = samples.len();
+        }
+        self.sink.flush()?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Samples appended so far, including those that haven't been flushed
+    pub fn session(&self) -> Session {
+        let channels = self
+            .document
+            .channels
+            .iter()
+            .zip(&self.samples)
+            .map(|(channel, samples)| {
+                let mut channel = channel.clone();
+                channel.data_count = samples.len() as u32;
+                (channel, samples.clone())
+            })
+            .collect();
+        session_from(&self.document, channels)
+    }
+
+    /// Flushes the journal, marks it as complete and writes the .ld file to `ld`
+    ///
+    /// Returns the journal sink, which can be deleted once the .ld file is safely stored.
+    pub fn finish<W: Write + Seek>(mut self, ld: &mut W) -> I2Result<S> {
+        self.flush()?;
+        write_record(&mut self.sink, RECORD_END, &[])?;
+        self.sink.flush()?;
+        self.session().write(ld)?;
+        Ok(self.sink)
+    }
+}
+
+/// The result of [recover_journal]
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalRecovery {
+    pub session: Session,
+    /// Number of sample records that were read
+    pub records: usize,
+    /// True if the journal was finished with [JournalWriter::finish]
+    pub finished: bool,
+    /// Bytes at the end of the journal that were dropped, because they didn't form a complete
+    /// record or failed the checksum
+    pub dropped_bytes: u64,
+}
+
+/// Rebuilds the session from a journal written by [JournalWriter]
+///
+/// Records are read until the end of the journal or the first record that is cut off or fails
+/// its checksum. Fails only if the metadata at the start of the journal is damaged.
+pub fn recover_journal<R: Read>(source: &mut R) -> I2Result<JournalRecovery> {
+    let mut bytes = vec![];
+    source.read_to_end(&mut bytes)?;
+
+    if bytes.len() < JOURNAL_MAGIC.len() || &bytes[..JOURNAL_MAGIC.len()] != JOURNAL_MAGIC {
+        return Err(I2Error::InvalidJournal(
+            "missing journal header".to_string(),
+        ));
+    }
+    let mut position = JOURNAL_MAGIC.len();
+
+    let document = match read_record(&bytes[position..]) {
+        Some((RECORD_METADATA, payload, len)) => {
+            position += len;
+            LDReader::new(&mut Cursor::new(payload)).read_session_document()?
+        }
+        _ => {
+            return Err(I2Error::InvalidJournal(
+                "session metadata is damaged".to_string(),
+            ))
+        }
+    };
+
+    let mut samples = vec![vec![]; document.channels.len()];
+    let mut records = 0;
+    let mut finished = false;
+    while let Some((kind, payload, len)) = read_record(&bytes[position..]) {
+        match kind {
+            RECORD_SAMPLES => {
+                let mut payload = payload;
+                let channel = payload.read_u16::<LittleEndian>()? as usize;
+                let datatype = match document.channels.get(channel) {
+                    Some(channel) => &channel.datatype,
+                    None => break,
+                };
+                let size = datatype.size() as usize;
+                if size == 0 || payload.len() % size != 0 {
+                    break;
+                }
+                for _ in 0..payload.len() / size {
+                    samples[channel].push(read_sample(&mut payload, datatype)?);
+                }
+            }
+            RECORD_END => finished = true,
+            _ => break,
+        }
+        position += len;
+        records += (kind == RECORD_SAMPLES) as usize;
+    }
+
+    let channels = document
+        .channels
+        .iter()
+        .zip(samples)
+        .map(|(channel, samples)| {
+            let mut channel = channel.clone();
+            channel.data_count = samples.len() as u32;
+            (channel, samples)
+        })
+        .collect();
+
+    Ok(JournalRecovery {
+        session: session_from(&document, channels),
+        records,
+        finished,
+        dropped_bytes: (bytes.len() - position) as u64,
+    })
+}
+
+fn session_from(document: &SessionDocument, channels: Vec<ChannelWithSamples>) -> Session {
+    Session {
+        header: document.header.clone(),
+        event: document.event.clone(),
+        venue: document.venue.clone(),
+        vehicle: document.vehicle.clone(),
+        channels,
+    }
+}
+
+/// Writes a record: kind, payload length, payload and the CRC-32 of all three
+fn write_record<W: Write>(sink: &mut W, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let mut record = Vec::with_capacity(payload.len() + 9);
+    record.push(kind);
+    record.write_u32::<LittleEndian>(payload.len() as u32)?;
+    record.extend_from_slice(payload);
+    let crc = crc32(&record);
+    record.write_u32::<LittleEndian>(crc)?;
+    sink.write_all(&record)
+}
+
+/// Reads the record at the start of `bytes`, returning its kind, payload and total length
+fn read_record(bytes: &[u8]) -> Option<(u8, &[u8], usize)> {
+    let len = u32::from_le_bytes(bytes.get(1..5)?.try_into().ok()?) as usize;
+    let end = 5usize.checked_add(len)?;
+    let crc = u32::from_le_bytes(bytes.get(end..end + 4)?.try_into().ok()?);
+    if crc32(&bytes[..end]) != crc {
+        return None;
+    }
+    Some((bytes[0], &bytes[5..end], end + 4))
+}
+
+/// CRC-32 (IEEE) as used by zip and PNG
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, recover_journal, JournalWriter};
+    use crate::{I2Error, LDReader, Session};
+    use std::fs;
+    use std::io::Cursor;
+
+    fn sample1() -> Session {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        LDReader::new(&mut Cursor::new(bytes))
+            .read_session()
+            .unwrap()
+    }
+
+    /// Journals the first `seconds` of a few channels of Sample1, flushing every second
+    fn write_journal(session: &Session, seconds: usize, finish: bool) -> Vec<u8> {
+        let mut document =
+            LDReader::new(&mut Cursor::new(fs::read("./samples/Sample1.ld").unwrap()))
+                .read_session_document()
+                .unwrap();
+        document.channels.truncate(4);
+
+        let mut journal = JournalWriter::new(Vec::new(), document).unwrap();
+        for second in 0..seconds {
+            for (i, (channel, samples)) in session.channels[..4].iter().enumerate() {
+                let rate = channel.sample_rate as usize;
+                journal
+                    .append(i, &samples[second * rate..(second + 1) * rate])
+                    .unwrap();
+            }
+            journal.flush().unwrap();
+        }
+
+        if finish {
+            let mut ld = Cursor::new(Vec::new());
+            let bytes = journal.finish(&mut ld).unwrap();
+            let written = LDReader::new(&mut ld).read_session().unwrap();
+            assert_eq!(written.channels.len(), 4);
+            bytes
+        } else {
+            journal.flush().unwrap();
+            let JournalWriter { sink, .. } = journal;
+            sink
+        }
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn recover_finished_journal() {
+        let session = sample1();
+        let bytes = write_journal(&session, 10, true);
+
+        let recovery = recover_journal(&mut Cursor::new(bytes)).unwrap();
+        assert!(recovery.finished);
+        assert_eq!(recovery.records, 40);
+        assert_eq!(recovery.dropped_bytes, 0);
+        assert_eq!(recovery.session.event, session.event);
+        assert_eq!(recovery.session.venue, session.venue);
+        for ((channel, samples), (original, expected)) in
+            recovery.session.channels.iter().zip(&session.channels)
+        {
+            assert_eq!(channel.name, original.name);
+            assert_eq!(samples[..], expected[..10 * original.sample_rate as usize]);
+        }
+    }
+
+    #[test]
+    fn recover_truncated_journal() {
+        let session = sample1();
+        let bytes = write_journal(&session, 5, false);
+
+        let metadata_len = write_journal(&session, 0, false).len();
+        let mut previous = 0;
+        for len in 0..=bytes.len() {
+            let result = recover_journal(&mut Cursor::new(&bytes[..len]));
+            if len < metadata_len {
+                assert!(matches!(result, Err(I2Error::InvalidJournal(_))));
+                continue;
+            }
+
+            let recovery = result.unwrap();
+            assert!(!recovery.finished);
+            let total: usize = recovery.session.channels.iter().map(|(_, s)| s.len()).sum();
+            assert!(total >= previous);
+            previous = total;
+            for ((channel, samples), (_, expected)) in
+                recovery.session.channels.iter().zip(&session.channels)
+            {
+                assert_eq!(channel.data_count as usize, samples.len());
+                assert_eq!(samples[..], expected[..samples.len()]);
+            }
+
+            // Every recovered journal can be written as a valid .ld file
+            if len % 97 == 0 || len == bytes.len() {
+                let mut ld = Cursor::new(Vec::new());
+                recovery.session.write(&mut ld).unwrap();
+                let mut reader = LDReader::new(&mut ld);
+                assert!(reader.validate().unwrap().is_valid());
+                assert_eq!(reader.read_session().unwrap().channels.len(), 4);
+            }
+        }
+        let rate: usize = session.channels[..4]
+            .iter()
+            .map(|(channel, _)| channel.sample_rate as usize)
+            .sum();
+        assert_eq!(previous, 5 * rate);
+
+        // A damaged record stops recovery, but keeps everything before it
+        let mut damaged = bytes.clone();
+        let last = damaged.len() - 3;
+        damaged[last] ^= 0xFF;
+        let recovery = recover_journal(&mut Cursor::new(damaged)).unwrap();
+        assert_eq!(recovery.records, 19);
+        assert!(recovery.dropped_bytes > 0);
+    }
+}
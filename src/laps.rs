@@ -0,0 +1,130 @@
+use crate::{I2Error, I2Result, LDReader};
+use std::io::{Read, Seek};
+
+/// Name of the channel that loggers use to count completed laps
+pub const LAP_NUMBER_CHANNEL: &str = "Lap Number";
+
+/// A single lap of a session, times are in seconds since the start of the log
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lap {
+    pub number: u32,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Lap {
+    /// Lap duration in seconds
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+
+    /// Returns true if `time` (in seconds) falls within this lap
+    pub fn contains(&self, time: f64) -> bool {
+        time >= self.start && time < self.end
+    }
+
+    /// Splits a session into laps using the values of a lap number channel
+    ///
+    /// A new lap starts every time the lap number changes, the last lap ends at the end of
+    /// the channel data.
+    pub fn from_lap_numbers(values: &[f64], sample_rate: u16) -> Vec<Lap> {
+        if sample_rate == 0 {
+            return vec![];
+        }
+
+        let rate = sample_rate as f64;
+        let mut laps: Vec<Lap> = vec![];
+        for (i, value) in values.iter().enumerate() {
+            let number = value.round().max(0.0) as u32;
+            let time = i as f64 / rate;
+
+            match laps.last_mut() {
+                Some(lap) if lap.number == number => {}
+                Some(lap) => {
+                    lap.end = time;
+                    laps.push(Lap {
+                        number,
+                        start: time,
+                        end: time,
+                    });
+                }
+                None => laps.push(Lap {
+                    number,
+                    start: time,
+                    end: time,
+                }),
+            }
+        }
+
+        if let Some(lap) = laps.last_mut() {
+            lap.end = values.len() as f64 / rate;
+        }
+        laps
+    }
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Splits the session into laps using the [LAP_NUMBER_CHANNEL] channel
+    ///
+    /// Calls [LDReader::read_header] if it hasn't been called before
+    pub fn read_laps(&mut self) -> I2Result<Vec<Lap>> {
+        let channel = self
+            .read_channels()?
+            .into_iter()
+            .find(|channel| channel.name == LAP_NUMBER_CHANNEL)
+            .ok_or_else(|| I2Error::ChannelNotFound(LAP_NUMBER_CHANNEL.to_string()))?;
+
+        let values = self.channel_values(&channel)?;
+        Ok(Lap::from_lap_numbers(&values, channel.sample_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LDReader, Lap};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn laps_from_lap_numbers() {
+        let laps = Lap::from_lap_numbers(&[0.0, 0.0, 1.0, 1.0, 1.0, 2.0], 2);
+        assert_eq!(
+            laps,
+            vec![
+                Lap {
+                    number: 0,
+                    start: 0.0,
+                    end: 1.0
+                },
+                Lap {
+                    number: 1,
+                    start: 1.0,
+                    end: 2.5
+                },
+                Lap {
+                    number: 2,
+                    start: 2.5,
+                    end: 3.0
+                },
+            ]
+        );
+        assert!(Lap::from_lap_numbers(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn read_sample1_laps() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let laps = reader.read_laps().unwrap();
+        assert_eq!(laps.len(), 5);
+        assert_eq!(laps[0].number, 0);
+        assert_eq!(laps[4].number, 4);
+        assert_eq!(laps[4].end, 454.0);
+        for pair in laps.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+    }
+}
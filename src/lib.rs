@@ -1,10 +1,68 @@
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "async")]
+mod async_io;
+mod calculus;
+#[cfg(feature = "capi")]
+mod capi;
+mod decode;
+mod diff;
+mod document;
 mod error;
+mod filter;
 mod full_header;
+mod histogram;
+mod ibt;
+#[cfg(not(target_arch = "wasm32"))]
+mod journal;
+mod laps;
+mod math;
+mod merge;
+#[cfg(feature = "python")]
+mod python;
 mod reader;
+#[cfg(not(target_arch = "wasm32"))]
+mod recorder;
+mod repair;
+mod resample;
+mod spectrum;
+mod stats;
 mod structs;
+mod telemetry;
+mod template;
+mod trim;
+mod units;
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
 mod writer;
 
+#[cfg(feature = "arrow")]
+pub use arrow::*;
+#[cfg(feature = "async")]
+pub use async_io::*;
+pub use calculus::*;
+pub use diff::*;
+pub use document::*;
 pub use error::*;
+pub use filter::*;
+pub use histogram::*;
+pub use ibt::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use journal::*;
+pub use laps::*;
+pub use math::*;
+pub use merge::*;
 pub use reader::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use recorder::*;
+pub use repair::*;
+pub use resample::*;
+pub use spectrum::*;
+pub use stats::*;
 pub use structs::*;
+pub use telemetry::*;
+pub use template::*;
+pub use units::*;
+pub use validate::*;
 pub use writer::*;
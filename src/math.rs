@@ -0,0 +1,736 @@
+use crate::calculus::{derivative, integral};
+use crate::filter::moving_average;
+use crate::resample::{resample, resampled_len};
+use crate::{ChannelMetadata, Datatype, I2Error, I2Result, LDReader, Sample};
+use std::io::{Read, Seek};
+
+/// A math channel expression, similar to the math channels in i2 Pro
+///
+/// Expressions support the usual arithmetic operators (`+`, `-`, `*`, `/`, `^`), comparisons
+/// (`<`, `<=`, `>`, `>=`, `==`, `!=`) which evaluate to `1.0` or `0.0`, and the functions
+/// `abs(x)`, `min(a, b, ..)`, `max(a, b, ..)`, `derivative(x)`, `integrate(x)` and
+/// `smooth(x, seconds)`.
+///
+/// Channels are referenced by name in single quotes, for example
+/// `'Wheel Speed FL' - 'Ground Speed'`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MathExpr {
+    root: Node,
+}
+
+/// The result of evaluating a [MathExpr]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MathChannel {
+    /// Sample Rate in Hz, this is the rate of the fastest channel in the expression
+    pub sample_rate: u16,
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Channel(String),
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Abs,
+    Min,
+    Max,
+    Derivative,
+    Integrate,
+    Smooth,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "abs" => Some(Function::Abs),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "derivative" => Some(Function::Derivative),
+            "integrate" => Some(Function::Integrate),
+            "smooth" => Some(Function::Smooth),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Channel(String),
+    Ident(String),
+    Op(BinaryOp),
+    Minus,
+    Plus,
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl MathExpr {
+    /// Parses a math channel expression
+    pub fn parse(expr: &str) -> I2Result<Self> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            len: expr.len(),
+        };
+        let root = parser.comparison()?;
+        if let Some((position, token)) = parser.tokens.get(parser.pos) {
+            return Err(I2Error::InvalidExpression {
+                position: *position,
+                message: format!("Unexpected token {:?}", token),
+            });
+        }
+        Ok(Self { root })
+    }
+
+    /// Names of all channels referenced in this expression, in order of first appearance
+    pub fn channel_names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        collect_channels(&self.root, &mut names);
+        names
+    }
+
+    /// Reads all referenced channels from `reader` and evaluates the expression
+    ///
+    /// Calls [LDReader::read_header] if it hasn't been called before
+    pub fn evaluate<S: Read + Seek>(&self, reader: &mut LDReader<S>) -> I2Result<MathChannel> {
+        let channels = reader.read_channels()?;
+
+        let mut inputs = vec![];
+        for name in self.channel_names() {
+            let channel = channels
+                .iter()
+                .find(|channel| channel.name == name)
+                .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))?;
+            let values = reader.channel_values(channel)?;
+            inputs.push((channel.clone(), values));
+        }
+
+        self.evaluate_channels(&inputs)
+    }
+
+    /// Evaluates the expression over already decoded channels
+    ///
+    /// All inputs are resampled to the rate of the fastest channel referenced by the expression.
+    pub fn evaluate_channels(
+        &self,
+        inputs: &[(ChannelMetadata, Vec<f64>)],
+    ) -> I2Result<MathChannel> {
+        let names = self.channel_names();
+        let referenced = names
+            .iter()
+            .map(|name| {
+                inputs
+                    .iter()
+                    .find(|(channel, _)| channel.name == *name)
+                    .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))
+            })
+            .collect::<I2Result<Vec<_>>>()?;
+
+        let sample_rate = referenced
+            .iter()
+            .map(|(channel, _)| channel.sample_rate)
+            .max()
+            .unwrap_or(0);
+        if sample_rate == 0 {
+            return Err(I2Error::InvalidExpression {
+                position: 0,
+                message: "Expression does not reference any channel with a sample rate".into(),
+            });
+        }
+
+        let len = referenced
+            .iter()
+            .map(|(channel, values)| resampled_len(values.len(), channel.sample_rate, sample_rate))
+            .max()
+            .unwrap_or(0);
+
+        let resampled = referenced
+            .iter()
+            .map(|(channel, values)| {
+                let values = resample(values, channel.sample_rate, sample_rate, len);
+                (channel.name.as_str(), values)
+            })
+            .collect();
+
+        let ctx = EvalContext {
+            sample_rate,
+            len,
+            channels: resampled,
+        };
+        let values = ctx.eval(&self.root);
+
+        Ok(MathChannel {
+            sample_rate,
+            values,
+        })
+    }
+}
+
+impl MathChannel {
+    /// Builds a [ChannelMetadata] and the encoded samples for this channel, ready to be passed
+    /// to [crate::LDWriter::with_channel]
+    ///
+    /// Math channels are stored as `F32` samples.
+    pub fn to_channel(
+        &self,
+        name: &str,
+        short_name: &str,
+        unit: &str,
+    ) -> I2Result<(ChannelMetadata, Vec<Sample>)> {
+        let channel = ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: self.values.len() as u32,
+            datatype: Datatype::F32,
+            sample_rate: self.sample_rate,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 0,
+            name: name.to_string(),
+            short_name: short_name.to_string(),
+            unit: unit.to_string(),
+        };
+
+        let samples = channel.encode_values(&self.values)?;
+        Ok((channel, samples))
+    }
+}
+
+fn collect_channels<'a>(node: &'a Node, names: &mut Vec<&'a str>) {
+    match node {
+        Node::Number(_) => {}
+        Node::Channel(name) => {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        Node::Neg(node) => collect_channels(node, names),
+        Node::Binary(_, lhs, rhs) => {
+            collect_channels(lhs, names);
+            collect_channels(rhs, names);
+        }
+        Node::Call(_, args) => args.iter().for_each(|arg| collect_channels(arg, names)),
+    }
+}
+
+fn tokenize(expr: &str) -> I2Result<Vec<(usize, Token)>> {
+    let mut tokens = vec![];
+    let mut chars = expr.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Op(BinaryOp::Mul),
+            '/' => Token::Op(BinaryOp::Div),
+            '^' => Token::Op(BinaryOp::Pow),
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '<' | '>' | '=' | '!' => {
+                let has_eq = chars.next_if(|(_, c)| *c == '=').is_some();
+                match (c, has_eq) {
+                    ('<', false) => Token::Op(BinaryOp::Lt),
+                    ('<', true) => Token::Op(BinaryOp::Le),
+                    ('>', false) => Token::Op(BinaryOp::Gt),
+                    ('>', true) => Token::Op(BinaryOp::Ge),
+                    ('=', true) => Token::Op(BinaryOp::Eq),
+                    ('!', true) => Token::Op(BinaryOp::Ne),
+                    _ => {
+                        return Err(I2Error::InvalidExpression {
+                            position: pos,
+                            message: format!("Expected '=' after '{}'", c),
+                        })
+                    }
+                }
+            }
+            '\'' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => name.push(c),
+                        None => {
+                            return Err(I2Error::InvalidExpression {
+                                position: pos,
+                                message: "Unterminated channel name".into(),
+                            })
+                        }
+                    }
+                }
+                Token::Channel(name)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                let value = number.parse().map_err(|_| I2Error::InvalidExpression {
+                    position: pos,
+                    message: format!("Invalid number '{}'", number),
+                })?;
+                Token::Number(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            _ => {
+                return Err(I2Error::InvalidExpression {
+                    position: pos,
+                    message: format!("Unexpected character '{}'", c),
+                })
+            }
+        };
+        tokens.push((pos, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser, each method parses one precedence level
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Length of the source expression, used to report errors at the end of the input
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.len)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> I2Result<T> {
+        Err(I2Error::InvalidExpression {
+            position: self.position(),
+            message: message.into(),
+        })
+    }
+
+    fn expect(&mut self, expected: Token) -> I2Result<()> {
+        if self.peek() == Some(&expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(format!("Expected {:?}", expected))
+        }
+    }
+
+    fn comparison(&mut self) -> I2Result<Node> {
+        let lhs = self.additive()?;
+        match self.peek() {
+            Some(Token::Op(
+                op @ (BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge
+                | BinaryOp::Eq
+                | BinaryOp::Ne),
+            )) => {
+                let op = *op;
+                self.pos += 1;
+                let rhs = self.additive()?;
+                Ok(Node::Binary(op, Box::new(lhs), Box::new(rhs)))
+            }
+            _ => Ok(lhs),
+        }
+    }
+
+    fn additive(&mut self) -> I2Result<Node> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.multiplicative()?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn multiplicative(&mut self) -> I2Result<Node> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op @ (BinaryOp::Mul | BinaryOp::Div))) => *op,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.unary()?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> I2Result<Node> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.pos += 1;
+                Ok(Node::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Plus) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> I2Result<Node> {
+        let base = self.primary()?;
+        if self.peek() == Some(&Token::Op(BinaryOp::Pow)) {
+            self.pos += 1;
+            // Right associative, and binds tighter than unary minus on the left
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> I2Result<Node> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return self.error("Unexpected end of expression"),
+        };
+
+        match token {
+            Token::Number(value) => {
+                self.pos += 1;
+                Ok(Node::Number(value))
+            }
+            Token::Channel(name) => {
+                self.pos += 1;
+                Ok(Node::Channel(name))
+            }
+            Token::LParen => {
+                self.pos += 1;
+                let node = self.comparison()?;
+                self.expect(Token::RParen)?;
+                Ok(node)
+            }
+            Token::Ident(name) => {
+                let function = match Function::from_name(&name) {
+                    Some(function) => function,
+                    None => return self.error(format!("Unknown function '{}'", name)),
+                };
+                self.pos += 1;
+                self.expect(Token::LParen)?;
+
+                let mut args = vec![self.comparison()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    args.push(self.comparison()?);
+                }
+                self.expect(Token::RParen)?;
+
+                self.check_arguments(&name, function, &args)?;
+                Ok(Node::Call(function, args))
+            }
+            token => self.error(format!("Unexpected token {:?}", token)),
+        }
+    }
+
+    fn check_arguments(&self, name: &str, function: Function, args: &[Node]) -> I2Result<()> {
+        let valid = match function {
+            Function::Abs | Function::Derivative | Function::Integrate => args.len() == 1,
+            Function::Min | Function::Max => args.len() >= 2,
+            Function::Smooth => args.len() == 2 && matches!(args[1], Node::Number(_)),
+        };
+        if valid {
+            return Ok(());
+        }
+
+        match function {
+            Function::Abs | Function::Derivative | Function::Integrate => {
+                self.error(format!("{} takes exactly one argument", name))
+            }
+            Function::Min | Function::Max => {
+                self.error(format!("{} takes at least two arguments", name))
+            }
+            Function::Smooth => {
+                self.error("smooth takes a channel and a constant window length in seconds")
+            }
+        }
+    }
+}
+
+struct EvalContext<'a> {
+    sample_rate: u16,
+    len: usize,
+    channels: Vec<(&'a str, Vec<f64>)>,
+}
+
+impl EvalContext<'_> {
+    fn eval(&self, node: &Node) -> Vec<f64> {
+        match node {
+            Node::Number(value) => vec![*value; self.len],
+            Node::Channel(name) => self
+                .channels
+                .iter()
+                .find(|(channel, _)| channel == name)
+                .map(|(_, values)| values.clone())
+                // All referenced channels are resolved before evaluation starts
+                .expect("Channel was not resolved before evaluation"),
+            Node::Neg(node) => self.eval(node).into_iter().map(|v| -v).collect(),
+            Node::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs);
+                let rhs = self.eval(rhs);
+                lhs.into_iter()
+                    .zip(rhs)
+                    .map(|(a, b)| apply_binary(*op, a, b))
+                    .collect()
+            }
+            Node::Call(function, args) => self.call(*function, args),
+        }
+    }
+
+    fn call(&self, function: Function, args: &[Node]) -> Vec<f64> {
+        match function {
+            Function::Abs => self.eval(&args[0]).into_iter().map(f64::abs).collect(),
+            Function::Min | Function::Max => {
+                let mut values = self.eval(&args[0]);
+                for arg in &args[1..] {
+                    for (value, other) in values.iter_mut().zip(self.eval(arg)) {
+                        *value = if function == Function::Min {
+                            value.min(other)
+                        } else {
+                            value.max(other)
+                        };
+                    }
+                }
+                values
+            }
+            Function::Derivative => derivative(&self.eval(&args[0]), self.sample_rate),
+            Function::Integrate => integral(&self.eval(&args[0]), self.sample_rate, &[]),
+            Function::Smooth => {
+                let values = self.eval(&args[0]);
+                let seconds = match args[1] {
+                    Node::Number(seconds) => seconds,
+                    _ => unreachable!("smooth window is checked while parsing"),
+                };
+                let samples = (seconds * self.sample_rate as f64).round() as usize;
+                moving_average(&values, samples.max(1))
+            }
+        }
+    }
+}
+
+fn apply_binary(op: BinaryOp, a: f64, b: f64) -> f64 {
+    let bool_value = |b: bool| if b { 1.0 } else { 0.0 };
+    match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        BinaryOp::Div => a / b,
+        BinaryOp::Pow => a.powf(b),
+        BinaryOp::Lt => bool_value(a < b),
+        BinaryOp::Le => bool_value(a <= b),
+        BinaryOp::Gt => bool_value(a > b),
+        BinaryOp::Ge => bool_value(a >= b),
+        BinaryOp::Eq => bool_value(a == b),
+        BinaryOp::Ne => bool_value(a != b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChannelMetadata, Datatype, I2Error, LDReader, LDWriter, MathExpr, Sample};
+    use std::fs;
+    use std::io::Cursor;
+
+    fn channel(name: &str, sample_rate: u16) -> ChannelMetadata {
+        ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            datatype: Datatype::I16,
+            sample_rate,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 0,
+            name: name.to_string(),
+            short_name: "".to_string(),
+            unit: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let inputs = vec![(channel("A", 1), vec![2.0])];
+        let eval = |expr: &str| {
+            MathExpr::parse(expr)
+                .unwrap()
+                .evaluate_channels(&inputs)
+                .unwrap()
+                .values[0]
+        };
+
+        assert_eq!(eval("'A' + 3 * 4"), 14.0);
+        assert_eq!(eval("('A' + 3) * 4"), 20.0);
+        assert_eq!(eval("-'A' ^ 2"), -4.0);
+        assert_eq!(eval("2 ^ 3 ^ 2 - 'A'"), 510.0);
+        assert_eq!(eval("'A' * 2 > 3"), 1.0);
+        assert_eq!(eval("'A' != 2"), 0.0);
+        assert_eq!(eval("max(abs(-'A'), 1, 5) + min('A', 0.5)"), 5.5);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            MathExpr::parse("'Ground Speed"),
+            Err(I2Error::InvalidExpression { position: 0, .. })
+        ));
+        assert!(matches!(
+            MathExpr::parse("1 + "),
+            Err(I2Error::InvalidExpression { position: 4, .. })
+        ));
+        assert!(matches!(
+            MathExpr::parse("sqrt(1)"),
+            Err(I2Error::InvalidExpression { position: 0, .. })
+        ));
+        assert!(matches!(
+            MathExpr::parse("smooth('A', 'B')"),
+            Err(I2Error::InvalidExpression { .. })
+        ));
+        assert!(matches!(
+            MathExpr::parse("1 = 2"),
+            Err(I2Error::InvalidExpression { position: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn mixed_sample_rates_resample_to_fastest() {
+        let inputs = vec![
+            (channel("Slow", 1), vec![0.0, 10.0]),
+            (channel("Fast", 4), vec![1.0; 8]),
+        ];
+
+        let result = MathExpr::parse("'Slow' + 'Fast'")
+            .unwrap()
+            .evaluate_channels(&inputs)
+            .unwrap();
+
+        assert_eq!(result.sample_rate, 4);
+        assert_eq!(
+            result.values,
+            vec![1.0, 3.5, 6.0, 8.5, 11.0, 11.0, 11.0, 11.0]
+        );
+    }
+
+    #[test]
+    fn derivative_and_integrate() {
+        let inputs = vec![(channel("Distance", 2), vec![0.0, 1.0, 2.0, 3.0, 4.0])];
+
+        let speed = MathExpr::parse("derivative('Distance')")
+            .unwrap()
+            .evaluate_channels(&inputs)
+            .unwrap();
+        assert_eq!(speed.values, vec![2.0; 5]);
+
+        let area = MathExpr::parse("integrate('Distance')")
+            .unwrap()
+            .evaluate_channels(&inputs)
+            .unwrap();
+        assert_eq!(area.values, vec![0.0, 0.25, 1.0, 2.25, 4.0]);
+
+        let smooth = MathExpr::parse("smooth('Distance', 1.5)")
+            .unwrap()
+            .evaluate_channels(&inputs)
+            .unwrap();
+        assert_eq!(smooth.values, vec![0.5, 1.0, 2.0, 3.0, 3.5]);
+    }
+
+    #[test]
+    fn encode_offset_channel() {
+        let mut offset = channel("Offset", 1);
+        offset.offset = 10;
+        assert!(matches!(
+            Sample::encode_f64(1.0, &offset),
+            Err(I2Error::UnsupportedOffset(10))
+        ));
+    }
+
+    #[test]
+    fn evaluate_sample1_and_write() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let expr = MathExpr::parse("'Wheel Speed FL' - 'Ground Speed'").unwrap();
+        assert_eq!(expr.channel_names(), vec!["Wheel Speed FL", "Ground Speed"]);
+
+        let result = expr.evaluate(&mut reader).unwrap();
+        assert_eq!(result.sample_rate, 20);
+        assert_eq!(result.values.len(), 9080);
+
+        let missing = MathExpr::parse("'Not A Channel' * 2").unwrap();
+        assert!(matches!(
+            missing.evaluate(&mut reader),
+            Err(I2Error::ChannelNotFound(_))
+        ));
+
+        let header = reader.read_header().unwrap();
+        let (channel, samples) = result
+            .to_channel("Wheel Slip FL", "WSlipFL", "km/h")
+            .unwrap();
+
+        let mut output = Cursor::new(Vec::new());
+        LDWriter::new(&mut output, header)
+            .with_channel(channel, samples)
+            .write()
+            .unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        let channels = reader.read_channels().unwrap();
+        assert_eq!(channels.len(), 1);
+        let values = reader.channel_values(&channels[0]).unwrap();
+        for (written, expected) in values.iter().zip(&result.values) {
+            assert!((written - expected).abs() < 0.001);
+        }
+    }
+}
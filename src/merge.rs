@@ -0,0 +1,375 @@
+use crate::resample::{resample, resampled_len};
+use crate::{ChannelMetadata, ChannelWithSamples, I2Error, I2Result, LDReader, Sample, Session};
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+
+/// How the sessions passed to [merge_sessions] are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeMode {
+    /// Appends each session after the previous one, channels with the same name are joined
+    ///
+    /// Used for several logs of the same car, such as endurance stints.
+    Concatenate,
+    /// Adds the channels of every session to the first one, sharing the same start time
+    ///
+    /// Used to combine data logged by separate devices, such as a video or GPS box.
+    SideBySide,
+}
+
+/// What to do when [MergeMode::SideBySide] finds a channel name that is already in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameCollision {
+    /// Keeps the channel of the earlier session and drops the later one
+    KeepFirst,
+    /// Appends the number of the session (starting at 1) to the name of the later channel
+    Rename,
+    /// Fails with [I2Error::ChannelNameCollision]
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeOptions {
+    pub mode: MergeMode,
+    pub collision: NameCollision,
+    /// Time offset of each session in seconds, sessions without an offset use 0
+    ///
+    /// With [MergeMode::Concatenate] this is the gap between the end of the previous session and
+    /// the start of this one. With [MergeMode::SideBySide] this is the time at which the session
+    /// starts relative to the first session, negative offsets drop the start of the session.
+    pub time_offsets: Vec<f64>,
+}
+
+impl MergeOptions {
+    pub fn new(mode: MergeMode) -> Self {
+        Self {
+            mode,
+            collision: NameCollision::Rename,
+            time_offsets: vec![],
+        }
+    }
+
+    fn offset(&self, session: usize) -> f64 {
+        self.time_offsets.get(session).copied().unwrap_or(0.0)
+    }
+}
+
+/// Merges several sessions into one
+///
+/// The header, event, venue and vehicle of the first session are used for the merged session.
+/// Gaps in the merged channels are filled by repeating the last sample before the gap.
+pub fn merge_sessions(sessions: &[Session], options: &MergeOptions) -> I2Result<Session> {
+    let first = sessions
+        .first()
+        .ok_or_else(|| I2Error::InvalidMerge("No sessions to merge".into()))?;
+
+    let channels = match options.mode {
+        MergeMode::Concatenate => concatenate(sessions, options)?,
+        MergeMode::SideBySide => side_by_side(sessions, options)?,
+    };
+
+    Ok(Session {
+        header: first.header.clone(),
+        event: first.event.clone(),
+        venue: first.venue.clone(),
+        vehicle: first.vehicle.clone(),
+        channels,
+    })
+}
+
+/// Reads every file from `readers` and merges them, see [merge_sessions]
+pub fn merge_readers<S: Read + Seek>(
+    readers: &mut [LDReader<S>],
+    options: &MergeOptions,
+) -> I2Result<Session> {
+    let sessions = readers
+        .iter_mut()
+        .map(|reader| reader.read_session())
+        .collect::<I2Result<Vec<_>>>()?;
+    merge_sessions(&sessions, options)
+}
+
+fn concatenate(sessions: &[Session], options: &MergeOptions) -> I2Result<Vec<ChannelWithSamples>> {
+    let mut starts = vec![];
+    let mut end = 0.0;
+    for (i, session) in sessions.iter().enumerate() {
+        let start = if i == 0 {
+            0.0
+        } else {
+            end + options.offset(i).max(0.0)
+        };
+        starts.push(start);
+        end = start + session.duration();
+    }
+
+    // Channels are ordered by their first appearance
+    let mut names = vec![];
+    for session in sessions {
+        for (channel, _) in &session.channels {
+            if !names.contains(&&channel.name) {
+                names.push(&channel.name);
+            }
+        }
+    }
+
+    let mut merged = vec![];
+    for name in names {
+        let mut target: Option<ChannelMetadata> = None;
+        let mut samples: Vec<Sample> = vec![];
+        for (session, start) in sessions.iter().zip(&starts) {
+            let (channel, data) = match session.channel(name) {
+                Some(channel) => channel,
+                None => continue,
+            };
+            let target = target.get_or_insert_with(|| channel.clone());
+            if data.is_empty() {
+                continue;
+            }
+            if target.sample_rate == 0 {
+                samples.extend(data.iter().cloned());
+                continue;
+            }
+
+            let start = (start * target.sample_rate as f64).round() as usize;
+            if samples.len() < start {
+                if let Some(fill) = hold_sample(&samples, target) {
+                    samples.resize(start, fill);
+                }
+            }
+            samples.extend(convert_samples(channel, data, target)?);
+        }
+
+        if let Some(mut channel) = target {
+            channel.data_count = samples.len() as u32;
+            merged.push((channel, samples));
+        }
+    }
+    Ok(merged)
+}
+
+fn side_by_side(sessions: &[Session], options: &MergeOptions) -> I2Result<Vec<ChannelWithSamples>> {
+    let mut names: HashSet<String> = HashSet::new();
+    let mut merged = vec![];
+    for (i, session) in sessions.iter().enumerate() {
+        let offset = options.offset(i);
+        for (channel, samples) in &session.channels {
+            let mut channel = channel.clone();
+            if names.contains(&channel.name) {
+                match options.collision {
+                    NameCollision::KeepFirst => continue,
+                    NameCollision::Error => {
+                        return Err(I2Error::ChannelNameCollision(channel.name));
+                    }
+                    NameCollision::Rename => rename(&mut channel, i + 1, &names),
+                }
+            }
+            names.insert(channel.name.clone());
+
+            let shift = (offset.abs() * channel.sample_rate as f64).round() as usize;
+            let samples = if offset >= 0.0 && shift > 0 && !samples.is_empty() {
+                let mut shifted = vec![samples[0].clone(); shift];
+                shifted.extend(samples.iter().cloned());
+                shifted
+            } else {
+                samples.iter().skip(shift).cloned().collect()
+            };
+
+            channel.data_count = samples.len() as u32;
+            merged.push((channel, samples));
+        }
+    }
+    Ok(merged)
+}
+
+/// Appends ` <session>` to the name and short name of `channel`, counting up until the name is
+/// unique
+fn rename(channel: &mut ChannelMetadata, session: usize, names: &HashSet<String>) {
+    let base = channel.name.clone();
+    let short = channel.short_name.clone();
+    let mut number = session;
+    loop {
+        let suffix = format!(" {}", number);
+        let name = format!("{}{}", truncate(&base, 32 - suffix.len()), suffix);
+        if !names.contains(&name) {
+            channel.name = name;
+            channel.short_name = format!("{}{}", truncate(&short, 8 - suffix.len()), number);
+            return;
+        }
+        number += 1;
+    }
+}
+
+fn truncate(string: &str, max_len: usize) -> &str {
+    let mut end = string.len().min(max_len);
+    while !string.is_char_boundary(end) {
+        end -= 1;
+    }
+    &string[..end]
+}
+
+/// Sample used to fill gaps, the last sample or a 0 value if there is none
+///
+/// Gives `None` when there is no sample and 0 can't be encoded in the datatype of `channel`.
+fn hold_sample(samples: &[Sample], channel: &ChannelMetadata) -> Option<Sample> {
+    match samples.last() {
+        Some(sample) => Some(sample.clone()),
+        None => Sample::encode_f64(0.0, channel).ok(),
+    }
+}
+
+/// Converts samples of `channel` into samples of `target`, re-encoding and resampling them if the
+/// channels don't match
+fn convert_samples(
+    channel: &ChannelMetadata,
+    samples: &[Sample],
+    target: &ChannelMetadata,
+) -> I2Result<Vec<Sample>> {
+    let same_encoding = channel.datatype == target.datatype
+        && channel.offset == target.offset
+        && channel.mul == target.mul
+        && channel.scale == target.scale
+        && channel.dec_places == target.dec_places;
+    if same_encoding && channel.sample_rate == target.sample_rate {
+        return Ok(samples.to_vec());
+    }
+
+    let values: Vec<f64> = samples.iter().map(|s| s.decode_f64(channel)).collect();
+    let len = resampled_len(values.len(), channel.sample_rate, target.sample_rate);
+    let values = resample(&values, channel.sample_rate, target.sample_rate, len);
+    target.encode_values(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        merge_readers, merge_sessions, resample, Datatype, I2Error, LDReader, MergeMode,
+        MergeOptions, NameCollision, Sample, Session,
+    };
+    use std::fs;
+    use std::io::Cursor;
+
+    fn sample1() -> Session {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        LDReader::new(&mut cursor).read_session().unwrap()
+    }
+
+    #[test]
+    fn concatenate_stints() {
+        let session = sample1();
+        let mut options = MergeOptions::new(MergeMode::Concatenate);
+        options.time_offsets = vec![0.0, 10.0];
+        let merged = merge_sessions(&[session.clone(), session.clone()], &options).unwrap();
+
+        assert_eq!(merged.channels.len(), session.channels.len());
+        assert!((merged.duration() - (454.0 * 2.0 + 10.0)).abs() < 1e-9);
+
+        let (rpm, samples) = merged.channel("Engine RPM").unwrap();
+        let (_, original) = session.channel("Engine RPM").unwrap();
+        let rate = rpm.sample_rate as usize;
+        let n = original.len();
+        assert_eq!(samples.len(), 2 * n + 10 * rate);
+        assert_eq!(&samples[..n], &original[..]);
+        assert!(samples[n..n + 10 * rate]
+            .iter()
+            .all(|s| *s == original[n - 1]));
+        assert_eq!(&samples[n + 10 * rate..], &original[..]);
+    }
+
+    #[test]
+    fn concatenate_reencodes_mismatched_channels() {
+        let session = sample1();
+        let mut other = session.clone();
+        other.channels.retain(|(c, _)| c.name == "Ground Speed");
+        let (speed, samples) = &mut other.channels[0];
+        let values: Vec<f64> = samples.iter().map(|s| s.decode_f64(speed)).collect();
+        let values = resample(&values, 10, 20, values.len() * 2);
+        speed.dec_places += 1;
+        speed.sample_rate = 20;
+        *samples = speed.encode_values(&values).unwrap();
+
+        let options = MergeOptions::new(MergeMode::Concatenate);
+        let merged = merge_sessions(&[session.clone(), other], &options).unwrap();
+        let (speed, samples) = merged.channel("Ground Speed").unwrap();
+        let (_, original) = session.channel("Ground Speed").unwrap();
+        // The second session is resampled to 10Hz and re-encoded, so it has the same samples
+        assert_eq!(samples.len(), original.len() * 2);
+        assert_eq!(speed.dec_places, 1);
+        assert_eq!(&samples[original.len()..original.len() + 2], &original[..2]);
+    }
+
+    #[test]
+    fn side_by_side_with_collisions() {
+        let session = sample1();
+        let sessions = [session.clone(), session.clone()];
+
+        let mut options = MergeOptions::new(MergeMode::SideBySide);
+        options.time_offsets = vec![0.0, 1.0];
+        let merged = merge_sessions(&sessions, &options).unwrap();
+        assert_eq!(merged.channels.len(), 2 * session.channels.len());
+        let (rpm, samples) = merged.channel("Engine RPM 2").unwrap();
+        assert!(rpm.short_name.len() <= 8);
+        assert!(rpm.short_name.ends_with('2'));
+        let (_, original) = session.channel("Engine RPM").unwrap();
+        let shift = rpm.sample_rate as usize;
+        assert!(samples[..shift].iter().all(|s| *s == original[0]));
+        assert_eq!(&samples[shift..], &original[..]);
+
+        options.collision = NameCollision::KeepFirst;
+        let merged = merge_sessions(&sessions, &options).unwrap();
+        assert_eq!(merged.channels, session.channels);
+
+        options.collision = NameCollision::Error;
+        assert!(matches!(
+            merge_sessions(&sessions, &options),
+            Err(I2Error::ChannelNameCollision(name)) if name == session.channels[0].0.name
+        ));
+    }
+
+    #[test]
+    fn merge_empty_invalid_channel() {
+        let mut session = sample1();
+        let mut empty = session.channels[0].0.clone();
+        empty.name = "Empty".to_string();
+        empty.datatype = Datatype::Invalid;
+        empty.data_count = 0;
+        session.channels.push((empty, vec![]));
+        let sessions = [session.clone(), session.clone()];
+
+        let mut options = MergeOptions::new(MergeMode::Concatenate);
+        options.time_offsets = vec![0.0, 10.0];
+        let merged = merge_sessions(&sessions, &options).unwrap();
+        let (channel, samples) = merged.channel("Empty").unwrap();
+        assert_eq!(channel.data_count, 0);
+        assert!(samples.is_empty());
+
+        let mut options = MergeOptions::new(MergeMode::SideBySide);
+        options.time_offsets = vec![0.0, 1.0];
+        let merged = merge_sessions(&sessions, &options).unwrap();
+        assert!(merged.channel("Empty 2").unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn merge_and_write() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut first = Cursor::new(bytes.clone());
+        let mut second = Cursor::new(bytes);
+        let mut readers = [LDReader::new(&mut first), LDReader::new(&mut second)];
+
+        let mut options = MergeOptions::new(MergeMode::SideBySide);
+        options.time_offsets = vec![0.0, -1.0];
+        let merged = merge_readers(&mut readers, &options).unwrap();
+
+        let mut output = Cursor::new(Vec::new());
+        merged.write(&mut output).unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        let written = reader.read_session().unwrap();
+        assert_eq!(written.header.num_channels, 156);
+        assert_eq!(written.header.channel_data_ptr, 0x3448 + 156 * 124);
+        assert_eq!(written.venue, merged.venue);
+        let (channel, samples) = written.channel("Engine RPM 2").unwrap();
+        assert_eq!(channel.data_count as usize, samples.len());
+        assert_eq!(&samples[..], &merged.channel("Engine RPM 2").unwrap().1[..]);
+        assert!(!samples.is_empty() && samples.iter().all(|s| matches!(s, Sample::I16(_))));
+    }
+}
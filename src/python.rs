@@ -0,0 +1,318 @@
+//! Python bindings, built into a wheel with `maturin build --features python`
+
+use crate::{
+    ChannelMetadata, ChannelWithSamples, Datatype, Event, Header, I2Error, LDReader, LDWriter,
+    SessionDocument, Vehicle, Venue,
+};
+use numpy::{AllowTypeChange, PyArray1, PyArrayLike1};
+use pyo3::exceptions::{PyIOError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+impl From<I2Error> for PyErr {
+    fn from(error: I2Error) -> Self {
+        match error {
+            I2Error::IOError(e) => PyIOError::new_err(e.to_string()),
+            I2Error::ChannelNotFound(name) => PyKeyError::new_err(name),
+            error => PyValueError::new_err(error.to_string()),
+        }
+    }
+}
+
+/// An ld file opened for reading
+#[pyclass(name = "LdFile", module = "motec_i2")]
+struct LdFile {
+    reader: LDReader<BufReader<File>>,
+    document: SessionDocument,
+}
+
+#[pymethods]
+impl LdFile {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        let mut reader = LDReader::open(path)?;
+        let document = reader.read_session_document()?;
+        Ok(Self { reader, document })
+    }
+
+    #[getter]
+    fn header<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        header_to_dict(py, &self.document.header)
+    }
+
+    #[getter]
+    fn event<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.document
+            .event
+            .as_ref()
+            .map(|event| {
+                let dict = PyDict::new(py);
+                dict.set_item("name", &event.name)?;
+                dict.set_item("session", &event.session)?;
+                dict.set_item("comment", &event.comment)?;
+                Ok(dict)
+            })
+            .transpose()
+    }
+
+    #[getter]
+    fn venue<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.document
+            .venue
+            .as_ref()
+            .map(|venue| {
+                let dict = PyDict::new(py);
+                dict.set_item("name", &venue.name)?;
+                Ok(dict)
+            })
+            .transpose()
+    }
+
+    #[getter]
+    fn vehicle<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.document
+            .vehicle
+            .as_ref()
+            .map(|vehicle| {
+                let dict = PyDict::new(py);
+                dict.set_item("id", &vehicle.id)?;
+                dict.set_item("weight", vehicle.weight)?;
+                dict.set_item("type", &vehicle._type)?;
+                dict.set_item("comment", &vehicle.comment)?;
+                Ok(dict)
+            })
+            .transpose()
+    }
+
+    /// Metadata of every channel as a list of dictionaries
+    #[getter]
+    fn channels<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.document
+            .channels
+            .iter()
+            .map(|channel| {
+                let dict = PyDict::new(py);
+                dict.set_item("name", &channel.name)?;
+                dict.set_item("short_name", &channel.short_name)?;
+                dict.set_item("unit", &channel.unit)?;
+                dict.set_item("sample_rate", channel.sample_rate)?;
+                dict.set_item("data_count", channel.data_count)?;
+                dict.set_item("datatype", format!("{:?}", channel.datatype))?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Returns the physical values of a channel and the time of every sample in seconds
+    #[allow(clippy::type_complexity)]
+    fn channel<'py>(
+        &mut self,
+        py: Python<'py>,
+        name: &str,
+    ) -> PyResult<(Bound<'py, PyArray1<f64>>, Bound<'py, PyArray1<f64>>)> {
+        let channel = self
+            .document
+            .channel(name)
+            .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))?
+            .clone();
+        let values = self.reader.channel_values(&channel)?;
+        let rate = channel.sample_rate as f64;
+        let time = (0..values.len()).map(|i| i as f64 / rate).collect();
+        Ok((PyArray1::from_vec(py, values), PyArray1::from_vec(py, time)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.document.channels.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "LdFile(venue={:?}, date={:?}, channels={})",
+            self.document.header.venue,
+            self.document.header.date_string,
+            self.document.channels.len()
+        )
+    }
+}
+
+/// Builds an ld file from NumPy arrays
+#[pyclass(name = "LdWriter", module = "motec_i2")]
+struct LdWriter {
+    path: PathBuf,
+    header: Header,
+    event: Option<Event>,
+    venue: Option<Venue>,
+    vehicle: Option<Vehicle>,
+    channels: Vec<ChannelWithSamples>,
+}
+
+#[pymethods]
+impl LdWriter {
+    /// `header` takes the same keys as `LdFile.header`, missing keys are left empty
+    #[new]
+    #[pyo3(signature = (path, header=None))]
+    fn new(py: Python<'_>, path: PathBuf, header: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let header = match header {
+            Some(dict) => header_from_dict(dict)?,
+            None => header_from_dict(&PyDict::new(py))?,
+        };
+        Ok(Self {
+            path,
+            header,
+            event: None,
+            venue: None,
+            vehicle: None,
+            channels: vec![],
+        })
+    }
+
+    #[pyo3(signature = (name, session="", comment=""))]
+    fn set_event(&mut self, name: &str, session: &str, comment: &str) {
+        self.event = Some(Event {
+            name: name.to_string(),
+            session: session.to_string(),
+            comment: comment.to_string(),
+            venue_addr: 0,
+        });
+    }
+
+    /// Requires an event, see `set_event`
+    fn set_venue(&mut self, name: &str) {
+        self.venue = Some(Venue {
+            name: name.to_string(),
+            vehicle_addr: 0,
+        });
+    }
+
+    /// Requires a venue, see `set_venue`
+    #[pyo3(signature = (id, weight=0, vehicle_type="", comment=""))]
+    fn set_vehicle(&mut self, id: &str, weight: u32, vehicle_type: &str, comment: &str) {
+        self.vehicle = Some(Vehicle {
+            id: id.to_string(),
+            weight,
+            _type: vehicle_type.to_string(),
+            comment: comment.to_string(),
+        });
+    }
+
+    /// Adds a channel with physical `values` sampled at `sample_rate` Hz
+    ///
+    /// The datatype and decimal places are chosen to fit the values.
+    #[pyo3(signature = (name, values, sample_rate, unit="", short_name=None))]
+    fn add_channel(
+        &mut self,
+        name: &str,
+        values: PyArrayLike1<'_, f64, AllowTypeChange>,
+        sample_rate: u16,
+        unit: &str,
+        short_name: Option<&str>,
+    ) -> PyResult<()> {
+        if sample_rate == 0 {
+            return Err(PyValueError::new_err("sample_rate must be at least 1 Hz"));
+        }
+        let values: Vec<f64> = values.as_array().iter().copied().collect();
+
+        let mut channel = ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: values.len() as u32,
+            datatype: Datatype::I16,
+            sample_rate,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 0,
+            name: name.to_string(),
+            short_name: short_name
+                .unwrap_or(name)
+                .chars()
+                .take(8)
+                .collect::<String>(),
+            unit: unit.to_string(),
+        };
+        channel.fit_to_values(&values);
+        let samples = channel.encode_values(&values)?;
+        self.channels.push((channel, samples));
+        Ok(())
+    }
+
+    /// Writes the file to `path`
+    fn write(&self) -> PyResult<()> {
+        let mut writer = LDWriter::create(&self.path, self.header.clone())?;
+        if let Some(event) = &self.event {
+            writer = writer.with_event(event.clone());
+        }
+        if let Some(venue) = &self.venue {
+            writer = writer.with_venue(venue.clone());
+        }
+        if let Some(vehicle) = &self.vehicle {
+            writer = writer.with_vehicle(vehicle.clone());
+        }
+        for (channel, samples) in &self.channels {
+            writer = writer.with_channel(channel.clone(), samples.clone());
+        }
+        writer.write()?;
+        Ok(())
+    }
+}
+
+fn header_to_dict<'py>(py: Python<'py>, header: &Header) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("device_serial", header.device_serial)?;
+    dict.set_item("device_type", &header.device_type)?;
+    dict.set_item("device_version", header.device_version)?;
+    dict.set_item("num_channels", header.num_channels)?;
+    dict.set_item("date", &header.date_string)?;
+    dict.set_item("time", &header.time_string)?;
+    dict.set_item("driver", &header.driver)?;
+    dict.set_item("vehicle_id", &header.vehicleid)?;
+    dict.set_item("venue", &header.venue)?;
+    dict.set_item("session", &header.session)?;
+    dict.set_item("short_comment", &header.short_comment)?;
+    Ok(dict)
+}
+
+fn header_from_dict(dict: &Bound<'_, PyDict>) -> PyResult<Header> {
+    let string = |key: &str| -> PyResult<String> {
+        Ok(match dict.get_item(key)? {
+            Some(value) => value.extract()?,
+            None => String::new(),
+        })
+    };
+    Ok(Header {
+        channel_meta_ptr: 0,
+        channel_data_ptr: 0,
+        event_ptr: 0,
+        device_serial: match dict.get_item("device_serial")? {
+            Some(value) => value.extract()?,
+            None => 0,
+        },
+        device_type: match dict.get_item("device_type")? {
+            Some(value) => value.extract()?,
+            None => "ADL".to_string(),
+        },
+        device_version: match dict.get_item("device_version")? {
+            Some(value) => value.extract()?,
+            None => 420,
+        },
+        num_channels: 0,
+        date_string: string("date")?,
+        time_string: string("time")?,
+        driver: string("driver")?,
+        vehicleid: string("vehicle_id")?,
+        venue: string("venue")?,
+        session: string("session")?,
+        short_comment: string("short_comment")?,
+    })
+}
+
+#[pymodule]
+fn motec_i2(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<LdFile>()?;
+    module.add_class::<LdWriter>()?;
+    Ok(())
+}
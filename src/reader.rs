@@ -1,109 +1,61 @@
 use crate::{ChannelMetadata, Datatype, Event, Header, I2Error, I2Result, Sample, Vehicle, Venue};
 use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufReader;
 use std::io::{Read, Seek, SeekFrom};
-use std::{io, iter};
+use std::ops::Range;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
 
 pub(crate) const LD_HEADER_MARKER: u32 = 64;
 
+/// Largest number of bytes [LDReader::read_all_channel_data] reads at once, unless a single
+/// channel is larger
+const BULK_READ_SIZE: u64 = 16 * 1024 * 1024;
+/// Gaps between the data of channels up to this size are read instead of seeking over them
+const BULK_MAX_GAP: u64 = 64 * 1024;
+
+/// Reads ld files from `S`
+///
+/// The reader owns its source, pass `&mut source` to keep using the source afterwards.
 #[derive(Debug)]
-pub struct LDReader<'a, S: Read + Seek> {
-    source: &'a mut S,
+pub struct LDReader<S: Read + Seek> {
+    source: S,
     header: Option<Header>,
 }
 
-impl<'a, S: Read + Seek> LDReader<'a, S> {
-    pub fn new(source: &'a mut S) -> Self {
+// There is no file system on wasm32-unknown-unknown, read from memory with LDReader::new instead
+#[cfg(not(target_arch = "wasm32"))]
+impl LDReader<BufReader<File>> {
+    /// Opens the file at `path` for buffered reading
+    pub fn open<P: AsRef<Path>>(path: P) -> I2Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    pub fn new(source: S) -> Self {
         Self {
             source,
             header: None,
         }
     }
 
-    // TODO: Remove asserts and change into a proper error type
+    /// Returns the source, consuming the reader
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
     pub fn read_header(&mut self) -> I2Result<Header> {
         // Header is always at start
         self.source.seek(SeekFrom::Start(0))?;
 
-        let ldmarker = self.source.read_u32::<LittleEndian>()?;
-        if ldmarker != LD_HEADER_MARKER {
-            return Err(I2Error::InvalidHeaderMarker {
-                found: ldmarker,
-                expected: LD_HEADER_MARKER,
-            });
-        }
-
-        let _unknown = self.source.read_u32::<LittleEndian>()?;
-
-        let channel_meta_ptr = self.source.read_u32::<LittleEndian>()?;
-        let channel_data_ptr = self.source.read_u32::<LittleEndian>()?;
-
-        let mut _unknown = self.read_bytes(20)?;
-        // assert_eq!(_unknown, [0u8; 20]);
-
-        // Sample1.ld has this at addr 0x6E2, that is probably the length of the header????
-        let event_ptr = self.source.read_u32::<LittleEndian>()?;
-
-        let mut _unknown = self.read_bytes(24)?;
-        // Not 0 in 20160903-0051401.ld
-        // assert_eq!(_unknown, [0u8; 24]);
-
-        // TODO: These may not actually be const...
-        let _unknown_const_1 = self.source.read_u16::<LittleEndian>()?;
-        // assert_eq!(_unknown_const_1, 0x0000);
-        let _unknown_const_2 = self.source.read_u16::<LittleEndian>()?;
-        // assert_eq!(_unknown_const_2, 0x4240);
-        let _unknown_const_3 = self.source.read_u16::<LittleEndian>()?;
-        // assert_eq!(_unknown_const_3, 0x000F);
-
-        let device_serial = self.source.read_u32::<LittleEndian>()?;
-        let device_type = self.read_string(8)?;
-        let device_version = self.source.read_u16::<LittleEndian>()?;
-
-        // TODO: This may not actually be const...
-        let _unknown_const_4 = self.source.read_u16::<LittleEndian>()?;
-        // assert_eq!(_unknown_const_4, 0x0080);
-
-        let num_channels = self.source.read_u32::<LittleEndian>()?;
-        let _unknown = self.source.read_u32::<LittleEndian>()?;
-
-        let date_string = self.read_string(16)?;
-        let _unknown = self.read_bytes(16)?;
-        let time_string = self.read_string(16)?;
-        let _unknown = self.read_bytes(16)?;
-
-        let driver = self.read_string(64)?;
-        let vehicleid = self.read_string(64)?;
-        let _unknown = self.read_bytes(64)?;
-        let venue = self.read_string(64)?;
-        let _unknown = self.read_bytes(64)?;
-
-        let _unknown = self.read_bytes(1024)?;
-
-        let _pro_logging_bytes = self.source.read_u32::<LittleEndian>()?;
-
-        let _unknown = self.read_bytes(2)?;
-        let session = self.read_string(64)?;
-        let short_comment = self.read_string(64)?;
-        let _unknown = self.read_bytes(126)?; // Probably long_comment? + some 2byte
-
-        //let long_comment = self.read_string(??);
-
-        let header = Header {
-            channel_meta_ptr,
-            channel_data_ptr,
-            event_ptr,
-            device_serial,
-            device_type,
-            device_version,
-            num_channels,
-            date_string,
-            time_string,
-            driver,
-            vehicleid,
-            venue,
-            session,
-            short_comment,
-        };
+        let header = parse_header(&mut self.source)?;
         self.header = Some(header.clone());
         Ok(header)
     }
@@ -119,18 +71,7 @@ impl<'a, S: Read + Seek> LDReader<'a, S> {
         }
 
         self.source.seek(SeekFrom::Start(event_ptr as u64))?;
-
-        let name = self.read_string(64)?;
-        let session = self.read_string(64)?;
-        let comment = self.read_string(1024)?;
-        let venue_addr = self.source.read_u16::<LittleEndian>()?;
-
-        Ok(Some(Event {
-            name,
-            session,
-            comment,
-            venue_addr,
-        }))
+        Ok(Some(parse_event(&mut self.source)?))
     }
 
     pub fn read_venue(&mut self) -> I2Result<Option<Venue>> {
@@ -141,12 +82,7 @@ impl<'a, S: Read + Seek> LDReader<'a, S> {
                 }
 
                 self.source.seek(SeekFrom::Start(event.venue_addr as u64))?;
-
-                let name = self.read_string(64)?;
-                let _unknown = self.read_bytes(1034)?;
-                let vehicle_addr = self.source.read_u16::<LittleEndian>()?;
-
-                Some(Venue { name, vehicle_addr })
+                Some(parse_venue(&mut self.source)?)
             }
             None => None,
         })
@@ -161,19 +97,7 @@ impl<'a, S: Read + Seek> LDReader<'a, S> {
 
                 self.source
                     .seek(SeekFrom::Start(venue.vehicle_addr as u64))?;
-
-                let id = self.read_string(64)?;
-                let _unknown = self.read_bytes(128)?;
-                let weight = self.source.read_u32::<LittleEndian>()?;
-                let _type = self.read_string(32)?;
-                let comment = self.read_string(32)?;
-
-                Some(Vehicle {
-                    id,
-                    weight,
-                    _type,
-                    comment,
-                })
+                Some(parse_vehicle(&mut self.source)?)
             }
             None => None,
         })
@@ -207,100 +131,436 @@ impl<'a, S: Read + Seek> LDReader<'a, S> {
 
     /// Read the [ChannelMetadata] block at file offset `addr`
     fn read_channel_metadata(&mut self, addr: u32) -> I2Result<ChannelMetadata> {
+        let (mut channel, (_type, size)) = self.read_raw_channel_metadata(addr)?;
+        channel.datatype = Datatype::from_type_and_size(_type, size)?;
+        Ok(channel)
+    }
+
+    /// Read the [ChannelMetadata] block at file offset `addr` without interpreting the datatype
+    ///
+    /// The datatype is returned as the raw `(type, size)` pair and left as [Datatype::Invalid]
+    /// in the metadata.
+    pub(crate) fn read_raw_channel_metadata(
+        &mut self,
+        addr: u32,
+    ) -> I2Result<(ChannelMetadata, (u16, u16))> {
         self.source.seek(SeekFrom::Start(addr as u64))?;
+        parse_raw_channel_metadata(&mut self.source)
+    }
+
+    /// Reads all samples of a channel
+    pub fn channel_data(&mut self, channel: &ChannelMetadata) -> I2Result<Vec<Sample>> {
+        self.iter_channel_data(channel)?.collect()
+    }
+
+    /// Returns a iterator over the channel data
+    ///
+    /// Samples are read lazily from the source as the iterator advances, so this can be used
+    /// to process long channels without loading them into memory.
+    pub fn iter_channel_data(
+        &mut self,
+        channel: &ChannelMetadata,
+    ) -> I2Result<ChannelDataIter<'_, S>> {
+        self.source
+            .seek(SeekFrom::Start(channel.data_addr as u64))?;
 
-        let prev_addr = self.source.read_u32::<LittleEndian>()?;
-        let next_addr = self.source.read_u32::<LittleEndian>()?;
-        let data_addr = self.source.read_u32::<LittleEndian>()?;
-        let data_count = self.source.read_u32::<LittleEndian>()?;
-
-        let _unknown = self.source.read_u16::<LittleEndian>()?;
-
-        let datatype_type = self.source.read_u16::<LittleEndian>()?;
-        let datatype_size = self.source.read_u16::<LittleEndian>()?;
-        let datatype = Datatype::from_type_and_size(datatype_type, datatype_size)?;
-
-        let sample_rate = self.source.read_u16::<LittleEndian>()?;
-
-        let offset = self.source.read_u16::<LittleEndian>()?;
-        let mul = self.source.read_u16::<LittleEndian>()?;
-        let scale = self.source.read_u16::<LittleEndian>()?;
-        let dec_places = self.source.read_i16::<LittleEndian>()?;
-
-        let name = self.read_string(32)?;
-        let short_name = self.read_string(8)?;
-        let unit = self.read_string(12)?;
-        let _unknown = self.read_bytes(40)?; // ? (40 bytes for ACC, 32 bytes for acti)
-
-        Ok(ChannelMetadata {
-            prev_addr,
-            next_addr,
-            data_addr,
-            data_count,
-            datatype,
-            sample_rate,
-            offset,
-            mul,
-            scale,
-            dec_places,
-            name,
-            short_name,
-            unit,
+        Ok(ChannelDataIter {
+            source: &mut self.source,
+            channel: channel.clone(),
+            remaining: channel.data_count,
         })
     }
 
-    // TODO: We should probably have a iterator over channel data
+    /// Reads the samples with indices in `range` of a channel
+    ///
+    /// Only the bytes of those samples are read from the source. The range is clamped to the
+    /// samples of the channel.
+    pub fn channel_data_range(
+        &mut self,
+        channel: &ChannelMetadata,
+        range: Range<usize>,
+    ) -> I2Result<Vec<Sample>> {
+        let count = channel.data_count as usize;
+        let start = range.start.min(count);
+        let end = range.end.clamp(start, count);
+
+        let offset = start as u64 * channel.datatype.size() as u64;
+        self.source
+            .seek(SeekFrom::Start(channel.data_addr as u64 + offset))?;
 
-    /// Returns a iterator over the channel data
-    pub fn channel_data(&mut self, channel: &ChannelMetadata) -> I2Result<Vec<Sample>> {
+        ChannelDataIter {
+            source: &mut self.source,
+            channel: channel.clone(),
+            remaining: (end - start) as u32,
+        }
+        .collect()
+    }
+
+    /// Reads all samples of many channels, returned in the order of `channels`
+    ///
+    /// Instead of seeking to every channel, the data section is read in large sequential
+    /// chunks in order of `data_addr`. The channels of each chunk are decoded in parallel when
+    /// the `rayon` feature is enabled.
+    pub fn read_all_channel_data(
+        &mut self,
+        channels: &[ChannelMetadata],
+    ) -> I2Result<Vec<Vec<Sample>>> {
+        let source_len = self.source_len()?;
+        let mut order: Vec<usize> = (0..channels.len())
+            .filter(|&i| channels[i].data_count > 0)
+            .collect();
+        for &i in &order {
+            check_in_source(channels[i].data_addr, channels[i].data_size(), source_len)?;
+        }
+        order.sort_by_key(|&i| channels[i].data_addr);
+
+        let mut data = vec![vec![]; channels.len()];
+        let mut pending = &order[..];
+        while let Some(&first) = pending.first() {
+            // Extend the chunk over the following channels until the gap or size gets too big
+            let start = channels[first].data_addr as u64;
+            let mut end = start + channels[first].data_size();
+            let mut count = 1;
+            for &i in &pending[1..] {
+                let addr = channels[i].data_addr as u64;
+                let channel_end = addr + channels[i].data_size();
+                if addr > end + BULK_MAX_GAP || channel_end.max(end) - start > BULK_READ_SIZE {
+                    break;
+                }
+                end = end.max(channel_end);
+                count += 1;
+            }
+            let (chunk, rest) = pending.split_at(count);
+            pending = rest;
+
+            self.source.seek(SeekFrom::Start(start))?;
+            let mut bytes = vec![0u8; (end - start) as usize];
+            self.source.read_exact(&mut bytes)?;
+
+            let decode = |&i: &usize| {
+                let channel = &channels[i];
+                let offset = (channel.data_addr as u64 - start) as usize;
+                let bytes = &bytes[offset..offset + channel.data_size() as usize];
+                Ok((i, decode_samples(bytes, channel)?))
+            };
+            #[cfg(feature = "rayon")]
+            let decoded: I2Result<Vec<_>> = chunk.par_iter().map(decode).collect();
+            #[cfg(not(feature = "rayon"))]
+            let decoded: I2Result<Vec<_>> = chunk.iter().map(decode).collect();
+
+            for (i, samples) in decoded? {
+                data[i] = samples;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Reads the channel data and decodes every sample into its final value
+    ///
+    /// See [ChannelMetadata::decode_bytes]
+    pub fn channel_values(&mut self, channel: &ChannelMetadata) -> I2Result<Vec<f64>> {
+        let bytes = self.channel_bytes(channel)?;
+        channel.decode_bytes(&bytes)
+    }
+
+    /// Reads the raw little endian data section of a channel
+    ///
+    /// Fails without allocating if the data section doesn't fit in the source.
+    pub fn channel_bytes(&mut self, channel: &ChannelMetadata) -> I2Result<Vec<u8>> {
+        let size = channel.data_size();
+        check_in_source(channel.data_addr, size, self.source_len()?)?;
         self.source
             .seek(SeekFrom::Start(channel.data_addr as u64))?;
+        Ok(read_bytes(&mut self.source, size as usize)?)
+    }
+
+    /// Length of the source in bytes
+    pub(crate) fn source_len(&mut self) -> io::Result<u64> {
+        self.source.seek(SeekFrom::End(0))
+    }
+}
+
+/// Iterator over the samples of a channel, see [LDReader::iter_channel_data]
+#[derive(Debug)]
+pub struct ChannelDataIter<'a, S: Read + Seek> {
+    source: &'a mut S,
+    channel: ChannelMetadata,
+    remaining: u32,
+}
+
+impl<S: Read + Seek> Iterator for ChannelDataIter<'_, S> {
+    type Item = I2Result<Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
 
         // Data for a channel is stored in a contiguous manner at the addr ptr
-        let data = (0..channel.data_count)
-            .map(|_| {
-                Ok({
-                    match channel.datatype {
-                        Datatype::Beacon16 | Datatype::I16 => {
-                            Sample::I16(self.source.read_i16::<LittleEndian>()?)
-                        }
-                        Datatype::Beacon32 | Datatype::I32 => {
-                            Sample::I32(self.source.read_i32::<LittleEndian>()?)
-                        }
-
-                        Datatype::F16 => unimplemented!("Reading f16 samples unimplemented"),
-                        Datatype::F32 => Sample::F32(self.source.read_f32::<LittleEndian>()?),
-                        Datatype::Invalid => panic!(
-                            "Tried to read invalid datatype from channel: {}",
-                            channel.name
-                        ),
-                    }
-                })
-            })
-            .collect::<I2Result<Vec<_>>>()?;
+        let sample = read_sample(self.source, &self.channel.datatype);
+        // Stop after the first error, the source is no longer at a sample boundary
+        self.remaining = if sample.is_ok() {
+            self.remaining - 1
+        } else {
+            0
+        };
+        Some(sample)
+    }
 
-        Ok(data)
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
     }
+}
 
-    fn read_bytes(&mut self, size: usize) -> io::Result<Vec<u8>> {
-        let mut bytes: Vec<u8> = iter::repeat(0u8).take(size).collect();
-        self.source.read_exact(&mut bytes[0..size])?;
-        Ok(bytes)
+// Sizes in bytes of the blocks read by the parsers below
+#[cfg(feature = "async")]
+pub(crate) const HEADER_SIZE: usize = 0x6E2;
+pub(crate) const EVENT_SIZE: usize = 64 + 64 + 1024 + 2;
+pub(crate) const VENUE_SIZE: usize = 64 + 1034 + 2;
+pub(crate) const VEHICLE_SIZE: usize = 64 + 128 + 4 + 32 + 32;
+
+// The parsers below are shared by the sync and async readers. They read a block from the
+// current position of `source`, the async reader reads the block into memory first.
+
+// TODO: Remove asserts and change into a proper error type
+/// Parses the header at the start of a file
+pub(crate) fn parse_header<R: Read>(source: &mut R) -> I2Result<Header> {
+    let ldmarker = source.read_u32::<LittleEndian>()?;
+    if ldmarker != LD_HEADER_MARKER {
+        return Err(I2Error::InvalidHeaderMarker {
+            found: ldmarker,
+            expected: LD_HEADER_MARKER,
+        });
     }
 
-    /// Reads a string with a fixed size trimming null bytes
-    fn read_string(&mut self, size: usize) -> I2Result<String> {
-        let bytes = self.read_bytes(size)?;
-        let str_size = bytes.iter().position(|c| *c == b'\0').unwrap_or(size);
-        let str = ::std::str::from_utf8(&bytes[0..str_size])?;
-        Ok(str.to_string())
+    let _unknown = source.read_u32::<LittleEndian>()?;
+
+    let channel_meta_ptr = source.read_u32::<LittleEndian>()?;
+    let channel_data_ptr = source.read_u32::<LittleEndian>()?;
+
+    let mut _unknown = read_bytes(source, 20)?;
+    // assert_eq!(_unknown, [0u8; 20]);
+
+    // Sample1.ld has this at addr 0x6E2, that is probably the length of the header????
+    let event_ptr = source.read_u32::<LittleEndian>()?;
+
+    let mut _unknown = read_bytes(source, 24)?;
+    // Not 0 in 20160903-0051401.ld
+    // assert_eq!(_unknown, [0u8; 24]);
+
+    // TODO: These may not actually be const...
+    let _unknown_const_1 = source.read_u16::<LittleEndian>()?;
+    // assert_eq!(_unknown_const_1, 0x0000);
+    let _unknown_const_2 = source.read_u16::<LittleEndian>()?;
+    // assert_eq!(_unknown_const_2, 0x4240);
+    let _unknown_const_3 = source.read_u16::<LittleEndian>()?;
+    // assert_eq!(_unknown_const_3, 0x000F);
+
+    let device_serial = source.read_u32::<LittleEndian>()?;
+    let device_type = read_string(source, 8)?;
+    let device_version = source.read_u16::<LittleEndian>()?;
+
+    // TODO: This may not actually be const...
+    let _unknown_const_4 = source.read_u16::<LittleEndian>()?;
+    // assert_eq!(_unknown_const_4, 0x0080);
+
+    let num_channels = source.read_u32::<LittleEndian>()?;
+    let _unknown = source.read_u32::<LittleEndian>()?;
+
+    let date_string = read_string(source, 16)?;
+    let _unknown = read_bytes(source, 16)?;
+    let time_string = read_string(source, 16)?;
+    let _unknown = read_bytes(source, 16)?;
+
+    let driver = read_string(source, 64)?;
+    let vehicleid = read_string(source, 64)?;
+    let _unknown = read_bytes(source, 64)?;
+    let venue = read_string(source, 64)?;
+    let _unknown = read_bytes(source, 64)?;
+
+    let _unknown = read_bytes(source, 1024)?;
+
+    let _pro_logging_bytes = source.read_u32::<LittleEndian>()?;
+
+    let _unknown = read_bytes(source, 2)?;
+    let session = read_string(source, 64)?;
+    let short_comment = read_string(source, 64)?;
+    let _unknown = read_bytes(source, 126)?; // Probably long_comment? + some 2byte
+
+    //let long_comment = read_string(source, ??);
+
+    Ok(Header {
+        channel_meta_ptr,
+        channel_data_ptr,
+        event_ptr,
+        device_serial,
+        device_type,
+        device_version,
+        num_channels,
+        date_string,
+        time_string,
+        driver,
+        vehicleid,
+        venue,
+        session,
+        short_comment,
+    })
+}
+
+pub(crate) fn parse_event<R: Read>(source: &mut R) -> I2Result<Event> {
+    let name = read_string(source, 64)?;
+    let session = read_string(source, 64)?;
+    let comment = read_string(source, 1024)?;
+    let venue_addr = source.read_u16::<LittleEndian>()?;
+
+    Ok(Event {
+        name,
+        session,
+        comment,
+        venue_addr,
+    })
+}
+
+pub(crate) fn parse_venue<R: Read>(source: &mut R) -> I2Result<Venue> {
+    let name = read_string(source, 64)?;
+    let _unknown = read_bytes(source, 1034)?;
+    let vehicle_addr = source.read_u16::<LittleEndian>()?;
+
+    Ok(Venue { name, vehicle_addr })
+}
+
+pub(crate) fn parse_vehicle<R: Read>(source: &mut R) -> I2Result<Vehicle> {
+    let id = read_string(source, 64)?;
+    let _unknown = read_bytes(source, 128)?;
+    let weight = source.read_u32::<LittleEndian>()?;
+    let _type = read_string(source, 32)?;
+    let comment = read_string(source, 32)?;
+
+    Ok(Vehicle {
+        id,
+        weight,
+        _type,
+        comment,
+    })
+}
+
+/// Parses a [ChannelMetadata] block, see [LDReader::read_raw_channel_metadata]
+pub(crate) fn parse_raw_channel_metadata<R: Read>(
+    source: &mut R,
+) -> I2Result<(ChannelMetadata, (u16, u16))> {
+    let prev_addr = source.read_u32::<LittleEndian>()?;
+    let next_addr = source.read_u32::<LittleEndian>()?;
+    let data_addr = source.read_u32::<LittleEndian>()?;
+    let data_count = source.read_u32::<LittleEndian>()?;
+
+    let _unknown = source.read_u16::<LittleEndian>()?;
+
+    let datatype_type = source.read_u16::<LittleEndian>()?;
+    let datatype_size = source.read_u16::<LittleEndian>()?;
+
+    let sample_rate = source.read_u16::<LittleEndian>()?;
+
+    let offset = source.read_u16::<LittleEndian>()?;
+    let mul = source.read_u16::<LittleEndian>()?;
+    let scale = source.read_u16::<LittleEndian>()?;
+    let dec_places = source.read_i16::<LittleEndian>()?;
+
+    let name = read_string(source, 32)?;
+    let short_name = read_string(source, 8)?;
+    let unit = read_string(source, 12)?;
+    let _unknown = read_bytes(source, 40)?; // ? (40 bytes for ACC, 32 bytes for acti)
+
+    let channel = ChannelMetadata {
+        prev_addr,
+        next_addr,
+        data_addr,
+        data_count,
+        datatype: Datatype::Invalid,
+        sample_rate,
+        offset,
+        mul,
+        scale,
+        dec_places,
+        name,
+        short_name,
+        unit,
+    };
+    Ok((channel, (datatype_type, datatype_size)))
+}
+
+/// Reads a single sample stored as `datatype`
+///
+/// `F16` samples can't be read yet and `Invalid` has no known layout, both return
+/// [I2Error::UnsupportedDatatype].
+pub(crate) fn read_sample<R: Read>(source: &mut R, datatype: &Datatype) -> I2Result<Sample> {
+    Ok(match datatype {
+        Datatype::Beacon16 | Datatype::I16 => Sample::I16(source.read_i16::<LittleEndian>()?),
+        Datatype::Beacon32 | Datatype::I32 => Sample::I32(source.read_i32::<LittleEndian>()?),
+        Datatype::F32 => Sample::F32(source.read_f32::<LittleEndian>()?),
+        Datatype::F16 | Datatype::Invalid => {
+            return Err(I2Error::UnsupportedDatatype(datatype.clone()))
+        }
+    })
+}
+
+/// Decodes all samples of `channel` from `bytes`, which hold its whole data section
+///
+/// Same layout as [read_sample], but decodes the whole slice at once.
+pub(crate) fn decode_samples(bytes: &[u8], channel: &ChannelMetadata) -> I2Result<Vec<Sample>> {
+    let count = channel.data_count as usize;
+    let size = channel.datatype.size() as usize;
+    let bytes = bytes
+        .get(..count * size)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+    let samples = bytes.chunks_exact(size.max(1));
+    Ok(match channel.datatype {
+        Datatype::Beacon16 | Datatype::I16 => samples
+            .map(|b| Sample::I16(i16::from_le_bytes([b[0], b[1]])))
+            .collect(),
+        Datatype::Beacon32 | Datatype::I32 => samples
+            .map(|b| Sample::I32(i32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+            .collect(),
+        Datatype::F32 => samples
+            .map(|b| Sample::F32(f32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+            .collect(),
+        Datatype::F16 | Datatype::Invalid if count == 0 => vec![],
+        Datatype::F16 | Datatype::Invalid => {
+            return Err(I2Error::UnsupportedDatatype(channel.datatype.clone()))
+        }
+    })
+}
+
+/// Checks that the `size` bytes at `addr` are inside a source of `source_len` bytes, so that
+/// corrupt sizes fail before their buffer is allocated
+pub(crate) fn check_in_source(addr: u32, size: u64, source_len: u64) -> io::Result<()> {
+    if addr as u64 + size > source_len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} bytes at {:#x} are past the end of the file", size, addr),
+        ));
     }
+    Ok(())
+}
+
+fn read_bytes<R: Read>(source: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; size];
+    source.read_exact(&mut bytes[0..size])?;
+    Ok(bytes)
+}
+
+/// Reads a string with a fixed size trimming null bytes
+fn read_string<R: Read>(source: &mut R, size: usize) -> I2Result<String> {
+    let bytes = read_bytes(source, size)?;
+    let str_size = bytes.iter().position(|c| *c == b'\0').unwrap_or(size);
+    let str = ::std::str::from_utf8(&bytes[0..str_size])?;
+    Ok(str.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::reader::LDReader;
-    use crate::{ChannelMetadata, Datatype, Event, Header, Sample, Vehicle, Venue};
+    use crate::{ChannelMetadata, Datatype, Event, Header, I2Error, Sample, Vehicle, Venue};
     use std::fs;
     use std::io::Cursor;
 
@@ -436,6 +696,91 @@ mod tests {
         assert_delta!(data[4].decode_f64(channel), 19.9, 0.000001);
     }
 
+    #[test]
+    fn read_sample1_all_channel_data() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader = LDReader::new(Cursor::new(bytes));
+        let channels = reader.read_channels().unwrap();
+
+        // Reversed, with a duplicate and a channel without samples
+        let mut requested: Vec<_> = channels.iter().rev().cloned().collect();
+        requested.push(channels[3].clone());
+        let mut empty = channels[5].clone();
+        empty.data_count = 0;
+        requested.push(empty);
+
+        let data = reader.read_all_channel_data(&requested).unwrap();
+        assert_eq!(data.len(), 80);
+        for (channel, samples) in requested.iter().zip(&data) {
+            assert_eq!(samples, &reader.channel_data(channel).unwrap());
+        }
+        assert!(data[79].is_empty());
+    }
+
+    #[test]
+    fn corrupt_data_count() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader = LDReader::new(Cursor::new(bytes));
+        let mut channel = reader.read_channels().unwrap().remove(0);
+        channel.data_count = u32::MAX;
+
+        // The size doesn't fit in the file, so nothing is allocated for it
+        assert!(matches!(
+            reader.channel_bytes(&channel),
+            Err(I2Error::IOError(_))
+        ));
+        assert!(matches!(
+            reader.read_all_channel_data(&[channel]),
+            Err(I2Error::IOError(_))
+        ));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn open_sample1_owned() {
+        let readers: Vec<_> = (0..2)
+            .map(|_| LDReader::open("./samples/Sample1.ld").unwrap())
+            .collect();
+
+        // Readers own their file, so they can be moved to other threads
+        let handles: Vec<_> = readers
+            .into_iter()
+            .map(|mut reader| std::thread::spawn(move || reader.read_channels().unwrap().len()))
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 78);
+        }
+
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader = LDReader::new(Cursor::new(bytes));
+        assert_eq!(reader.read_header().unwrap().num_channels, 78);
+        assert_eq!(reader.into_inner().position(), 1762);
+    }
+
+    #[test]
+    fn read_sample1_channel_data_range() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let channel = &channels[0];
+        let data = reader.channel_data(channel).unwrap();
+
+        let range = reader.channel_data_range(channel, 2..5).unwrap();
+        assert_eq!(range, data[2..5]);
+
+        let count = channel.data_count as usize;
+        let tail = reader
+            .channel_data_range(channel, count - 2..count + 10)
+            .unwrap();
+        assert_eq!(tail, data[count - 2..]);
+        assert!(reader
+            .channel_data_range(channel, count + 1..count + 5)
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn read_sample1_event() {
         let bytes = fs::read("./samples/Sample1.ld").unwrap();
@@ -0,0 +1,653 @@
+use crate::ibt::date_time_from_unix;
+use crate::{
+    ChannelMetadata, Datatype, Header, I2Error, I2Result, Sample, Session, TelemetryPacket,
+    TelemetryProtocol, TelemetryStream,
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often the receiving thread checks if [Recorder::stop] was called
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Largest packet of the supported protocols is 1352 bytes (F1 car telemetry)
+const MAX_PACKET_SIZE: usize = 2048;
+
+/// First bytes of a capture file written by [Recorder]
+pub const CAPTURE_MAGIC: &[u8; 8] = b"I2UDPCAP";
+
+/// Time stamps that jump back by more than this many seconds are treated as a restart of the
+/// session or game, smaller jumps are packets arriving out of order
+const RESET_THRESHOLD: f64 = 1.0;
+
+/// The packets of one [TelemetryStream] in the order they arrived
+#[derive(Debug, Clone)]
+struct StreamRecording {
+    stream: &'static TelemetryStream,
+    times: Vec<f64>,
+    values: Vec<Vec<f64>>,
+}
+
+/// Decoded telemetry packets, that can be turned into a [Session] once recording is done
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub protocol: TelemetryProtocol,
+    /// Header of the written file, the date and time are set to the start of the recording
+    pub header: Header,
+    streams: Vec<StreamRecording>,
+    /// Arrival time of the first packet, all streams start at this time
+    first_arrival: Option<f64>,
+    /// Protocol time and recording time that protocol time stamps are counted from, moved when
+    /// the time stamps are reset
+    base: (f64, f64),
+}
+
+impl Recording {
+    pub fn new(protocol: TelemetryProtocol) -> Self {
+        Self::starting_at(protocol, unix_now())
+    }
+
+    /// Decodes every packet of a capture file written by [Recorder]
+    ///
+    /// A capture that was cut off, such as when the recorder process died, is read up to the
+    /// last complete packet.
+    pub fn read_capture<R: Read>(protocol: TelemetryProtocol, source: &mut R) -> I2Result<Self> {
+        let mut magic = [0u8; 8];
+        source.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(I2Error::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a telemetry capture",
+            )));
+        }
+        let mut recording = Self::starting_at(protocol, source.read_i64::<LittleEndian>()?);
+
+        let mut packet = vec![];
+        loop {
+            let record = (|| -> io::Result<f64> {
+                let arrival = source.read_f64::<LittleEndian>()?;
+                packet.resize(source.read_u16::<LittleEndian>()? as usize, 0);
+                source.read_exact(&mut packet)?;
+                Ok(arrival)
+            })();
+            match record {
+                Ok(arrival) => recording.push(&packet, arrival),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+        }
+        Ok(recording)
+    }
+
+    fn starting_at(protocol: TelemetryProtocol, unix_time: i64) -> Self {
+        let (date_string, time_string) = date_time_from_unix(unix_time);
+
+        Self {
+            protocol,
+            header: Header {
+                channel_meta_ptr: 0,
+                channel_data_ptr: 0,
+                event_ptr: 0,
+                device_serial: 0,
+                device_type: "ADL".to_string(),
+                device_version: 420,
+                num_channels: 0,
+                date_string,
+                time_string,
+                driver: String::new(),
+                vehicleid: String::new(),
+                venue: String::new(),
+                session: String::new(),
+                short_comment: String::new(),
+            },
+            streams: vec![],
+            first_arrival: None,
+            base: (0.0, 0.0),
+        }
+    }
+
+    /// Decodes `packet` and records its values
+    ///
+    /// `arrival` is the time in seconds since the start of the recording, it is used for
+    /// protocols that don't time stamp their packets. When the time stamps jump back, such as
+    /// when the session is restarted, recording continues from the arrival time or the end of
+    /// the recording, whichever is later. Returns false if the packet was ignored.
+    pub fn push(&mut self, packet: &[u8], arrival: f64) -> bool {
+        let packets = self.protocol.decode(packet);
+        let decoded = !packets.is_empty();
+        for packet in packets {
+            self.push_decoded(packet, arrival);
+        }
+        decoded
+    }
+
+    fn push_decoded(&mut self, packet: TelemetryPacket, arrival: f64) {
+        let time = packet.time.unwrap_or(arrival);
+        let first_arrival = *self.first_arrival.get_or_insert_with(|| {
+            self.base = (time, 0.0);
+            arrival
+        });
+
+        let index = match self
+            .streams
+            .iter()
+            .position(|recording| std::ptr::eq(recording.stream, packet.stream))
+        {
+            Some(index) => index,
+            None => {
+                self.streams.push(StreamRecording {
+                    stream: packet.stream,
+                    times: vec![],
+                    values: vec![vec![]; packet.stream.channels.len()],
+                });
+                self.streams.len() - 1
+            }
+        };
+        let mut recorded = time - self.base.0 + self.base.1;
+        let last = self.streams[index].times.last().copied();
+        if last.is_some_and(|last| recorded < last - RESET_THRESHOLD) {
+            let end = self
+                .streams
+                .iter()
+                .filter_map(|recording| recording.times.last())
+                .fold(0.0, |end: f64, last| end.max(*last));
+            // Leave a gap of a millisecond so the packet isn't dropped as a duplicate
+            self.base = (time, (arrival - first_arrival).max(end + 0.001));
+            recorded = self.base.1;
+        }
+
+        // Packets that arrive out of order or twice are dropped
+        let recording = &mut self.streams[index];
+        let time = recorded;
+        if recording.times.last().is_some_and(|last| time <= *last) {
+            return;
+        }
+        recording.times.push(time);
+        for (values, value) in recording.values.iter_mut().zip(packet.values) {
+            values.push(value);
+        }
+    }
+
+    /// Number of packets recorded for every stream
+    pub fn packet_counts(&self) -> Vec<(&'static str, usize)> {
+        self.streams
+            .iter()
+            .map(|recording| (recording.stream.name, recording.times.len()))
+            .collect()
+    }
+
+    /// Turns the recording into a session with one channel per decoded value
+    ///
+    /// Every stream is written at its native rate, estimated from the median time between
+    /// packets and rounded to whole Hz. Each sample holds the value of the last packet within
+    /// half a sample of its time. When several streams have a channel with the same name, the
+    /// first stream to arrive keeps it.
+    pub fn to_session(&self) -> Session {
+        let mut channels: Vec<(ChannelMetadata, Vec<_>)> = vec![];
+        for recording in &self.streams {
+            let rate = native_rate(&recording.times);
+            let end = recording.times.last().copied().unwrap_or_default();
+            let count = (end * rate as f64).round() as usize + 1;
+
+            let mut indices = Vec::with_capacity(count);
+            let mut packet = 0;
+            for i in 0..count {
+                let time = (i as f64 + 0.5) / rate as f64;
+                while packet + 1 < recording.times.len() && recording.times[packet + 1] < time {
+                    packet += 1;
+                }
+                indices.push(packet);
+            }
+
+            for (channel, values) in recording.stream.channels.iter().zip(&recording.values) {
+                if channels.iter().any(|(c, _)| c.name == channel.name) {
+                    continue;
+                }
+                let metadata = ChannelMetadata {
+                    prev_addr: 0,
+                    next_addr: 0,
+                    data_addr: 0,
+                    data_count: count as u32,
+                    datatype: Datatype::F32,
+                    sample_rate: rate,
+                    offset: 0,
+                    mul: 1,
+                    scale: 1,
+                    dec_places: 0,
+                    name: channel.name.to_string(),
+                    short_name: channel.short_name.to_string(),
+                    unit: channel.unit.to_string(),
+                };
+                let samples = indices
+                    .iter()
+                    .map(|i| Sample::F32(values[*i] as f32))
+                    .collect();
+                channels.push((metadata, samples));
+            }
+        }
+
+        let mut header = self.header.clone();
+        header.num_channels = channels.len() as u32;
+        Session {
+            header,
+            event: None,
+            venue: None,
+            vehicle: None,
+            channels,
+        }
+    }
+
+    /// Writes the recording as an .ld file, see [Recording::to_session]
+    pub fn write<S: Write + Seek>(&self, sink: &mut S) -> I2Result<()> {
+        self.to_session().write(sink)
+    }
+}
+
+fn native_rate(times: &[f64]) -> u16 {
+    // Spans of several packets even out time stamps that are rounded to whole milliseconds
+    let span = (times.len().max(1) - 1).min(10);
+    if span == 0 {
+        return 1;
+    }
+    let mut intervals: Vec<f64> = times
+        .windows(span + 1)
+        .map(|window| (window[span] - window[0]) / span as f64)
+        .collect();
+    intervals.sort_by(f64::total_cmp);
+    let median = intervals[intervals.len() / 2];
+    (1.0 / median).round().clamp(1.0, u16::MAX as f64) as u16
+}
+
+/// Listens for sim telemetry on a UDP socket in a background thread and records it to an .ld file
+///
+/// Packets are appended to a capture file next to the .ld file as they arrive, so memory use
+/// doesn't grow with the length of the session. [Recorder::stop] decodes the capture into the
+/// .ld file and deletes it. If the process dies before that, [Recording::read_capture] rebuilds
+/// the recording from the capture file.
+///
+/// ```no_run
+/// use motec_i2::{Recorder, TelemetryProtocol};
+///
+/// let recorder = Recorder::start("0.0.0.0:20777", TelemetryProtocol::F1, "session.ld").unwrap();
+/// // ... drive ...
+/// let recording = recorder.stop().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Recorder {
+    local_addr: SocketAddr,
+    protocol: TelemetryProtocol,
+    path: PathBuf,
+    capture_path: PathBuf,
+    running: Arc<AtomicBool>,
+    capture: Arc<Mutex<BufWriter<File>>>,
+    packet_counts: Arc<Mutex<Vec<(&'static str, usize)>>>,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl Recorder {
+    /// Binds the socket and starts recording to the .ld file at `path`
+    ///
+    /// The capture file is `path` with `.capture` appended.
+    pub fn start<A: ToSocketAddrs, P: AsRef<Path>>(
+        addr: A,
+        protocol: TelemetryProtocol,
+        path: P,
+    ) -> I2Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut capture_path = path.clone().into_os_string();
+        capture_path.push(".capture");
+        let capture_path = PathBuf::from(capture_path);
+
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let local_addr = socket.local_addr()?;
+
+        let mut capture = BufWriter::new(File::create(&capture_path)?);
+        capture.write_all(CAPTURE_MAGIC)?;
+        capture.write_i64::<LittleEndian>(unix_now())?;
+        capture.flush()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let capture = Arc::new(Mutex::new(capture));
+        let packet_counts = Arc::new(Mutex::new(vec![]));
+        let thread = {
+            let running = running.clone();
+            let capture = capture.clone();
+            let packet_counts = packet_counts.clone();
+            thread::spawn(move || receive(socket, protocol, &running, &capture, &packet_counts))
+        };
+
+        Ok(Self {
+            local_addr,
+            protocol,
+            path,
+            capture_path,
+            running,
+            capture,
+            packet_counts,
+            thread,
+        })
+    }
+
+    /// Address the socket is bound to, useful when binding to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Path of the capture file that packets are appended to
+    pub fn capture_path(&self) -> &Path {
+        &self.capture_path
+    }
+
+    /// Number of packets received so far for every stream
+    pub fn packet_counts(&self) -> Vec<(&'static str, usize)> {
+        self.packet_counts.lock().unwrap().clone()
+    }
+
+    /// Stops listening, writes the .ld file and deletes the capture file
+    pub fn stop(self) -> I2Result<Recording> {
+        self.running.store(false, Ordering::Relaxed);
+        self.thread
+            .join()
+            .map_err(|_| I2Error::IOError(io::Error::other("recorder thread panicked")))??;
+        self.capture.lock().unwrap().flush()?;
+
+        let mut capture = BufReader::new(File::open(&self.capture_path)?);
+        let recording = Recording::read_capture(self.protocol, &mut capture)?;
+        recording.write(&mut BufWriter::new(File::create(&self.path)?))?;
+        fs::remove_file(&self.capture_path)?;
+        Ok(recording)
+    }
+}
+
+fn receive(
+    socket: UdpSocket,
+    protocol: TelemetryProtocol,
+    running: &AtomicBool,
+    capture: &Mutex<BufWriter<File>>,
+    packet_counts: &Mutex<Vec<(&'static str, usize)>>,
+) -> io::Result<()> {
+    let start = Instant::now();
+    let mut last_flush = start;
+    let mut buffer = [0u8; MAX_PACKET_SIZE];
+    while running.load(Ordering::Relaxed) {
+        match socket.recv(&mut buffer) {
+            Ok(len) => {
+                let packet = &buffer[..len];
+                let arrival = start.elapsed().as_secs_f64();
+                // Packets that can't be decoded are left out of the capture
+                let decoded = protocol.decode(packet);
+                if !decoded.is_empty() {
+                    write_capture_record(&mut *capture.lock().unwrap(), arrival, packet)?;
+                }
+
+                let mut counts = packet_counts.lock().unwrap();
+                for packet in decoded {
+                    match counts
+                        .iter_mut()
+                        .find(|(name, _)| *name == packet.stream.name)
+                    {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((packet.stream.name, 1)),
+                    }
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e),
+        }
+
+        if last_flush.elapsed() >= POLL_INTERVAL {
+            capture.lock().unwrap().flush()?;
+            last_flush = Instant::now();
+        }
+    }
+    Ok(())
+}
+
+/// Appends a packet to a capture: arrival time, length and the packet as received
+fn write_capture_record<W: Write>(sink: &mut W, arrival: f64, packet: &[u8]) -> io::Result<()> {
+    sink.write_f64::<LittleEndian>(arrival)?;
+    sink.write_u16::<LittleEndian>(packet.len() as u16)?;
+    sink.write_all(packet)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Recording;
+    use crate::{LDReader, Recorder, TelemetryProtocol};
+    use std::net::UdpSocket;
+    use std::time::{Duration, Instant};
+    use std::{env, fs, process, thread};
+
+    fn outgauge_packet(time_ms: u32, speed: f32, rpm: f32, gear: u8) -> Vec<u8> {
+        let mut packet = vec![0u8; 96];
+        packet[0..4].copy_from_slice(&time_ms.to_le_bytes());
+        packet[4..8].copy_from_slice(b"XRT\0");
+        packet[10] = gear;
+        packet[12..16].copy_from_slice(&speed.to_le_bytes());
+        packet[16..20].copy_from_slice(&rpm.to_le_bytes());
+        packet[48..52].copy_from_slice(&0.25f32.to_le_bytes());
+        packet
+    }
+
+    /// Lap data of a single car in the field order of the F1 23 and F1 24 specifications
+    fn f1_lap_data(format: u16, distance: f32, position: u8, lap: u8) -> Vec<u8> {
+        let mut car = vec![];
+        car.extend_from_slice(&83_000u32.to_le_bytes()); // m_lastLapTimeInMS
+        car.extend_from_slice(&12_500u32.to_le_bytes()); // m_currentLapTimeInMS
+        car.extend_from_slice(&[0; 3]); // m_sector1TimeInMS, m_sector1TimeMinutes
+        car.extend_from_slice(&[0; 3]); // m_sector2TimeInMS, m_sector2TimeMinutes
+        car.extend_from_slice(&[0; 2]); // m_deltaToCarInFrontInMS
+        if format >= 2024 {
+            car.push(0); // m_deltaToCarInFrontMinutes
+        }
+        car.extend_from_slice(&[0; 2]); // m_deltaToRaceLeaderInMS
+        if format >= 2024 {
+            car.push(0); // m_deltaToRaceLeaderMinutes
+        }
+        car.extend_from_slice(&distance.to_le_bytes()); // m_lapDistance
+        car.extend_from_slice(&(5000.0 + distance).to_le_bytes()); // m_totalDistance
+        car.extend_from_slice(&0.0f32.to_le_bytes()); // m_safetyCarDelta
+        car.push(position); // m_carPosition
+        car.push(lap); // m_currentLapNum
+        car.push(1); // m_pitStatus
+        car.resize(if format >= 2024 { 57 } else { 50 }, 0);
+        car
+    }
+
+    #[test]
+    fn record_outgauge_over_udp() {
+        let path = env::temp_dir().join(format!("motec_i2_recorder_{}.ld", process::id()));
+        let recorder = Recorder::start("127.0.0.1:0", TelemetryProtocol::OutGauge, &path).unwrap();
+        let capture_path = recorder.capture_path().to_path_buf();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        // 2 seconds at 50 Hz
+        for i in 0..100u32 {
+            let packet = outgauge_packet(i * 20, i as f32, 1000.0 + i as f32, 3);
+            sender.send_to(&packet, recorder.local_addr()).unwrap();
+            if i % 10 == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        sender
+            .send_to(b"not telemetry", recorder.local_addr())
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while recorder.packet_counts() != [("OutGauge", 100)] && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Packets are on disk before the recording is stopped, a cut off packet is dropped
+        let recovered = || {
+            let capture = fs::read(&capture_path).unwrap();
+            let mut source = &capture[..capture.len() - 5];
+            Recording::read_capture(TelemetryProtocol::OutGauge, &mut source).unwrap()
+        };
+        while recovered().packet_counts() != [("OutGauge", 99)] && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(recovered().packet_counts(), [("OutGauge", 99)]);
+
+        let recording = recorder.stop().unwrap();
+        assert_eq!(recording.packet_counts(), [("OutGauge", 100)]);
+        assert!(!capture_path.exists());
+
+        let mut file = fs::File::open(&path).unwrap();
+        let mut reader = LDReader::new(&mut file);
+        let session = reader.read_session().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(session.channels.len(), 11);
+
+        let (speed, samples) = session.channel("Ground Speed").unwrap();
+        assert_eq!(speed.sample_rate, 50);
+        assert_eq!(speed.unit, "km/h");
+        assert_eq!(samples.len(), 100);
+        assert!((samples[10].decode_f64(speed) - 36.0).abs() < 1e-4);
+
+        let (gear, samples) = session.channel("Gear").unwrap();
+        assert_eq!(samples[0].decode_f64(gear), 2.0);
+        let (throttle, samples) = session.channel("Throttle Pos").unwrap();
+        assert_eq!(samples[0].decode_f64(throttle), 25.0);
+    }
+
+    #[test]
+    fn record_across_time_reset() {
+        let mut recording = Recording::new(TelemetryProtocol::OutGauge);
+        // 2 seconds at 50 Hz, a duplicate, then the game restarts and sends 1 more second
+        for i in 0..100u32 {
+            assert!(recording.push(&outgauge_packet(60_000 + i * 20, 1.0, 1000.0, 3), 0.0));
+        }
+        recording.push(&outgauge_packet(60_000 + 98 * 20, 1.0, 1000.0, 3), 0.0);
+        for i in 0..50u32 {
+            recording.push(&outgauge_packet(i * 20, 2.0, 2000.0, 3), 0.0);
+        }
+        assert_eq!(recording.packet_counts(), [("OutGauge", 150)]);
+
+        let session = recording.to_session();
+        let (rpm, samples) = session.channel("Engine RPM").unwrap();
+        assert_eq!(rpm.sample_rate, 50);
+        // The second part starts right after the first one
+        assert_eq!(samples.len(), 149);
+        assert_eq!(samples[98].decode_f64(rpm), 1000.0);
+        assert_eq!(samples[100].decode_f64(rpm), 2000.0);
+    }
+
+    #[test]
+    fn record_forza_and_f1_streams() {
+        // Forza Horizon dash packet at 60 Hz
+        let mut recording = Recording::new(TelemetryProtocol::Forza);
+        for i in 0..120u32 {
+            let mut packet = vec![0u8; 324];
+            packet[0..4].copy_from_slice(&1i32.to_le_bytes());
+            packet[4..8].copy_from_slice(&(1000 + i * 1000 / 60).to_le_bytes());
+            packet[16..20].copy_from_slice(&6500.0f32.to_le_bytes());
+            packet[20..24].copy_from_slice(&9.80665f32.to_le_bytes());
+            packet[256..260].copy_from_slice(&50.0f32.to_le_bytes());
+            packet[268..272].copy_from_slice(&212.0f32.to_le_bytes());
+            packet[319] = 4;
+            assert!(recording.push(&packet, 0.0));
+        }
+        // Not racing
+        assert!(!recording.push(&[0u8; 324], 0.0));
+
+        let session = recording.to_session();
+        let (rpm, samples) = session.channel("Engine RPM").unwrap();
+        assert_eq!(rpm.sample_rate, 60);
+        assert_eq!(samples.len(), 120);
+        assert_eq!(samples[0].decode_f64(rpm), 6500.0);
+        let (g, samples) = session.channel("G Force Lat").unwrap();
+        assert!((samples[0].decode_f64(g) - 1.0).abs() < 1e-6);
+        let (speed, samples) = session.channel("Ground Speed").unwrap();
+        assert!((samples[0].decode_f64(speed) - 180.0).abs() < 1e-4);
+        let (temp, samples) = session.channel("Tyre Temp FL").unwrap();
+        assert!((samples[0].decode_f64(temp) - 100.0).abs() < 1e-4);
+        let (gear, samples) = session.channel("Gear").unwrap();
+        assert_eq!(samples[0].decode_f64(gear), 4.0);
+
+        // F1 23 car telemetry at 20 Hz and lap data at 10 Hz, player in car 1
+        let mut recording = Recording::new(TelemetryProtocol::F1);
+        let header = |packet: &mut Vec<u8>, format: u16, id: u8, time: f32| {
+            packet[0..2].copy_from_slice(&format.to_le_bytes());
+            packet[6] = id;
+            packet[15..19].copy_from_slice(&time.to_le_bytes());
+            packet[27] = 1;
+        };
+        for i in 0..40 {
+            let mut packet = vec![0u8; 29 + 22 * 60 + 3];
+            header(&mut packet, 2023, 6, i as f32 / 20.0);
+            let car = 29 + 60;
+            packet[car..car + 2].copy_from_slice(&(200 + i as u16).to_le_bytes());
+            packet[car + 16..car + 18].copy_from_slice(&11000u16.to_le_bytes());
+            packet[car + 15] = 6;
+            assert!(recording.push(&packet, 0.0));
+
+            if i % 2 == 0 {
+                let mut packet = vec![0u8; 29 + 22 * 50 + 2];
+                header(&mut packet, 2023, 2, i as f32 / 20.0);
+                let car = 29 + 50;
+                packet[car..car + 50].copy_from_slice(&f1_lap_data(2023, i as f32 * 5.0, 7, 3));
+                assert!(recording.push(&packet, 0.0));
+            }
+        }
+        assert_eq!(
+            recording.packet_counts(),
+            [("F1 Car Telemetry", 40), ("F1 Lap Data", 20)]
+        );
+
+        let session = recording.to_session();
+        let (speed, samples) = session.channel("Ground Speed").unwrap();
+        assert_eq!(speed.sample_rate, 20);
+        assert_eq!(samples[10].decode_f64(speed), 210.0);
+        let (lap, samples) = session.channel("Lap Number").unwrap();
+        assert_eq!(lap.sample_rate, 10);
+        assert_eq!(samples.len(), 20);
+        assert_eq!(samples[5].decode_f64(lap), 3.0);
+        let (position, samples) = session.channel("Race Position").unwrap();
+        assert_eq!(samples[5].decode_f64(position), 7.0);
+        let (distance, samples) = session.channel("Lap Distance").unwrap();
+        assert_eq!(samples[5].decode_f64(distance), 50.0);
+        let (distance, samples) = session.channel("Distance").unwrap();
+        assert_eq!(samples[5].decode_f64(distance), 5050.0);
+        let (last, samples) = session.channel("Last Lap Time").unwrap();
+        assert_eq!(samples[5].decode_f64(last), 83.0);
+
+        // F1 24 lap data has two more bytes before the distances
+        let mut recording = Recording::new(TelemetryProtocol::F1);
+        for i in 0..2 {
+            let mut packet = vec![0u8; 29 + 22 * 57 + 2];
+            header(&mut packet, 2024, 2, i as f32 / 10.0);
+            let car = 29 + 57;
+            packet[car..car + 57].copy_from_slice(&f1_lap_data(2024, 120.0, 2, 4));
+            assert!(recording.push(&packet, 0.0));
+        }
+        let session = recording.to_session();
+        let (distance, samples) = session.channel("Lap Distance").unwrap();
+        assert_eq!(samples[0].decode_f64(distance), 120.0);
+        let (position, samples) = session.channel("Race Position").unwrap();
+        assert_eq!(samples[0].decode_f64(position), 2.0);
+        let (lap, samples) = session.channel("Lap Number").unwrap();
+        assert_eq!(samples[0].decode_f64(lap), 4.0);
+    }
+}
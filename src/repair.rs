@@ -0,0 +1,375 @@
+use crate::full_header::CHANNEL_META_ADDR;
+use crate::{ChannelMetadata, Datatype, I2Result, LDReader, Session};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek, Write};
+
+/// Something [LDReader::recover] had to change to salvage a file
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecoveryAction {
+    /// A channel that isn't reachable through the links of the channel list
+    ScannedChannel {
+        channel: String,
+        addr: u32,
+    },
+    /// The data of a channel is cut off by the end of the file
+    ClampedDataCount {
+        channel: String,
+        found: u32,
+        recovered: u32,
+    },
+    DroppedChannel {
+        channel: String,
+        reason: String,
+    },
+    /// The event, venue or vehicle block couldn't be read
+    DroppedBlock {
+        block: String,
+    },
+}
+
+impl fmt::Display for RecoveryAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoveryAction::ScannedChannel { channel, addr } => write!(
+                f,
+                "Found channel \"{}\" at {:#x} outside of the channel list",
+                channel, addr
+            ),
+            RecoveryAction::ClampedDataCount {
+                channel,
+                found,
+                recovered,
+            } => write!(
+                f,
+                "Recovered {} of {} samples of channel \"{}\"",
+                recovered, found, channel
+            ),
+            RecoveryAction::DroppedChannel { channel, reason } => {
+                write!(f, "Dropped channel \"{}\": {}", channel, reason)
+            }
+            RecoveryAction::DroppedBlock { block } => write!(f, "Dropped unreadable {}", block),
+        }
+    }
+}
+
+/// What [LDReader::recover] salvaged from a file
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RepairReport {
+    /// Names of the recovered channels, in the order they are written
+    pub channels: Vec<String>,
+    pub actions: Vec<RecoveryAction>,
+}
+
+impl RepairReport {
+    /// Returns true if the file was read without changing anything
+    pub fn is_clean(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Recovered {} channels", self.channels.len())?;
+        for action in &self.actions {
+            writeln!(f, "{}", action)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Salvages as much of a damaged or truncated file as possible
+    ///
+    /// The channel list is followed forward and backward through `next_addr` and `prev_addr`.
+    /// If that doesn't find every channel listed in the header, the metadata area is scanned
+    /// for blocks that look like channel metadata. Channels are returned in list order, followed
+    /// by the scanned channels, with `data_count` clamped to the samples present in the file.
+    /// Only a missing or unreadable header is returned as [Err].
+    pub fn recover(&mut self) -> I2Result<(Session, RepairReport)> {
+        let file_len = self.source_len()?;
+        let header = self.read_header()?;
+        let mut report = RepairReport::default();
+
+        let event = self.read_event().unwrap_or_else(|_| {
+            report.actions.push(RecoveryAction::DroppedBlock {
+                block: "event".to_string(),
+            });
+            None
+        });
+        let venue = match event {
+            Some(_) => self.read_venue().unwrap_or_else(|_| {
+                report.actions.push(RecoveryAction::DroppedBlock {
+                    block: "venue".to_string(),
+                });
+                None
+            }),
+            None => None,
+        };
+        let vehicle = match venue {
+            Some(_) => self.read_vehicle().unwrap_or_else(|_| {
+                report.actions.push(RecoveryAction::DroppedBlock {
+                    block: "vehicle".to_string(),
+                });
+                None
+            }),
+            None => None,
+        };
+
+        let mut blocks = BTreeMap::new();
+        let broken = self.follow_links(header.channel_meta_ptr, file_len, &mut blocks);
+        if broken || blocks.len() as u32 != header.num_channels {
+            let linked: HashSet<u32> = blocks.keys().copied().collect();
+            let start = match header.channel_meta_ptr {
+                0 => CHANNEL_META_ADDR,
+                addr if addr as u64 >= file_len => CHANNEL_META_ADDR,
+                addr => addr,
+            };
+            let end = match header.channel_data_ptr as u64 {
+                addr if addr > start as u64 && addr <= file_len => addr,
+                _ => file_len,
+            };
+
+            let mut addr = start as u64;
+            while addr + ChannelMetadata::ENTRY_SIZE as u64 <= end {
+                if !blocks.contains_key(&(addr as u32)) {
+                    self.follow_links(addr as u32, file_len, &mut blocks);
+                }
+                addr += ChannelMetadata::ENTRY_SIZE as u64;
+            }
+
+            for (addr, channel) in &blocks {
+                if !linked.contains(addr) {
+                    report.actions.push(RecoveryAction::ScannedChannel {
+                        channel: channel.name.clone(),
+                        addr: *addr,
+                    });
+                }
+            }
+        }
+
+        let mut channels = vec![];
+        for addr in list_order(&blocks, header.channel_meta_ptr) {
+            let channel = blocks.remove(&addr).unwrap();
+            if channel.datatype == Datatype::Invalid {
+                report.actions.push(RecoveryAction::DroppedChannel {
+                    channel: channel.name,
+                    reason: "invalid datatype".to_string(),
+                });
+                continue;
+            }
+
+            let size = channel.datatype.size() as u64;
+            let available = file_len.saturating_sub(channel.data_addr as u64) / size;
+            let count = (channel.data_count as u64).min(available) as u32;
+            if count < channel.data_count {
+                if count == 0 {
+                    report.actions.push(RecoveryAction::DroppedChannel {
+                        channel: channel.name,
+                        reason: "no data left in the file".to_string(),
+                    });
+                    continue;
+                }
+                report.actions.push(RecoveryAction::ClampedDataCount {
+                    channel: channel.name.clone(),
+                    found: channel.data_count,
+                    recovered: count,
+                });
+            }
+
+            let samples = self.channel_data_range(&channel, 0..count as usize)?;
+            let mut channel = channel;
+            channel.data_count = count;
+            report.channels.push(channel.name.clone());
+            channels.push((channel, samples));
+        }
+
+        let session = Session {
+            header,
+            event,
+            venue,
+            vehicle,
+            channels,
+        };
+        Ok((session, report))
+    }
+
+    /// Recovers the file with [LDReader::recover] and writes the result to `sink`
+    pub fn repair<W: Write + Seek>(&mut self, sink: &mut W) -> I2Result<RepairReport> {
+        let (session, report) = self.recover()?;
+        session.write(sink)?;
+        Ok(report)
+    }
+
+    /// Collects every block reachable from `addr` in either direction
+    ///
+    /// Returns true if a link pointed at something that isn't a channel metadata block.
+    fn follow_links(
+        &mut self,
+        addr: u32,
+        file_len: u64,
+        blocks: &mut BTreeMap<u32, ChannelMetadata>,
+    ) -> bool {
+        let mut broken = false;
+        let mut pending = vec![addr];
+        while let Some(addr) = pending.pop() {
+            if addr == 0 || blocks.contains_key(&addr) {
+                continue;
+            }
+            match self.read_plausible_channel(addr, file_len) {
+                Some(channel) => {
+                    for link in [channel.next_addr, channel.prev_addr] {
+                        if link as u64 >= file_len {
+                            broken = true;
+                        } else {
+                            pending.push(link);
+                        }
+                    }
+                    blocks.insert(addr, channel);
+                }
+                None => broken = true,
+            }
+        }
+        broken
+    }
+
+    /// Reads the channel metadata at `addr` if it looks like a valid block
+    fn read_plausible_channel(&mut self, addr: u32, file_len: u64) -> Option<ChannelMetadata> {
+        if addr as u64 + ChannelMetadata::ENTRY_SIZE as u64 > file_len {
+            return None;
+        }
+        let (mut channel, (_type, size)) = self.read_raw_channel_metadata(addr).ok()?;
+        channel.datatype = Datatype::from_type_and_size(_type, size).ok()?;
+
+        let links_ok = channel.prev_addr != addr && channel.next_addr != addr;
+        let name_ok = !channel.name.is_empty() && !channel.name.chars().any(char::is_control);
+        (links_ok && name_ok).then_some(channel)
+    }
+}
+
+/// Orders `blocks` by their links, starting with the list that contains `first`
+///
+/// Blocks that can't be reached from `first` follow one list fragment at a time, in order of the
+/// lowest address of each fragment.
+fn list_order(blocks: &BTreeMap<u32, ChannelMetadata>, first: u32) -> Vec<u32> {
+    let mut order = vec![];
+    let mut visited = HashSet::new();
+    for start in std::iter::once(first).chain(blocks.keys().copied()) {
+        if !blocks.contains_key(&start) || visited.contains(&start) {
+            continue;
+        }
+        // Walk back to the start of the fragment, stopping at loops and visited blocks
+        let mut head = start;
+        let mut seen = HashSet::from([start]);
+        loop {
+            let prev = blocks[&head].prev_addr;
+            if !blocks.contains_key(&prev) || visited.contains(&prev) || !seen.insert(prev) {
+                break;
+            }
+            head = prev;
+        }
+
+        // The links of a damaged fragment may not lead back to `start`
+        for mut addr in [head, start] {
+            while blocks.contains_key(&addr) && visited.insert(addr) {
+                order.push(addr);
+                addr = blocks[&addr].next_addr;
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LDReader, RecoveryAction};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn recover_sample1() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let original = reader.read_session().unwrap();
+        let (session, report) = reader.recover().unwrap();
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(session, original);
+    }
+
+    #[test]
+    fn recover_keeps_list_order() {
+        let mut bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let channels = LDReader::new(&mut Cursor::new(bytes.clone()))
+            .read_channels()
+            .unwrap();
+
+        // Swap the first two channels in the list, so that it isn't sorted by address
+        let first = channels[1].prev_addr;
+        let second = channels[0].next_addr;
+        let third = channels[1].next_addr;
+        let mut set = |offset: u32, value: u32| {
+            let offset = offset as usize;
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        set(8, second);
+        set(second, 0);
+        set(second + 4, first);
+        set(first, second);
+        set(first + 4, third);
+        set(third, first);
+
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+        let original = reader.read_session().unwrap();
+        assert_eq!(original.channels[0].0.name, channels[1].name);
+        let (session, report) = reader.recover().unwrap();
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(session, original);
+    }
+
+    #[test]
+    fn repair_truncated_file() {
+        let mut bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes.clone());
+        let mut reader = LDReader::new(&mut cursor);
+        let original = reader.read_session().unwrap();
+        let channels = reader.read_channels().unwrap();
+
+        // Break the link between the 5th and 6th channel and cut off the end of the file
+        let fifth = channels[3].next_addr as usize;
+        bytes[fifth + 4..fifth + 8].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+        let last = channels.iter().max_by_key(|c| c.data_addr).unwrap();
+        let cut = last.data_addr as usize + 11;
+        bytes.truncate(cut);
+
+        let mut cursor = Cursor::new(bytes);
+        let mut output = Cursor::new(Vec::new());
+        let report = LDReader::new(&mut cursor).repair(&mut output).unwrap();
+        assert_eq!(report.channels.len(), 78);
+        assert!(report.actions.contains(&RecoveryAction::ScannedChannel {
+            channel: channels[5].name.clone(),
+            addr: channels[4].next_addr,
+        }));
+        assert!(report.actions.contains(&RecoveryAction::ClampedDataCount {
+            channel: last.name.clone(),
+            found: last.data_count,
+            recovered: 11 / last.datatype.size() as u32,
+        }));
+
+        let mut reader = LDReader::new(&mut output);
+        assert!(reader.validate().unwrap().findings.is_empty());
+        let repaired = reader.read_session().unwrap();
+        assert_eq!(repaired.header.venue, original.header.venue);
+        assert_eq!(repaired.event, original.event);
+        for ((channel, samples), (original, expected)) in
+            repaired.channels.iter().zip(&original.channels)
+        {
+            assert_eq!(channel.name, original.name);
+            assert_eq!(samples[..], expected[..samples.len()]);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// Number of samples needed to cover `count` samples recorded at `from_rate` Hz when
+/// sampling at `to_rate` Hz
+pub fn resampled_len(count: usize, from_rate: u16, to_rate: u16) -> usize {
+    if from_rate == 0 {
+        return 0;
+    }
+    (count * to_rate as usize).div_ceil(from_rate as usize)
+}
+
+/// Resamples `values` recorded at `from_rate` Hz into `len` samples at `to_rate` Hz
+///
+/// Values in between source samples are linearly interpolated, and the last source sample is
+/// held when the output extends past the end of the source data.
+pub fn resample(values: &[f64], from_rate: u16, to_rate: u16, len: usize) -> Vec<f64> {
+    if values.is_empty() || from_rate == 0 || to_rate == 0 {
+        return vec![0.0; len];
+    }
+
+    if from_rate == to_rate {
+        let last = values[values.len() - 1];
+        return (0..len)
+            .map(|i| values.get(i).copied().unwrap_or(last))
+            .collect();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let last_index = values.len() - 1;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position.floor() as usize;
+            if index >= last_index {
+                return values[last_index];
+            }
+
+            let fraction = position - index as f64;
+            values[index] + (values[index + 1] - values[index]) * fraction
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resample::{resample, resampled_len};
+
+    #[test]
+    fn resampled_len_rounds_up() {
+        assert_eq!(resampled_len(10, 10, 20), 20);
+        assert_eq!(resampled_len(3, 2, 5), 8);
+        assert_eq!(resampled_len(3, 0, 5), 0);
+    }
+
+    #[test]
+    fn upsample_interpolates() {
+        let values = resample(&[0.0, 10.0, 20.0], 1, 2, 6);
+        assert_eq!(values, vec![0.0, 5.0, 10.0, 15.0, 20.0, 20.0]);
+    }
+
+    #[test]
+    fn downsample_picks_samples() {
+        let values = resample(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0], 4, 2, 3);
+        assert_eq!(values, vec![0.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn same_rate_holds_last_value() {
+        let values = resample(&[1.0, 2.0], 5, 5, 4);
+        assert_eq!(values, vec![1.0, 2.0, 2.0, 2.0]);
+    }
+}
@@ -0,0 +1,526 @@
+use crate::{ChannelMetadata, I2Error, I2Result, LDReader};
+use std::f64::consts::PI;
+use std::io::{Read, Seek};
+
+/// Window function applied to each segment before the FFT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// Window coefficients for a segment of `len` samples
+    pub fn coefficients(&self, len: usize) -> Vec<f64> {
+        if len < 2 {
+            return vec![1.0; len];
+        }
+        let n = (len - 1) as f64;
+        (0..len)
+            .map(|i| {
+                let x = 2.0 * PI * i as f64 / n;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Configures how a channel is split into segments for [PowerSpectralDensity] and
+/// [Spectrogram]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WelchConfig {
+    /// Length of each segment in seconds, shortened to the length of the data if needed
+    pub segment_length: f64,
+    /// Fraction of each segment that overlaps with the next one, in the range `0.0..1.0`
+    pub overlap: f64,
+    pub window: Window,
+}
+
+impl Default for WelchConfig {
+    fn default() -> Self {
+        Self {
+            segment_length: 2.0,
+            overlap: 0.5,
+            window: Window::Hann,
+        }
+    }
+}
+
+/// Single sided amplitude spectrum of a channel
+///
+/// Values are zero padded to the next power of two, so the frequency resolution is
+/// `sample_rate / frequencies.len()` at best.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spectrum {
+    /// Frequency of each bin in Hz, from 0 up to half the sample rate
+    pub frequencies: Vec<f64>,
+    /// Amplitude of each bin in the units of the channel
+    pub amplitudes: Vec<f64>,
+}
+
+/// Power spectral density of a channel estimated using Welch's method
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerSpectralDensity {
+    /// Frequency of each bin in Hz, from 0 up to half the sample rate
+    pub frequencies: Vec<f64>,
+    /// Power of each bin in units² / Hz
+    pub power: Vec<f64>,
+}
+
+/// Power spectral density of consecutive segments of a channel
+///
+/// Power is indexed as `[time][frequency]`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spectrogram {
+    /// Time of the center of each segment in seconds since the start of the data
+    pub times: Vec<f64>,
+    /// Frequency of each bin in Hz, from 0 up to half the sample rate
+    pub frequencies: Vec<f64>,
+    /// Power of each bin in units² / Hz
+    pub power: Vec<Vec<f64>>,
+}
+
+impl Spectrum {
+    /// Computes the spectrum of `values` recorded at `sample_rate` Hz
+    pub fn from_values(values: &[f64], sample_rate: u16, window: Window) -> I2Result<Self> {
+        let rate = check_sample_rate(sample_rate)?;
+        if values.is_empty() {
+            return Ok(Self {
+                frequencies: vec![],
+                amplitudes: vec![],
+            });
+        }
+
+        let coefficients = window.coefficients(values.len());
+        let gain: f64 = coefficients.iter().sum();
+        let fft_len = values.len().next_power_of_two();
+        let magnitudes = windowed_fft(values, &coefficients, fft_len);
+
+        let amplitudes = magnitudes
+            .iter()
+            .enumerate()
+            .map(|(i, m)| m.sqrt() / gain * one_sided_factor(i, fft_len))
+            .collect();
+
+        Ok(Self {
+            frequencies: frequencies(fft_len, rate),
+            amplitudes,
+        })
+    }
+
+    /// Reads a channel from `reader` and computes its spectrum
+    ///
+    /// If `time_range` is given only the samples between its start and end (in seconds) are
+    /// used, for example the start and end of a [crate::Lap].
+    pub fn read<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        window: Window,
+        time_range: Option<(f64, f64)>,
+    ) -> I2Result<Self> {
+        let values = reader.channel_values(channel)?;
+        let values = time_slice(&values, channel.sample_rate, time_range);
+        Self::from_values(values, channel.sample_rate, window)
+    }
+
+    /// Frequency in Hz of the largest amplitude, ignoring the 0 Hz bin
+    pub fn peak_frequency(&self) -> Option<f64> {
+        peak_frequency(&self.frequencies, &self.amplitudes)
+    }
+}
+
+impl PowerSpectralDensity {
+    /// Estimates the power spectral density of `values` recorded at `sample_rate` Hz
+    ///
+    /// The mean of each segment is removed before the FFT and the periodograms of all segments
+    /// are averaged.
+    pub fn from_values(values: &[f64], sample_rate: u16, config: &WelchConfig) -> I2Result<Self> {
+        let spectrogram = Spectrogram::from_values(values, sample_rate, config)?;
+
+        let segments = spectrogram.power.len().max(1) as f64;
+        let mut power = vec![0.0; spectrogram.frequencies.len()];
+        for segment in &spectrogram.power {
+            for (total, p) in power.iter_mut().zip(segment) {
+                *total += p / segments;
+            }
+        }
+
+        Ok(Self {
+            frequencies: spectrogram.frequencies,
+            power,
+        })
+    }
+
+    /// Reads a channel from `reader` and estimates its power spectral density
+    ///
+    /// See [Spectrum::read] for `time_range`.
+    pub fn read<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        config: &WelchConfig,
+        time_range: Option<(f64, f64)>,
+    ) -> I2Result<Self> {
+        let values = reader.channel_values(channel)?;
+        let values = time_slice(&values, channel.sample_rate, time_range);
+        Self::from_values(values, channel.sample_rate, config)
+    }
+
+    /// Frequency in Hz with the most power, ignoring the 0 Hz bin
+    pub fn peak_frequency(&self) -> Option<f64> {
+        peak_frequency(&self.frequencies, &self.power)
+    }
+
+    /// Total power between `low` and `high` Hz, in units²
+    pub fn band_power(&self, low: f64, high: f64) -> f64 {
+        let resolution = match self.frequencies.get(1) {
+            Some(resolution) => *resolution,
+            None => return 0.0,
+        };
+        self.frequencies
+            .iter()
+            .zip(&self.power)
+            .filter(|(f, _)| **f >= low && **f <= high)
+            .map(|(_, p)| p * resolution)
+            .sum()
+    }
+}
+
+impl Spectrogram {
+    /// Computes the spectrogram of `values` recorded at `sample_rate` Hz
+    ///
+    /// Each segment is processed the same way as in [PowerSpectralDensity::from_values].
+    pub fn from_values(values: &[f64], sample_rate: u16, config: &WelchConfig) -> I2Result<Self> {
+        let rate = check_sample_rate(sample_rate)?;
+        if !(0.0..1.0).contains(&config.overlap) {
+            return Err(I2Error::InvalidSpectrum(format!(
+                "Overlap must be in the range 0 to 1, got {}",
+                config.overlap
+            )));
+        }
+        if config.segment_length <= 0.0 || !config.segment_length.is_finite() {
+            return Err(I2Error::InvalidSpectrum(format!(
+                "Invalid segment length: {}",
+                config.segment_length
+            )));
+        }
+
+        let segment_len = ((config.segment_length * rate).round() as usize)
+            .max(2)
+            .min(values.len());
+        if segment_len == 0 {
+            return Ok(Self {
+                times: vec![],
+                frequencies: vec![],
+                power: vec![],
+            });
+        }
+        let step = ((segment_len as f64 * (1.0 - config.overlap)).round() as usize).max(1);
+
+        let coefficients = config.window.coefficients(segment_len);
+        let gain: f64 = coefficients.iter().map(|w| w * w).sum::<f64>() * rate;
+        let fft_len = segment_len.next_power_of_two();
+
+        let mut times = vec![];
+        let mut power = vec![];
+        for start in (0..=values.len() - segment_len).step_by(step) {
+            let segment = &values[start..start + segment_len];
+            let mean = segment.iter().sum::<f64>() / segment_len as f64;
+            let detrended: Vec<f64> = segment.iter().map(|v| v - mean).collect();
+
+            let magnitudes = windowed_fft(&detrended, &coefficients, fft_len);
+            power.push(
+                magnitudes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, m)| m / gain * one_sided_factor(i, fft_len))
+                    .collect(),
+            );
+            times.push((start as f64 + segment_len as f64 / 2.0) / rate);
+        }
+
+        Ok(Self {
+            times,
+            frequencies: frequencies(fft_len, rate),
+            power,
+        })
+    }
+
+    /// Reads a channel from `reader` and computes its spectrogram
+    ///
+    /// See [Spectrum::read] for `time_range`, times are relative to the start of the range.
+    pub fn read<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        config: &WelchConfig,
+        time_range: Option<(f64, f64)>,
+    ) -> I2Result<Self> {
+        let values = reader.channel_values(channel)?;
+        let values = time_slice(&values, channel.sample_rate, time_range);
+        Self::from_values(values, channel.sample_rate, config)
+    }
+}
+
+fn check_sample_rate(sample_rate: u16) -> I2Result<f64> {
+    if sample_rate == 0 {
+        return Err(I2Error::InvalidSpectrum(
+            "Channel has a sample rate of 0 Hz".into(),
+        ));
+    }
+    Ok(sample_rate as f64)
+}
+
+fn time_slice(values: &[f64], sample_rate: u16, time_range: Option<(f64, f64)>) -> &[f64] {
+    match time_range {
+        Some((start, end)) => {
+            let index = |time: f64| {
+                ((time * sample_rate as f64).round().max(0.0) as usize).min(values.len())
+            };
+            let start = index(start);
+            &values[start..index(end).max(start)]
+        }
+        None => values,
+    }
+}
+
+/// Frequencies of the bins of a single sided spectrum
+fn frequencies(fft_len: usize, rate: f64) -> Vec<f64> {
+    (0..=fft_len / 2)
+        .map(|i| i as f64 * rate / fft_len as f64)
+        .collect()
+}
+
+/// Bins other than 0 Hz and the Nyquist frequency also hold the power of the negative frequencies
+fn one_sided_factor(bin: usize, fft_len: usize) -> f64 {
+    if bin == 0 || bin * 2 == fft_len {
+        1.0
+    } else {
+        2.0
+    }
+}
+
+fn peak_frequency(frequencies: &[f64], values: &[f64]) -> Option<f64> {
+    frequencies
+        .iter()
+        .zip(values)
+        .skip(1)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(f, _)| *f)
+}
+
+/// Squared magnitudes of the single sided FFT of `values` multiplied with `window`
+fn windowed_fft(values: &[f64], window: &[f64], fft_len: usize) -> Vec<f64> {
+    let mut re = vec![0.0; fft_len];
+    let mut im = vec![0.0; fft_len];
+    for ((re, value), w) in re.iter_mut().zip(values).zip(window) {
+        *re = value * w;
+    }
+
+    fft(&mut re, &mut im);
+    re.iter()
+        .zip(&im)
+        .take(fft_len / 2 + 1)
+        .map(|(re, im)| re * re + im * im)
+        .collect()
+}
+
+/// In place iterative radix-2 FFT, the length must be a power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    if n < 2 {
+        return;
+    }
+
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fft;
+    use crate::{
+        I2Error, LDReader, PowerSpectralDensity, Spectrogram, Spectrum, WelchConfig, Window,
+    };
+    use std::f64::consts::PI;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn sine(frequency: f64, amplitude: f64, rate: u16, seconds: usize) -> Vec<f64> {
+        (0..rate as usize * seconds)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f64 / rate as f64).sin())
+            .collect()
+    }
+
+    #[test]
+    fn fft_matches_dft() {
+        let values = [1.0, 2.0, 0.0, -1.0, 3.0, 0.5, -2.0, 1.5];
+        let mut re = values.to_vec();
+        let mut im = vec![0.0; values.len()];
+        fft(&mut re, &mut im);
+
+        let n = values.len() as f64;
+        for k in 0..values.len() {
+            let (mut dft_re, mut dft_im) = (0.0, 0.0);
+            for (i, value) in values.iter().enumerate() {
+                let angle = -2.0 * PI * k as f64 * i as f64 / n;
+                dft_re += value * angle.cos();
+                dft_im += value * angle.sin();
+            }
+            assert!((re[k] - dft_re).abs() < 1e-9);
+            assert!((im[k] - dft_im).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn spectrum_finds_sine() {
+        // 8 Hz falls exactly on a bin when 256 samples are taken at 128 Hz
+        let values = sine(8.0, 3.0, 128, 2);
+        let spectrum = Spectrum::from_values(&values, 128, Window::Rectangular).unwrap();
+
+        assert_eq!(spectrum.frequencies.len(), 129);
+        assert_eq!(spectrum.frequencies[128], 64.0);
+        assert_eq!(spectrum.peak_frequency(), Some(8.0));
+        assert!((spectrum.amplitudes[16] - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn welch_psd_preserves_power() {
+        let values: Vec<f64> = sine(5.0, 2.0, 100, 20)
+            .iter()
+            .zip(sine(20.0, 1.0, 100, 20))
+            .map(|(a, b)| a + b + 10.0)
+            .collect();
+        let psd = PowerSpectralDensity::from_values(&values, 100, &WelchConfig::default()).unwrap();
+
+        assert_eq!(psd.frequencies[1], 100.0 / 256.0);
+        assert!((psd.peak_frequency().unwrap() - 5.0).abs() < 0.5);
+        // Parseval: the power of a sine is amplitude² / 2 and the mean is removed
+        assert!((psd.band_power(0.0, 50.0) - 2.5).abs() < 0.05);
+        assert!((psd.band_power(3.0, 7.0) - 2.0).abs() < 0.05);
+        assert!((psd.band_power(18.0, 22.0) - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn spectrogram_tracks_frequency_changes() {
+        let mut values = sine(2.0, 1.0, 50, 10);
+        values.extend(sine(15.0, 1.0, 50, 10));
+        let config = WelchConfig {
+            segment_length: 2.0,
+            overlap: 0.0,
+            window: Window::Hann,
+        };
+        let spectrogram = Spectrogram::from_values(&values, 50, &config).unwrap();
+
+        assert_eq!(
+            spectrogram.times,
+            vec![1.0, 3.0, 5.0, 7.0, 9.0, 11.0, 13.0, 15.0, 17.0, 19.0]
+        );
+        assert_eq!(spectrogram.power.len(), 10);
+        let peak = |segment: &Vec<f64>| {
+            let (i, _) = segment
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            spectrogram.frequencies[i]
+        };
+        assert!((peak(&spectrogram.power[0]) - 2.0).abs() < 0.5);
+        assert!((peak(&spectrogram.power[9]) - 15.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let values = [0.0; 10];
+        let invalid = |config: WelchConfig, rate: u16| {
+            matches!(
+                Spectrogram::from_values(&values, rate, &config),
+                Err(I2Error::InvalidSpectrum(_))
+            )
+        };
+
+        assert!(invalid(WelchConfig::default(), 0));
+        assert!(invalid(
+            WelchConfig {
+                overlap: 1.0,
+                ..WelchConfig::default()
+            },
+            10
+        ));
+        assert!(invalid(
+            WelchConfig {
+                segment_length: 0.0,
+                ..WelchConfig::default()
+            },
+            10
+        ));
+
+        let empty = PowerSpectralDensity::from_values(&[], 10, &WelchConfig::default()).unwrap();
+        assert!(empty.power.is_empty());
+    }
+
+    #[test]
+    fn read_sample1_spectra() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let susp = channels.iter().find(|c| c.name == "Susp Pos FL").unwrap();
+        let laps = reader.read_laps().unwrap();
+        let lap = &laps[2];
+
+        let psd = PowerSpectralDensity::read(
+            &mut reader,
+            susp,
+            &WelchConfig::default(),
+            Some((lap.start, lap.end)),
+        )
+        .unwrap();
+        assert_eq!(psd.frequencies.last(), Some(&50.0));
+        assert!(psd.power.iter().all(|p| p.is_finite() && *p >= 0.0));
+
+        let spectrogram =
+            Spectrogram::read(&mut reader, susp, &WelchConfig::default(), None).unwrap();
+        // 2s segments with 1s overlap over 454s of data
+        assert_eq!(spectrogram.times.len(), 453);
+        assert_eq!(spectrogram.power[0].len(), psd.frequencies.len());
+
+        let spectrum = Spectrum::read(&mut reader, susp, Window::Hann, None).unwrap();
+        assert_eq!(spectrum.frequencies.len(), 65536 / 2 + 1);
+    }
+}
@@ -0,0 +1,541 @@
+use crate::{ChannelMetadata, I2Result, LDReader, Lap};
+use std::convert::Infallible;
+use std::io::{Read, Seek};
+
+/// Configures which optional statistics are computed by a [StatsAccumulator]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatsConfig {
+    /// Percentiles to estimate, in the range `0.0..=100.0`
+    pub percentiles: Vec<f64>,
+    /// Thresholds for which the time spent above them is measured
+    pub thresholds: Vec<f64>,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            percentiles: vec![5.0, 25.0, 50.0, 75.0, 95.0],
+            thresholds: vec![],
+        }
+    }
+}
+
+/// Summary statistics of a channel or part of a channel
+///
+/// All values are in the physical units of the channel (see [crate::Sample::decode_f64]).
+/// NaN and infinite samples are only counted in `non_finite`. When no finite samples were seen
+/// `min`, `max`, `mean`, `std_dev` and all percentiles are NaN.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelStats {
+    /// Number of finite samples
+    pub count: usize,
+    /// Number of NaN or infinite samples
+    pub non_finite: usize,
+    /// Time covered by the samples in seconds
+    pub duration: f64,
+
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Population standard deviation
+    pub std_dev: f64,
+
+    /// Estimated value for each of [StatsConfig::percentiles], in the same order
+    pub percentiles: Vec<f64>,
+    /// Time in seconds spent above each of [StatsConfig::thresholds], in the same order
+    pub time_above: Vec<f64>,
+}
+
+/// Computes [ChannelStats] in a single pass over the samples with constant memory
+///
+/// Percentiles are estimated using the P² algorithm, which is exact for up to 5 samples and
+/// converges on the true percentile for longer channels.
+#[derive(Debug, Clone)]
+pub struct StatsAccumulator {
+    sample_rate: u16,
+    count: usize,
+    non_finite: usize,
+    min: f64,
+    max: f64,
+    mean: f64,
+    /// Sum of squared differences from the mean (Welford's algorithm)
+    m2: f64,
+    percentiles: Vec<P2Estimator>,
+    thresholds: Vec<(f64, usize)>,
+}
+
+impl StatsAccumulator {
+    pub fn new(sample_rate: u16, config: &StatsConfig) -> Self {
+        Self {
+            sample_rate,
+            count: 0,
+            non_finite: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            percentiles: config
+                .percentiles
+                .iter()
+                .map(|p| P2Estimator::new(p / 100.0))
+                .collect(),
+            thresholds: config.thresholds.iter().map(|t| (*t, 0)).collect(),
+        }
+    }
+
+    /// Adds a single value to the statistics
+    pub fn push(&mut self, value: f64) {
+        if !value.is_finite() {
+            self.non_finite += 1;
+            return;
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+
+        for estimator in self.percentiles.iter_mut() {
+            estimator.push(value);
+        }
+        for (threshold, count) in self.thresholds.iter_mut() {
+            if value > *threshold {
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn finish(&self) -> ChannelStats {
+        let seconds = |count: usize| {
+            if self.sample_rate == 0 {
+                0.0
+            } else {
+                count as f64 / self.sample_rate as f64
+            }
+        };
+
+        let duration = seconds(self.count + self.non_finite);
+        if self.count == 0 {
+            return ChannelStats {
+                count: 0,
+                non_finite: self.non_finite,
+                duration,
+                min: f64::NAN,
+                max: f64::NAN,
+                mean: f64::NAN,
+                std_dev: f64::NAN,
+                percentiles: vec![f64::NAN; self.percentiles.len()],
+                time_above: vec![0.0; self.thresholds.len()],
+            };
+        }
+
+        ChannelStats {
+            count: self.count,
+            non_finite: self.non_finite,
+            duration,
+            min: self.min,
+            max: self.max,
+            mean: self.mean,
+            std_dev: (self.m2 / self.count as f64).sqrt(),
+            percentiles: self.percentiles.iter().map(P2Estimator::value).collect(),
+            time_above: self
+                .thresholds
+                .iter()
+                .map(|(_, count)| seconds(*count))
+                .collect(),
+        }
+    }
+}
+
+impl ChannelStats {
+    /// Computes the statistics of a whole channel
+    pub fn from_values(
+        values: impl IntoIterator<Item = f64>,
+        sample_rate: u16,
+        config: &StatsConfig,
+    ) -> Self {
+        let mut acc = StatsAccumulator::new(sample_rate, config);
+        values.into_iter().for_each(|value| acc.push(value));
+        acc.finish()
+    }
+
+    /// Computes the statistics for consecutive windows of `window` seconds
+    ///
+    /// The last window may be shorter if the channel doesn't end on a window boundary.
+    pub fn per_window(
+        values: impl IntoIterator<Item = f64>,
+        sample_rate: u16,
+        window: f64,
+        config: &StatsConfig,
+    ) -> Vec<Self> {
+        unwrap_infallible(try_per_window(
+            values.into_iter().map(Ok),
+            sample_rate,
+            window,
+            config,
+        ))
+    }
+
+    /// Computes the statistics for each lap, returning one entry per lap in `laps`
+    ///
+    /// Samples outside of all laps are ignored.
+    pub fn per_lap(
+        values: impl IntoIterator<Item = f64>,
+        sample_rate: u16,
+        laps: &[Lap],
+        config: &StatsConfig,
+    ) -> Vec<Self> {
+        unwrap_infallible(try_per_lap(
+            values.into_iter().map(Ok),
+            sample_rate,
+            laps,
+            config,
+        ))
+    }
+
+    /// Computes the statistics of a channel while lazily reading it from `reader`
+    pub fn read<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        config: &StatsConfig,
+    ) -> I2Result<Self> {
+        let mut acc = StatsAccumulator::new(channel.sample_rate, config);
+        for sample in reader.iter_channel_data(channel)? {
+            acc.push(sample?.decode_f64(channel));
+        }
+        Ok(acc.finish())
+    }
+
+    /// Same as [ChannelStats::per_window] but lazily reading the channel from `reader`
+    pub fn read_per_window<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        window: f64,
+        config: &StatsConfig,
+    ) -> I2Result<Vec<Self>> {
+        let values = reader
+            .iter_channel_data(channel)?
+            .map(|sample| sample.map(|s| s.decode_f64(channel)));
+        try_per_window(values, channel.sample_rate, window, config)
+    }
+
+    /// Same as [ChannelStats::per_lap] but lazily reading the channel from `reader`
+    pub fn read_per_lap<S: Read + Seek>(
+        reader: &mut LDReader<S>,
+        channel: &ChannelMetadata,
+        laps: &[Lap],
+        config: &StatsConfig,
+    ) -> I2Result<Vec<Self>> {
+        let values = reader
+            .iter_channel_data(channel)?
+            .map(|sample| sample.map(|s| s.decode_f64(channel)));
+        try_per_lap(values, channel.sample_rate, laps, config)
+    }
+}
+
+fn unwrap_infallible<T>(result: Result<T, Infallible>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
+
+fn try_per_window<E>(
+    values: impl Iterator<Item = Result<f64, E>>,
+    sample_rate: u16,
+    window: f64,
+    config: &StatsConfig,
+) -> Result<Vec<ChannelStats>, E> {
+    let mut windows = vec![];
+    if sample_rate == 0 || window <= 0.0 {
+        return Ok(windows);
+    }
+
+    let mut acc = StatsAccumulator::new(sample_rate, config);
+    let mut current = 0;
+    for (i, value) in values.enumerate() {
+        let index = (i as f64 / sample_rate as f64 / window).floor() as usize;
+        while current < index {
+            windows.push(acc.finish());
+            acc = StatsAccumulator::new(sample_rate, config);
+            current += 1;
+        }
+        acc.push(value?);
+    }
+
+    if acc.count + acc.non_finite > 0 {
+        windows.push(acc.finish());
+    }
+    Ok(windows)
+}
+
+fn try_per_lap<E>(
+    values: impl Iterator<Item = Result<f64, E>>,
+    sample_rate: u16,
+    laps: &[Lap],
+    config: &StatsConfig,
+) -> Result<Vec<ChannelStats>, E> {
+    let mut accs: Vec<_> = laps
+        .iter()
+        .map(|_| StatsAccumulator::new(sample_rate, config))
+        .collect();
+
+    if sample_rate > 0 {
+        for (i, value) in values.enumerate() {
+            let value = value?;
+            let time = i as f64 / sample_rate as f64;
+            for (lap, acc) in laps.iter().zip(accs.iter_mut()) {
+                if lap.contains(time) {
+                    acc.push(value);
+                }
+            }
+        }
+    }
+
+    Ok(accs.iter().map(StatsAccumulator::finish).collect())
+}
+
+/// Streaming quantile estimator using the P² algorithm (Jain & Chlamtac, 1985)
+#[derive(Debug, Clone)]
+struct P2Estimator {
+    p: f64,
+    count: usize,
+    /// Marker heights
+    q: [f64; 5],
+    /// Marker positions
+    n: [f64; 5],
+    /// Desired marker positions
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Estimator {
+    fn new(p: f64) -> Self {
+        let p = p.clamp(0.0, 1.0);
+        Self {
+            p,
+            count: 0,
+            q: [0.0; 5],
+            n: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        if self.count < 5 {
+            self.q[self.count] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.q.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+
+        let k = if value < self.q[0] {
+            self.q[0] = value;
+            0
+        } else if value >= self.q[4] {
+            self.q[4] = value;
+            3
+        } else {
+            (0..4)
+                .find(|&i| value < self.q[i + 1])
+                .expect("value is below the last marker")
+        };
+
+        for i in (k + 1)..5 {
+            self.n[i] += 1.0;
+        }
+        for i in 0..5 {
+            self.desired[i] += self.increments[i];
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.n[i];
+            if (d >= 1.0 && self.n[i + 1] - self.n[i] > 1.0)
+                || (d <= -1.0 && self.n[i - 1] - self.n[i] < -1.0)
+            {
+                let d = d.signum();
+                let parabolic = self.parabolic(i, d);
+                self.q[i] = if self.q[i - 1] < parabolic && parabolic < self.q[i + 1] {
+                    parabolic
+                } else {
+                    self.linear(i, d)
+                };
+                self.n[i] += d;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.q, &self.n);
+        q[i] + d / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = if d > 0.0 { i + 1 } else { i - 1 };
+        self.q[i] + d * (self.q[j] - self.q[i]) / (self.n[j] - self.n[i])
+    }
+
+    fn value(&self) -> f64 {
+        match self.count {
+            0 => f64::NAN,
+            count if count <= 5 => {
+                // Not enough samples for the markers, interpolate between the sorted samples
+                let mut sorted = self.q[..count].to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let position = self.p * (count - 1) as f64;
+                let lower = position.floor() as usize;
+                let upper = position.ceil() as usize;
+                sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+            }
+            // The outer markers always track the exact minimum and maximum
+            _ if self.p == 0.0 => self.q[0],
+            _ if self.p == 1.0 => self.q[4],
+            _ => self.q[2],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChannelStats, LDReader, Lap, StatsConfig};
+    use std::fs;
+    use std::io::Cursor;
+
+    fn config() -> StatsConfig {
+        StatsConfig {
+            percentiles: vec![0.0, 50.0, 90.0, 100.0],
+            thresholds: vec![2.5],
+        }
+    }
+
+    #[test]
+    fn stats_small_channel() {
+        let stats = ChannelStats::from_values([1.0, 2.0, 3.0, 4.0], 2, &config());
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.duration, 2.0);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.mean, 2.5);
+        assert!((stats.std_dev - 1.25f64.sqrt()).abs() < 1e-12);
+        assert_eq!(stats.percentiles, vec![1.0, 2.5, 3.7, 4.0]);
+        assert_eq!(stats.time_above, vec![1.0]);
+    }
+
+    #[test]
+    fn stats_empty_channel() {
+        let stats = ChannelStats::from_values([], 2, &config());
+        assert_eq!(stats.count, 0);
+        assert!(stats.mean.is_nan());
+        assert!(stats.percentiles.iter().all(|p| p.is_nan()));
+        assert_eq!(stats.time_above, vec![0.0]);
+    }
+
+    #[test]
+    fn stats_skip_non_finite() {
+        let values = [
+            1.0,
+            2.0,
+            f64::NAN,
+            3.0,
+            4.0,
+            5.0,
+            f64::NAN,
+            6.0,
+            f64::INFINITY,
+        ];
+        let stats = ChannelStats::from_values(values, 1, &config());
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.non_finite, 3);
+        assert_eq!(stats.duration, 9.0);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 6.0);
+        assert_eq!(stats.mean, 3.5);
+        assert_eq!(stats.percentiles[0], 1.0);
+        assert_eq!(stats.percentiles[3], 6.0);
+        assert!(stats.percentiles.iter().all(|p| p.is_finite()));
+
+        let stats = ChannelStats::from_values([f64::NAN], 1, &config());
+        assert_eq!(stats.count, 0);
+        assert_eq!(stats.non_finite, 1);
+        assert!(stats.mean.is_nan());
+    }
+
+    #[test]
+    fn percentile_estimates_converge() {
+        // A shuffled permutation of 0..1000
+        let values = (0..1000).map(|i| ((i * 7919) % 1000) as f64);
+        let stats = ChannelStats::from_values(values, 100, &config());
+        assert_eq!(stats.percentiles[0], 0.0);
+        assert!((stats.percentiles[1] - 500.0).abs() < 10.0);
+        assert!((stats.percentiles[2] - 900.0).abs() < 10.0);
+        assert_eq!(stats.percentiles[3], 999.0);
+    }
+
+    #[test]
+    fn stats_per_window_and_lap() {
+        let values = [1.0, 1.0, 2.0, 2.0, 3.0];
+        let windows = ChannelStats::per_window(values, 2, 1.0, &config());
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[1].mean, 2.0);
+        assert_eq!(windows[2].count, 1);
+
+        let laps = vec![
+            Lap {
+                number: 1,
+                start: 0.5,
+                end: 1.5,
+            },
+            Lap {
+                number: 2,
+                start: 1.5,
+                end: 10.0,
+            },
+        ];
+        let laps = ChannelStats::per_lap(values, 2, &laps, &config());
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps[0].count, 2);
+        assert_eq!(laps[0].mean, 1.5);
+        assert_eq!(laps[1].count, 2);
+        assert_eq!(laps[1].mean, 2.5);
+    }
+
+    #[test]
+    fn read_sample1_stats() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let channel = channels.iter().find(|c| c.name == "Ground Speed").unwrap();
+        let config = StatsConfig {
+            thresholds: vec![100.0],
+            ..StatsConfig::default()
+        };
+
+        let stats = ChannelStats::read(&mut reader, channel, &config).unwrap();
+        let values = reader.channel_values(channel).unwrap();
+        assert_eq!(stats, ChannelStats::from_values(values, 10, &config));
+        assert_eq!(stats.count, 4540);
+        assert_eq!(stats.duration, 454.0);
+        assert!(stats.min <= stats.percentiles[0] && stats.percentiles[4] <= stats.max);
+
+        let laps = reader.read_laps().unwrap();
+        let per_lap = ChannelStats::read_per_lap(&mut reader, channel, &laps, &config).unwrap();
+        assert_eq!(per_lap.len(), laps.len());
+        assert_eq!(per_lap.iter().map(|s| s.count).sum::<usize>(), 4540);
+
+        let per_window =
+            ChannelStats::read_per_window(&mut reader, channel, 60.0, &config).unwrap();
+        assert_eq!(per_window.len(), 8);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{I2Error, I2Result};
 
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub channel_meta_ptr: u32,
     pub channel_data_ptr: u32,
@@ -25,6 +26,7 @@ pub struct Header {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Sample {
     I16(i16),
     I32(i32),
@@ -44,12 +46,45 @@ impl Sample {
         assert_eq!(channel.offset, 0);
         let value = value / channel.scale as f64;
         let value = value * (10.0f64.powi(-channel.dec_places as i32));
-        let value = value * channel.mul as f64;
-        value
+        value * channel.mul as f64
     }
+
+    /// Encodes a final value into a sample for `channel`, this is the inverse of [Sample::decode_f64]
+    ///
+    /// Integer datatypes are rounded and saturate at the limits of the datatype. Channels with
+    /// an `offset` aren't supported yet and return [I2Error::UnsupportedOffset].
+    pub fn encode_f64(value: f64, channel: &ChannelMetadata) -> I2Result<Self> {
+        // TODO: Offset not yet supported
+        if channel.offset != 0 {
+            return Err(I2Error::UnsupportedOffset(channel.offset));
+        }
+        let value = value / channel.mul as f64;
+        let value = value * (10.0f64.powi(channel.dec_places as i32));
+        let value = value * channel.scale as f64;
+
+        match channel.datatype {
+            Datatype::Beacon16 | Datatype::I16 => Ok(Sample::I16(value.round() as i16)),
+            Datatype::Beacon32 | Datatype::I32 => Ok(Sample::I32(value.round() as i32)),
+            Datatype::F32 => Ok(Sample::F32(value as f32)),
+            Datatype::F16 | Datatype::Invalid => {
+                Err(I2Error::UnsupportedDatatype(channel.datatype.clone()))
+            }
+        }
+    }
+}
+
+/// Whether `sample` has the size and type that `datatype` is stored as
+pub(crate) fn sample_matches(sample: &Sample, datatype: &Datatype) -> bool {
+    matches!(
+        (sample, datatype),
+        (Sample::I16(_), Datatype::I16 | Datatype::Beacon16)
+            | (Sample::I32(_), Datatype::I32 | Datatype::Beacon32)
+            | (Sample::F32(_), Datatype::F32)
+    )
 }
 
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Datatype {
     // TODO: Not Too sure about this data type, it shows up as beacon in the sample dataset
     // It behaves as an integer of the same size
@@ -112,6 +147,7 @@ impl Datatype {
 /// ChannelMetadata is a doubly linked list of blocks in the file
 /// This only contains info about a channel, actual data is stored somewhere else on the file.
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelMetadata {
     pub prev_addr: u32,
     pub next_addr: u32,
@@ -137,13 +173,72 @@ impl ChannelMetadata {
     /// Size of a metadata entry in bytes
     pub(crate) const ENTRY_SIZE: u32 = 124;
 
+    /// Most decimal places chosen by [ChannelMetadata::fit_to_values]
+    pub const MAX_FIT_DEC_PLACES: i16 = 3;
+
     /// Calculates the size in bytes of the data section for this channel
-    pub(crate) fn data_size(&self) -> u32 {
-        self.data_count * self.datatype.size() as u32
+    pub(crate) fn data_size(&self) -> u64 {
+        self.data_count as u64 * self.datatype.size() as u64
+    }
+
+    /// Creates the metadata for a new channel derived from this one
+    ///
+    /// The new channel keeps the datatype, sample rate, scaling and unit of this channel, but
+    /// has its file addresses and sample count cleared.
+    pub fn derive(&self, name: &str, short_name: &str) -> ChannelMetadata {
+        ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            name: name.to_string(),
+            short_name: short_name.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Chooses an integer datatype and number of decimal places that can store `values`
+    ///
+    /// Prefers `I16` samples with up to [ChannelMetadata::MAX_FIT_DEC_PLACES] decimal places,
+    /// falling back to `I32` samples for values that don't fit. Resets `scale`, `mul` and
+    /// `offset` to their neutral values.
+    pub fn fit_to_values(&mut self, values: &[f64]) {
+        let max = values
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold(0.0f64, |max, v| max.max(v.abs()));
+
+        let fits = |limit: f64, dec_places: i16| max * 10.0f64.powi(dec_places as i32) <= limit;
+        let (datatype, limit) = if fits(i16::MAX as f64, 0) {
+            (Datatype::I16, i16::MAX as f64)
+        } else {
+            (Datatype::I32, i32::MAX as f64)
+        };
+
+        self.datatype = datatype;
+        self.dec_places = (0..=Self::MAX_FIT_DEC_PLACES)
+            .rev()
+            .find(|dec_places| fits(limit, *dec_places))
+            .unwrap_or(0);
+        self.scale = 1;
+        self.mul = 1;
+        self.offset = 0;
+    }
+
+    /// Encodes final values into samples of this channel, see [Sample::encode_f64]
+    pub fn encode_values(&self, values: &[f64]) -> I2Result<Vec<Sample>> {
+        values
+            .iter()
+            .map(|value| Sample::encode_f64(*value, self))
+            .collect()
     }
 }
 
+/// A channel together with its samples, as passed to [crate::LDWriter::with_channel]
+pub type ChannelWithSamples = (ChannelMetadata, Vec<Sample>);
+
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     /// Max 64 chars
     pub name: String,
@@ -156,6 +251,7 @@ pub struct Event {
 }
 
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Venue {
     /// Max 64 chars
     pub name: String,
@@ -164,11 +260,13 @@ pub struct Venue {
 }
 
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vehicle {
     /// Max 64 chars
     pub id: String,
     pub weight: u32,
     /// Max 32 chars
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
     pub _type: String,
     /// Max 32 chars
     pub comment: String,
@@ -0,0 +1,415 @@
+/// A channel decoded from sim telemetry packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelemetryChannel {
+    pub name: &'static str,
+    pub short_name: &'static str,
+    pub unit: &'static str,
+}
+
+const fn channel(
+    name: &'static str,
+    short_name: &'static str,
+    unit: &'static str,
+) -> TelemetryChannel {
+    TelemetryChannel {
+        name,
+        short_name,
+        unit,
+    }
+}
+
+/// A kind of packet, all channels of a stream are sent together at the same rate
+#[derive(Debug, PartialEq, Eq)]
+pub struct TelemetryStream {
+    pub name: &'static str,
+    pub channels: &'static [TelemetryChannel],
+}
+
+/// The values of one packet, in the order of [TelemetryStream::channels]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryPacket {
+    pub stream: &'static TelemetryStream,
+    /// Time stamp of the packet in seconds, if the protocol has one
+    pub time: Option<f64>,
+    pub values: Vec<f64>,
+}
+
+/// Telemetry protocols that can be decoded with [TelemetryProtocol::decode]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryProtocol {
+    /// Live for Speed and BeamNG dashboard packets
+    OutGauge,
+    /// Live for Speed and BeamNG motion packets
+    OutSim,
+    /// Forza Motorsport and Forza Horizon "Data Out", both the Sled and Dash formats
+    Forza,
+    /// Codemasters / EA F1 23 and F1 24, the motion, lap data and car telemetry packets of the
+    /// player's car
+    F1,
+}
+
+impl TelemetryProtocol {
+    /// Decodes a UDP packet, packets that aren't recognized or carry no data are ignored
+    pub fn decode(&self, packet: &[u8]) -> Vec<TelemetryPacket> {
+        let packet = Packet(packet);
+        match self {
+            TelemetryProtocol::OutGauge => decode_outgauge(packet),
+            TelemetryProtocol::OutSim => decode_outsim(packet),
+            TelemetryProtocol::Forza => decode_forza(packet),
+            TelemetryProtocol::F1 => decode_f1(packet),
+        }
+    }
+}
+
+pub static OUTGAUGE: TelemetryStream = TelemetryStream {
+    name: "OutGauge",
+    channels: &[
+        channel("Ground Speed", "Speed", "km/h"),
+        channel("Engine RPM", "RPM", "rpm"),
+        channel("Turbo Boost", "Boost", "bar"),
+        channel("Engine Temp", "EngTemp", "C"),
+        channel("Fuel Level", "Fuel", "%"),
+        channel("Eng Oil Pres", "OilPres", "bar"),
+        channel("Eng Oil Temp", "OilTemp", "C"),
+        channel("Throttle Pos", "Throttle", "%"),
+        channel("Brake Pos", "Brake", "%"),
+        channel("Clutch Pos", "Clutch", "%"),
+        channel("Gear", "Gear", ""),
+    ],
+};
+
+pub static OUTSIM: TelemetryStream = TelemetryStream {
+    name: "OutSim",
+    channels: &[
+        channel("Roll Rate", "RollRate", "deg/s"),
+        channel("Pitch Rate", "PtchRate", "deg/s"),
+        channel("Yaw Rate", "YawRate", "deg/s"),
+        channel("Heading", "Heading", "deg"),
+        channel("Pitch Angle", "Pitch", "deg"),
+        channel("Roll Angle", "Roll", "deg"),
+        channel("Accel X", "AccelX", "m/s/s"),
+        channel("Accel Y", "AccelY", "m/s/s"),
+        channel("Accel Z", "AccelZ", "m/s/s"),
+        channel("Velocity X", "VelX", "m/s"),
+        channel("Velocity Y", "VelY", "m/s"),
+        channel("Velocity Z", "VelZ", "m/s"),
+        channel("World Pos X", "PosX", "m"),
+        channel("World Pos Y", "PosY", "m"),
+        channel("World Pos Z", "PosZ", "m"),
+    ],
+};
+
+pub static FORZA_SLED: TelemetryStream = TelemetryStream {
+    name: "Forza Sled",
+    channels: &[
+        channel("Engine RPM", "RPM", "rpm"),
+        channel("G Force Lat", "GLat", "G"),
+        channel("G Force Vert", "GVert", "G"),
+        channel("G Force Long", "GLong", "G"),
+        channel("Yaw Rate", "YawRate", "deg/s"),
+        channel("Yaw Angle", "Yaw", "deg"),
+        channel("Pitch Angle", "Pitch", "deg"),
+        channel("Roll Angle", "Roll", "deg"),
+        channel("Susp Pos FL", "SuspFL", "mm"),
+        channel("Susp Pos FR", "SuspFR", "mm"),
+        channel("Susp Pos RL", "SuspRL", "mm"),
+        channel("Susp Pos RR", "SuspRR", "mm"),
+        channel("Tyre Slip Ratio FL", "SlipFL", ""),
+        channel("Tyre Slip Ratio FR", "SlipFR", ""),
+        channel("Tyre Slip Ratio RL", "SlipRL", ""),
+        channel("Tyre Slip Ratio RR", "SlipRR", ""),
+    ],
+};
+
+pub static FORZA_DASH: TelemetryStream = TelemetryStream {
+    name: "Forza Dash",
+    channels: &[
+        channel("Ground Speed", "Speed", "km/h"),
+        channel("Engine Power", "Power", "kW"),
+        channel("Engine Torque", "Torque", "Nm"),
+        channel("Tyre Temp FL", "TTempFL", "C"),
+        channel("Tyre Temp FR", "TTempFR", "C"),
+        channel("Tyre Temp RL", "TTempRL", "C"),
+        channel("Tyre Temp RR", "TTempRR", "C"),
+        channel("Turbo Boost", "Boost", "psi"),
+        channel("Fuel Level", "Fuel", "%"),
+        channel("Distance", "Dist", "m"),
+        channel("Running Lap Time", "LapTime", "s"),
+        channel("Lap Number", "Lap", ""),
+        channel("Race Position", "Position", ""),
+        channel("Throttle Pos", "Throttle", "%"),
+        channel("Brake Pos", "Brake", "%"),
+        channel("Clutch Pos", "Clutch", "%"),
+        channel("Gear", "Gear", ""),
+        channel("Steering", "Steer", "%"),
+    ],
+};
+
+pub static F1_MOTION: TelemetryStream = TelemetryStream {
+    name: "F1 Motion",
+    channels: &[
+        channel("World Pos X", "PosX", "m"),
+        channel("World Pos Y", "PosY", "m"),
+        channel("World Pos Z", "PosZ", "m"),
+        channel("G Force Lat", "GLat", "G"),
+        channel("G Force Long", "GLong", "G"),
+        channel("G Force Vert", "GVert", "G"),
+        channel("Yaw Angle", "Yaw", "deg"),
+        channel("Pitch Angle", "Pitch", "deg"),
+        channel("Roll Angle", "Roll", "deg"),
+    ],
+};
+
+pub static F1_LAP_DATA: TelemetryStream = TelemetryStream {
+    name: "F1 Lap Data",
+    channels: &[
+        channel("Last Lap Time", "LastLap", "s"),
+        channel("Running Lap Time", "LapTime", "s"),
+        channel("Lap Distance", "LapDist", "m"),
+        channel("Distance", "Dist", "m"),
+        channel("Race Position", "Position", ""),
+        channel("Lap Number", "Lap", ""),
+    ],
+};
+
+pub static F1_CAR_TELEMETRY: TelemetryStream = TelemetryStream {
+    name: "F1 Car Telemetry",
+    channels: &[
+        channel("Ground Speed", "Speed", "km/h"),
+        channel("Throttle Pos", "Throttle", "%"),
+        channel("Steering", "Steer", "%"),
+        channel("Brake Pos", "Brake", "%"),
+        channel("Clutch Pos", "Clutch", "%"),
+        channel("Gear", "Gear", ""),
+        channel("Engine RPM", "RPM", "rpm"),
+        channel("DRS", "DRS", ""),
+        channel("Brake Temp RL", "BTempRL", "C"),
+        channel("Brake Temp RR", "BTempRR", "C"),
+        channel("Brake Temp FL", "BTempFL", "C"),
+        channel("Brake Temp FR", "BTempFR", "C"),
+        channel("Tyre Temp RL", "TTempRL", "C"),
+        channel("Tyre Temp RR", "TTempRR", "C"),
+        channel("Tyre Temp FL", "TTempFL", "C"),
+        channel("Tyre Temp FR", "TTempFR", "C"),
+        channel("Engine Temp", "EngTemp", "C"),
+        channel("Tyre Pres RL", "TPresRL", "psi"),
+        channel("Tyre Pres RR", "TPresRR", "psi"),
+        channel("Tyre Pres FL", "TPresFL", "psi"),
+        channel("Tyre Pres FR", "TPresFR", "psi"),
+    ],
+};
+
+const STANDARD_GRAVITY: f64 = 9.806_65;
+
+/// Little endian field access that returns 0 past the end of the packet
+#[derive(Clone, Copy)]
+struct Packet<'a>(&'a [u8]);
+
+impl Packet<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> [u8; N] {
+        self.0
+            .get(offset..offset + N)
+            .and_then(|bytes| bytes.try_into().ok())
+            .unwrap_or([0; N])
+    }
+
+    fn u8(&self, offset: usize) -> f64 {
+        self.bytes::<1>(offset)[0] as f64
+    }
+
+    fn i8(&self, offset: usize) -> f64 {
+        self.bytes::<1>(offset)[0] as i8 as f64
+    }
+
+    fn u16(&self, offset: usize) -> f64 {
+        u16::from_le_bytes(self.bytes(offset)) as f64
+    }
+
+    fn i32(&self, offset: usize) -> f64 {
+        i32::from_le_bytes(self.bytes(offset)) as f64
+    }
+
+    fn u32(&self, offset: usize) -> f64 {
+        u32::from_le_bytes(self.bytes(offset)) as f64
+    }
+
+    fn f32(&self, offset: usize) -> f64 {
+        f32::from_le_bytes(self.bytes(offset)) as f64
+    }
+}
+
+fn decode_outgauge(p: Packet) -> Vec<TelemetryPacket> {
+    // 92 bytes, or 96 with the optional id
+    if p.0.len() != 92 && p.0.len() != 96 {
+        return vec![];
+    }
+    vec![TelemetryPacket {
+        stream: &OUTGAUGE,
+        time: Some(p.u32(0) / 1000.0),
+        values: vec![
+            p.f32(12) * 3.6,
+            p.f32(16),
+            p.f32(20),
+            p.f32(24),
+            p.f32(28) * 100.0,
+            p.f32(32),
+            p.f32(36),
+            p.f32(48) * 100.0,
+            p.f32(52) * 100.0,
+            p.f32(56) * 100.0,
+            // 0 is reverse and 1 is neutral
+            p.u8(10) - 1.0,
+        ],
+    }]
+}
+
+fn decode_outsim(p: Packet) -> Vec<TelemetryPacket> {
+    // 64 bytes, or 68 with the optional id
+    if p.0.len() != 64 && p.0.len() != 68 {
+        return vec![];
+    }
+    let mut values = vec![];
+    values.extend((0..6).map(|i| p.f32(4 + i * 4).to_degrees()));
+    values.extend((0..6).map(|i| p.f32(28 + i * 4)));
+    values.extend((0..3).map(|i| p.i32(52 + i * 4) / 65536.0));
+    vec![TelemetryPacket {
+        stream: &OUTSIM,
+        time: Some(p.u32(0) / 1000.0),
+        values,
+    }]
+}
+
+fn decode_forza(p: Packet) -> Vec<TelemetryPacket> {
+    // Sled is 232 bytes, the Dash formats add fields after it: Motorsport 7 (311 bytes),
+    // Horizon 4 and 5 (324 bytes, with 12 unknown bytes in front of the dash) and
+    // Motorsport 2023 (331 bytes, with extra fields at the end)
+    let dash = match p.0.len() {
+        232 => None,
+        311 | 331 => Some(232),
+        324 => Some(244),
+        _ => return vec![],
+    };
+    // Packets are also sent in menus, without any data
+    if p.i32(0) == 0.0 {
+        return vec![];
+    }
+
+    let time = Some(p.u32(4) / 1000.0);
+    let mut values = vec![
+        p.f32(16),
+        p.f32(20) / STANDARD_GRAVITY,
+        p.f32(24) / STANDARD_GRAVITY,
+        p.f32(28) / STANDARD_GRAVITY,
+        p.f32(48).to_degrees(),
+        p.f32(56).to_degrees(),
+        p.f32(60).to_degrees(),
+        p.f32(64).to_degrees(),
+    ];
+    values.extend((0..4).map(|i| p.f32(196 + i * 4) * 1000.0));
+    values.extend((0..4).map(|i| p.f32(84 + i * 4)));
+    let mut packets = vec![TelemetryPacket {
+        stream: &FORZA_SLED,
+        time,
+        values,
+    }];
+
+    if let Some(d) = dash {
+        let mut values = vec![p.f32(d + 12) * 3.6, p.f32(d + 16) / 1000.0, p.f32(d + 20)];
+        values.extend((0..4).map(|i| (p.f32(d + 24 + i * 4) - 32.0) * 5.0 / 9.0));
+        values.extend([
+            p.f32(d + 40),
+            p.f32(d + 44) * 100.0,
+            p.f32(d + 48),
+            p.f32(d + 60),
+            p.u16(d + 68),
+            p.u8(d + 70),
+            p.u8(d + 71) / 2.55,
+            p.u8(d + 72) / 2.55,
+            p.u8(d + 73) / 2.55,
+            p.u8(d + 75),
+            p.i8(d + 76) / 1.27,
+        ]);
+        packets.push(TelemetryPacket {
+            stream: &FORZA_DASH,
+            time,
+            values,
+        });
+    }
+    packets
+}
+
+fn decode_f1(p: Packet) -> Vec<TelemetryPacket> {
+    const HEADER_SIZE: usize = 29;
+    const CARS: usize = 22;
+
+    let format = p.u16(0) as u16;
+    if format != 2023 && format != 2024 {
+        return vec![];
+    }
+    let packet_id = p.u8(6) as u8;
+    let time = Some(p.f32(15));
+    let player = p.u8(27) as usize;
+    if player >= CARS {
+        return vec![];
+    }
+
+    let (stream, car_size, size) = match (packet_id, format) {
+        (0, _) => (&F1_MOTION, 60, HEADER_SIZE + CARS * 60),
+        (2, 2023) => (&F1_LAP_DATA, 50, HEADER_SIZE + CARS * 50 + 2),
+        (2, _) => (&F1_LAP_DATA, 57, HEADER_SIZE + CARS * 57 + 2),
+        (6, _) => (&F1_CAR_TELEMETRY, 60, HEADER_SIZE + CARS * 60 + 3),
+        _ => return vec![],
+    };
+    if p.0.len() != size {
+        return vec![];
+    }
+
+    let c = HEADER_SIZE + player * car_size;
+    let values = match packet_id {
+        0 => vec![
+            p.f32(c),
+            p.f32(c + 4),
+            p.f32(c + 8),
+            p.f32(c + 36),
+            p.f32(c + 40),
+            p.f32(c + 44),
+            p.f32(c + 48).to_degrees(),
+            p.f32(c + 52).to_degrees(),
+            p.f32(c + 56).to_degrees(),
+        ],
+        2 => {
+            // F1 24 added the minutes of the two deltas in front of the distances
+            let d = if format == 2023 { c + 18 } else { c + 20 };
+            vec![
+                p.u32(c) / 1000.0,
+                p.u32(c + 4) / 1000.0,
+                p.f32(d),
+                p.f32(d + 4),
+                p.u8(d + 12),
+                p.u8(d + 13),
+            ]
+        }
+        _ => {
+            let mut values = vec![
+                p.u16(c),
+                p.f32(c + 2) * 100.0,
+                p.f32(c + 6) * 100.0,
+                p.f32(c + 10) * 100.0,
+                p.u8(c + 14),
+                p.i8(c + 15),
+                p.u16(c + 16),
+                p.u8(c + 18),
+            ];
+            values.extend((0..4).map(|i| p.u16(c + 22 + i * 2)));
+            values.extend((0..4).map(|i| p.u8(c + 30 + i)));
+            values.push(p.u16(c + 38));
+            values.extend((0..4).map(|i| p.f32(c + 40 + i * 4)));
+            values
+        }
+    };
+    vec![TelemetryPacket {
+        stream,
+        time,
+        values,
+    }]
+}
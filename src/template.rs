@@ -0,0 +1,531 @@
+use crate::full_header::{CHANNEL_META_ADDR, EVENT_ADDR};
+use crate::structs::sample_matches;
+use crate::{
+    ChannelMetadata, Datatype, Event, Header, I2Error, I2Result, LDWriter, Sample, Vehicle, Venue,
+};
+use std::collections::HashSet;
+use std::io::{Seek, Write};
+
+/// Description of a file to be generated, see [SessionTemplate::writer]
+///
+/// Unlike [crate::SessionDocument] a template doesn't contain any file addresses, these are
+/// filled in when writing. With the `serde` feature templates can be loaded from JSON (and TOML
+/// with the `toml` feature), where every field except the channel names is optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SessionTemplate {
+    pub header: HeaderTemplate,
+    pub event: Option<EventTemplate>,
+    pub venue: Option<VenueTemplate>,
+    pub vehicle: Option<VehicleTemplate>,
+    pub channels: Vec<ChannelTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct HeaderTemplate {
+    pub device_serial: u32,
+    pub device_type: String,
+    pub device_version: u16,
+    pub date: String,
+    pub time: String,
+    pub driver: String,
+    pub vehicle_id: String,
+    pub venue: String,
+    pub session: String,
+    pub short_comment: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EventTemplate {
+    pub name: String,
+    pub session: String,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VenueTemplate {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VehicleTemplate {
+    pub id: String,
+    pub weight: u32,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub _type: String,
+    pub comment: String,
+}
+
+/// Definition of a channel, the fields match those of [ChannelMetadata]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ChannelTemplate {
+    pub name: String,
+    pub short_name: String,
+    pub unit: String,
+    pub datatype: Datatype,
+    /// Sample Rate in Hz
+    pub sample_rate: u16,
+    pub offset: u16,
+    pub mul: u16,
+    pub scale: u16,
+    pub dec_places: i16,
+}
+
+impl Default for HeaderTemplate {
+    fn default() -> Self {
+        Self {
+            device_serial: 0,
+            device_type: "ADL".to_string(),
+            device_version: 420,
+            date: String::new(),
+            time: String::new(),
+            driver: String::new(),
+            vehicle_id: String::new(),
+            venue: String::new(),
+            session: String::new(),
+            short_comment: String::new(),
+        }
+    }
+}
+
+impl Default for ChannelTemplate {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            short_name: String::new(),
+            unit: String::new(),
+            datatype: Datatype::F32,
+            sample_rate: 1,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 0,
+        }
+    }
+}
+
+impl ChannelTemplate {
+    /// Metadata for this channel, with the file addresses and sample count left at 0
+    pub fn metadata(&self) -> ChannelMetadata {
+        ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            datatype: self.datatype.clone(),
+            sample_rate: self.sample_rate,
+            offset: self.offset,
+            mul: self.mul,
+            scale: self.scale,
+            dec_places: self.dec_places,
+            name: self.name.clone(),
+            short_name: self.short_name.clone(),
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+impl SessionTemplate {
+    /// Checks that the template can be written to a file
+    ///
+    /// All problems are reported at once in [I2Error::InvalidTemplate].
+    pub fn validate(&self) -> I2Result<()> {
+        let mut problems = vec![];
+        let mut check_len = |field: &str, value: &str, max: usize| {
+            if value.len() > max {
+                problems.push(format!(
+                    "{} \"{}\" is longer than {} bytes",
+                    field, value, max
+                ));
+            }
+        };
+
+        let header = &self.header;
+        check_len("header.device_type", &header.device_type, 8);
+        check_len("header.date", &header.date, 16);
+        check_len("header.time", &header.time, 16);
+        check_len("header.driver", &header.driver, 64);
+        check_len("header.vehicle_id", &header.vehicle_id, 64);
+        check_len("header.venue", &header.venue, 64);
+        check_len("header.session", &header.session, 64);
+        check_len("header.short_comment", &header.short_comment, 64);
+
+        if let Some(event) = &self.event {
+            check_len("event.name", &event.name, 64);
+            check_len("event.session", &event.session, 64);
+            check_len("event.comment", &event.comment, 1024);
+        }
+        if let Some(venue) = &self.venue {
+            check_len("venue.name", &venue.name, 64);
+        }
+        if let Some(vehicle) = &self.vehicle {
+            check_len("vehicle.id", &vehicle.id, 64);
+            check_len("vehicle.type", &vehicle._type, 32);
+            check_len("vehicle.comment", &vehicle.comment, 32);
+        }
+
+        for (i, channel) in self.channels.iter().enumerate() {
+            let field = |name: &str| format!("channels[{}].{}", i, name);
+            check_len(&field("name"), &channel.name, 32);
+            check_len(&field("short_name"), &channel.short_name, 8);
+            check_len(&field("unit"), &channel.unit, 12);
+        }
+
+        if self.venue.is_some() && self.event.is_none() {
+            problems.push("A venue requires an event".to_string());
+        }
+        if self.vehicle.is_some() && self.venue.is_none() {
+            problems.push("A vehicle requires a venue".to_string());
+        }
+
+        let mut names = HashSet::new();
+        for (i, channel) in self.channels.iter().enumerate() {
+            let mut problem = |message: &str| {
+                problems.push(format!("channels[{}] ({}): {}", i, channel.name, message))
+            };
+
+            if channel.name.is_empty() {
+                problem("Channel name must not be empty");
+            } else if !names.insert(&channel.name) {
+                problem("Duplicate channel name");
+            }
+            match channel.datatype {
+                Datatype::F16 | Datatype::Invalid => {
+                    problem("Datatype can't be written, use I16, I32 or F32")
+                }
+                _ => {}
+            }
+            if channel.sample_rate == 0 {
+                problem("Sample rate must be at least 1 Hz");
+            }
+            if channel.mul == 0 || channel.scale == 0 {
+                problem("mul and scale must not be 0");
+            }
+            if channel.offset != 0 {
+                problem("Offsets are not supported");
+            }
+            if !(-9..=9).contains(&channel.dec_places) {
+                problem("dec_places must be between -9 and 9");
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(I2Error::InvalidTemplate(problems))
+        }
+    }
+
+    /// Header for this template, with the file addresses filled in
+    pub fn header(&self) -> Header {
+        let header = &self.header;
+        let num_channels = self.channels.len() as u32;
+        Header {
+            channel_meta_ptr: CHANNEL_META_ADDR,
+            channel_data_ptr: CHANNEL_META_ADDR + num_channels * ChannelMetadata::ENTRY_SIZE,
+            event_ptr: if self.event.is_some() { EVENT_ADDR } else { 0 },
+            device_serial: header.device_serial,
+            device_type: header.device_type.clone(),
+            device_version: header.device_version,
+            num_channels,
+            date_string: header.date.clone(),
+            time_string: header.time.clone(),
+            driver: header.driver.clone(),
+            vehicleid: header.vehicle_id.clone(),
+            venue: header.venue.clone(),
+            session: header.session.clone(),
+            short_comment: header.short_comment.clone(),
+        }
+    }
+
+    /// Validates the template and creates a writer for it
+    pub fn writer<S: Write + Seek>(&self, sink: S) -> I2Result<TemplateWriter<S>> {
+        self.validate()?;
+
+        let mut writer = LDWriter::new(sink, self.header());
+        if let Some(event) = &self.event {
+            writer = writer.with_event(Event {
+                name: event.name.clone(),
+                session: event.session.clone(),
+                comment: event.comment.clone(),
+                // The addresses of the event blocks are filled in by LDWriter
+                venue_addr: 0,
+            });
+        }
+        if let Some(venue) = &self.venue {
+            writer = writer.with_venue(Venue {
+                name: venue.name.clone(),
+                vehicle_addr: 0,
+            });
+        }
+        if let Some(vehicle) = &self.vehicle {
+            writer = writer.with_vehicle(Vehicle {
+                id: vehicle.id.clone(),
+                weight: vehicle.weight,
+                _type: vehicle._type.clone(),
+                comment: vehicle.comment.clone(),
+            });
+        }
+
+        Ok(TemplateWriter {
+            writer,
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| (channel.metadata(), vec![]))
+                .collect(),
+        })
+    }
+
+    /// Parses and validates a template from JSON
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> I2Result<Self> {
+        let template: Self = serde_json::from_str(json)?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    /// Parses and validates a template from TOML
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> I2Result<Self> {
+        let template: Self =
+            toml::from_str(toml).map_err(|e| I2Error::SerializationError(e.to_string()))?;
+        template.validate()?;
+        Ok(template)
+    }
+}
+
+/// Writer created from a [SessionTemplate], which only needs the samples of each channel
+///
+/// Channels are written in the order of the template, channels without data are written
+/// with 0 samples.
+#[derive(Debug)]
+pub struct TemplateWriter<S: Write + Seek> {
+    writer: LDWriter<S>,
+    channels: Vec<(ChannelMetadata, Vec<Sample>)>,
+}
+
+impl<S: Write + Seek> TemplateWriter<S> {
+    /// Sets the samples of the channel called `name`
+    ///
+    /// The samples have to match the datatype of the channel.
+    pub fn with_samples(mut self, name: &str, samples: Vec<Sample>) -> I2Result<Self> {
+        let (channel, data) = self
+            .channels
+            .iter_mut()
+            .find(|(channel, _)| channel.name == name)
+            .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))?;
+        if !samples
+            .iter()
+            .all(|sample| sample_matches(sample, &channel.datatype))
+        {
+            return Err(I2Error::UnsupportedDatatype(channel.datatype.clone()));
+        }
+        *data = samples;
+        Ok(self)
+    }
+
+    /// Encodes `values` with the scaling of the channel called `name` and sets them as its
+    /// samples, see [Sample::encode_f64]
+    pub fn with_values(self, name: &str, values: &[f64]) -> I2Result<Self> {
+        let channel = self
+            .channels
+            .iter()
+            .find(|(channel, _)| channel.name == name)
+            .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))?;
+        let samples = channel.0.encode_values(values)?;
+        self.with_samples(name, samples)
+    }
+
+    pub fn write(self) -> I2Result<()> {
+        let mut writer = self.writer;
+        for (channel, samples) in self.channels {
+            writer = writer.with_channel(channel, samples);
+        }
+        writer.write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ChannelTemplate, Datatype, EventTemplate, I2Error, LDReader, Sample, SessionTemplate,
+        VehicleTemplate, VenueTemplate,
+    };
+    use std::io::Cursor;
+
+    fn template() -> SessionTemplate {
+        let mut template = SessionTemplate::default();
+        template.header.driver = "A. Driver".to_string();
+        template.header.venue = "Sim Track".to_string();
+        template.event = Some(EventTemplate {
+            name: "Sim Session".to_string(),
+            ..Default::default()
+        });
+        template.venue = Some(VenueTemplate {
+            name: "Sim Track".to_string(),
+        });
+        template.vehicle = Some(VehicleTemplate {
+            id: "GT3".to_string(),
+            weight: 1300,
+            ..Default::default()
+        });
+        template.channels = vec![
+            ChannelTemplate {
+                name: "Engine RPM".to_string(),
+                short_name: "RPM".to_string(),
+                unit: "rpm".to_string(),
+                datatype: Datatype::I16,
+                sample_rate: 20,
+                ..Default::default()
+            },
+            ChannelTemplate {
+                name: "Throttle Pos".to_string(),
+                unit: "%".to_string(),
+                dec_places: 1,
+                datatype: Datatype::I16,
+                ..Default::default()
+            },
+        ];
+        template
+    }
+
+    #[test]
+    fn write_from_template() {
+        let mut output = Cursor::new(Vec::new());
+        template()
+            .writer(&mut output)
+            .unwrap()
+            .with_samples("Engine RPM", vec![Sample::I16(5000), Sample::I16(5100)])
+            .unwrap()
+            .with_values("Throttle Pos", &[0.0, 55.5, 100.0])
+            .unwrap()
+            .write()
+            .unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        let document = reader.read_session_document().unwrap();
+        assert_eq!(document.header.num_channels, 2);
+        assert_eq!(document.header.driver, "A. Driver");
+        assert_eq!(document.event.unwrap().name, "Sim Session");
+        assert_eq!(document.venue.unwrap().name, "Sim Track");
+        assert_eq!(document.vehicle.unwrap().weight, 1300);
+
+        assert_eq!(document.channels[0].name, "Engine RPM");
+        assert_eq!(document.channels[0].sample_rate, 20);
+        let throttle = &document.channels[1];
+        assert_eq!(
+            reader.channel_values(throttle).unwrap(),
+            vec![0.0, 55.5, 100.0]
+        );
+    }
+
+    #[test]
+    fn unknown_channel() {
+        let mut output = Cursor::new(Vec::new());
+        let result = template()
+            .writer(&mut output)
+            .unwrap()
+            .with_values("Oil Temp", &[90.0]);
+        assert!(matches!(result, Err(I2Error::ChannelNotFound(name)) if name == "Oil Temp"));
+    }
+
+    #[test]
+    fn mismatched_samples() {
+        let mut output = Cursor::new(Vec::new());
+        let result = template()
+            .writer(&mut output)
+            .unwrap()
+            .with_samples("Engine RPM", vec![Sample::I16(5000), Sample::I32(5100)]);
+        assert!(matches!(
+            result,
+            Err(I2Error::UnsupportedDatatype(Datatype::I16))
+        ));
+    }
+
+    #[test]
+    fn validation_reports_all_problems() {
+        let mut template = template();
+        template.header.device_type = "TOO LONG DEVICE".to_string();
+        template.event = None;
+        template.channels[0].datatype = Datatype::F16;
+        template.channels[1].name = "Engine RPM".to_string();
+        template.channels[1].sample_rate = 0;
+
+        match template.validate() {
+            Err(I2Error::InvalidTemplate(problems)) => {
+                assert_eq!(problems.len(), 5, "{:?}", problems);
+                assert!(problems[0].starts_with("header.device_type"));
+                assert_eq!(problems[1], "A venue requires an event");
+            }
+            other => panic!("Expected an invalid template, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn template_from_json() {
+        let json = r#"{
+            "header": { "driver": "A. Driver", "venue": "Sim Track" },
+            "event": { "name": "Sim Session" },
+            "venue": { "name": "Sim Track" },
+            "vehicle": { "id": "GT3", "weight": 1300 },
+            "channels": [
+                { "name": "Engine RPM", "short_name": "RPM", "unit": "rpm", "datatype": "I16", "sample_rate": 20 },
+                { "name": "Throttle Pos", "unit": "%", "datatype": "I16", "dec_places": 1 }
+            ]
+        }"#;
+        assert_eq!(SessionTemplate::from_json(json).unwrap(), template());
+
+        let invalid = r#"{ "channels": [{ "name": "Engine RPM", "sample_rate": 0 }] }"#;
+        assert!(matches!(
+            SessionTemplate::from_json(invalid),
+            Err(I2Error::InvalidTemplate(_))
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn template_from_toml() {
+        let toml = r#"
+            [header]
+            driver = "A. Driver"
+            venue = "Sim Track"
+
+            [event]
+            name = "Sim Session"
+
+            [venue]
+            name = "Sim Track"
+
+            [vehicle]
+            id = "GT3"
+            weight = 1300
+
+            [[channels]]
+            name = "Engine RPM"
+            short_name = "RPM"
+            unit = "rpm"
+            datatype = "I16"
+            sample_rate = 20
+
+            [[channels]]
+            name = "Throttle Pos"
+            unit = "%"
+            datatype = "I16"
+            dec_places = 1
+        "#;
+        assert_eq!(SessionTemplate::from_toml(toml).unwrap(), template());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{I2Error, I2Result, LDReader, Session, SessionDocument};
+use std::io::{Read, Seek};
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Reads the part of the session between `start` and `end` seconds
+    ///
+    /// Only the samples inside the time window are read from the source. The time origin of the
+    /// returned session is moved to `start`, including the time in the header. The header only
+    /// stores whole seconds, so its time is moved by `start` rounded to the nearest second.
+    pub fn trim(&mut self, start: f64, end: f64) -> I2Result<Session> {
+        let document = self.read_session_document()?;
+        self.read_time_window(&document, start, end)
+    }
+
+    /// Splits the session into one session per lap, see [LDReader::read_laps]
+    pub fn split_by_laps(&mut self) -> I2Result<Vec<Session>> {
+        let laps = self.read_laps()?;
+        let document = self.read_session_document()?;
+        laps.iter()
+            .map(|lap| self.read_time_window(&document, lap.start, lap.end))
+            .collect()
+    }
+
+    /// Splits the session into consecutive sessions of `duration` seconds
+    ///
+    /// The last session is shorter if the log doesn't end on a multiple of `duration`.
+    pub fn split_by_duration(&mut self, duration: f64) -> I2Result<Vec<Session>> {
+        if duration <= 0.0 || !duration.is_finite() {
+            return Err(I2Error::InvalidTimeRange {
+                start: 0.0,
+                end: duration,
+            });
+        }
+
+        let document = self.read_session_document()?;
+        let total = document
+            .channels
+            .iter()
+            .filter(|channel| channel.sample_rate > 0)
+            .map(|channel| channel.data_count as f64 / channel.sample_rate as f64)
+            .fold(0.0, f64::max);
+
+        let count = (total / duration).ceil() as usize;
+        (0..count)
+            .map(|i| {
+                let start = i as f64 * duration;
+                self.read_time_window(&document, start, (start + duration).min(total))
+            })
+            .collect()
+    }
+
+    fn read_time_window(
+        &mut self,
+        document: &SessionDocument,
+        start: f64,
+        end: f64,
+    ) -> I2Result<Session> {
+        if start.is_nan() || end.is_nan() || start >= end {
+            return Err(I2Error::InvalidTimeRange { start, end });
+        }
+        let start = start.max(0.0);
+
+        let mut channels = vec![];
+        for channel in &document.channels {
+            let rate = channel.sample_rate as f64;
+            let first = (start * rate).round() as usize;
+            let last = (end * rate).round() as usize;
+            let samples = self.channel_data_range(channel, first..last)?;
+
+            let mut channel = channel.clone();
+            channel.data_count = samples.len() as u32;
+            channels.push((channel, samples));
+        }
+
+        let mut header = document.header.clone();
+        let (date, time) = shift_time(
+            &header.date_string,
+            &header.time_string,
+            start.round() as u64,
+        );
+        header.date_string = date;
+        header.time_string = time;
+
+        Ok(Session {
+            header,
+            event: document.event.clone(),
+            venue: document.venue.clone(),
+            vehicle: document.vehicle.clone(),
+            channels,
+        })
+    }
+}
+
+/// Adds `seconds` to a `dd/mm/yyyy` date and `hh:mm:ss` time as found in [crate::Header]
+///
+/// Strings that can't be parsed are returned unchanged.
+pub(crate) fn shift_time(date: &str, time: &str, seconds: u64) -> (String, String) {
+    let parse = |string: &str, separator: char| -> Option<Vec<u64>> {
+        let parts: Option<Vec<u64>> = string
+            .split(separator)
+            .map(|part| part.trim().parse().ok())
+            .collect();
+        parts.filter(|parts| parts.len() == 3)
+    };
+
+    let (hours, minutes, secs) = match parse(time, ':') {
+        Some(parts) => (parts[0], parts[1], parts[2]),
+        None => return (date.to_string(), time.to_string()),
+    };
+    let total = hours * 3600 + minutes * 60 + secs + seconds;
+    let days = total / 86400;
+    let total = total % 86400;
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        total % 3600 / 60,
+        total % 60
+    );
+
+    let date = match parse(date, '/') {
+        Some(parts) if days > 0 => {
+            let (mut day, mut month, mut year) = (parts[0], parts[1], parts[2]);
+            for _ in 0..days {
+                day += 1;
+                if day > days_in_month(month, year) {
+                    day = 1;
+                    month += 1;
+                    if month > 12 {
+                        month = 1;
+                        year += 1;
+                    }
+                }
+            }
+            format!("{:02}/{:02}/{:04}", day, month, year)
+        }
+        _ => date.to_string(),
+    };
+    (date, time)
+}
+
+fn days_in_month(month: u64, year: u64) -> u64 {
+    let leap = (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shift_time;
+    use crate::{I2Error, LDReader};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn shift_header_time() {
+        assert_eq!(
+            shift_time("23/11/2005", "09:53:00", 100),
+            ("23/11/2005".to_string(), "09:54:40".to_string())
+        );
+        assert_eq!(
+            shift_time("31/12/2005", "23:59:30", 45),
+            ("01/01/2006".to_string(), "00:00:15".to_string())
+        );
+        assert_eq!(
+            shift_time("28/02/2004", "12:00:00", 86400),
+            ("29/02/2004".to_string(), "12:00:00".to_string())
+        );
+        assert_eq!(
+            shift_time("", "unknown", 10),
+            ("".to_string(), "unknown".to_string())
+        );
+    }
+
+    #[test]
+    fn trim_sample1() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let channels = reader.read_channels().unwrap();
+        let trimmed = reader.trim(100.0, 200.0).unwrap();
+        assert_eq!(trimmed.header.time_string, "09:54:40");
+        assert!((trimmed.duration() - 100.0).abs() < 1e-9);
+
+        for ((channel, samples), original) in trimmed.channels.iter().zip(&channels) {
+            let rate = original.sample_rate as usize;
+            assert_eq!(samples.len(), 100 * rate);
+            assert_eq!(channel.data_count as usize, samples.len());
+            let data = reader.channel_data(original).unwrap();
+            assert_eq!(samples[..], data[100 * rate..200 * rate]);
+        }
+
+        let mut output = Cursor::new(Vec::new());
+        trimmed.write(&mut output).unwrap();
+        let written = LDReader::new(&mut output).read_session().unwrap();
+        assert_eq!(written.header.time_string, "09:54:40");
+        for ((written, samples), (channel, expected)) in
+            written.channels.iter().zip(&trimmed.channels)
+        {
+            assert_eq!(written.name, channel.name);
+            assert_eq!(written.data_count, channel.data_count);
+            assert_eq!(samples, expected);
+        }
+
+        let trimmed = reader.trim(59.9, 100.0).unwrap();
+        assert_eq!(trimmed.header.time_string, "09:54:00");
+
+        assert!(matches!(
+            reader.trim(200.0, 100.0),
+            Err(I2Error::InvalidTimeRange { .. })
+        ));
+    }
+
+    #[test]
+    fn split_sample1() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let laps = reader.read_laps().unwrap();
+        let sessions = reader.split_by_laps().unwrap();
+        assert_eq!(sessions.len(), laps.len());
+        for (session, lap) in sessions.iter().zip(&laps) {
+            assert!((session.duration() - lap.duration()).abs() < 1e-9);
+            let (channel, samples) = session.channel("Lap Number").unwrap();
+            assert!(samples
+                .iter()
+                .all(|s| s.decode_f64(channel) as u32 == lap.number));
+        }
+
+        let sessions = reader.split_by_duration(120.0).unwrap();
+        assert_eq!(sessions.len(), 4);
+        assert!((sessions[3].duration() - 94.0).abs() < 1e-9);
+        assert_eq!(sessions[1].header.time_string, "09:55:00");
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{ChannelMetadata, Datatype, I2Error, I2Result, LDReader, Sample};
+use std::io::{Read, Seek};
+
+/// Physical quantity measured by a unit, only units of the same dimension can be converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dimension {
+    Ratio,
+    Temperature,
+    Pressure,
+    Speed,
+    Acceleration,
+    Angle,
+    AngularVelocity,
+    Length,
+    Volume,
+    Mass,
+    Force,
+    Torque,
+    Time,
+    Voltage,
+}
+
+/// A system of units that channels can be normalized to, see [UnitSystem::preferred_unit]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+/// A unit known to the unit registry
+///
+/// A value in this unit is converted to the base unit of its dimension as
+/// `value * factor + offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    /// Symbol as written to the file
+    pub symbol: &'static str,
+    /// Other spellings of this unit found in files
+    pub aliases: &'static [&'static str],
+    pub dimension: Dimension,
+    pub factor: f64,
+    pub offset: f64,
+    /// System this unit belongs to, `None` for units used in both systems (such as seconds)
+    pub system: Option<UnitSystem>,
+}
+
+/// Linear conversion between two units, created with [Unit::conversion_to]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitConversion {
+    pub factor: f64,
+    pub offset: f64,
+}
+
+macro_rules! unit {
+    ($symbol:expr, [$($alias:expr),*], $dimension:ident, $factor:expr, $offset:expr, $system:expr) => {
+        Unit {
+            symbol: $symbol,
+            aliases: &[$($alias),*],
+            dimension: Dimension::$dimension,
+            factor: $factor,
+            offset: $offset,
+            system: $system,
+        }
+    };
+}
+
+const METRIC: Option<UnitSystem> = Some(UnitSystem::Metric);
+const IMPERIAL: Option<UnitSystem> = Some(UnitSystem::Imperial);
+
+/// All units known to the registry, the first unit of each dimension is its base unit
+#[rustfmt::skip]
+pub static UNITS: &[Unit] = &[
+    unit!("", [], Ratio, 1.0, 0.0, None),
+    unit!("%", [], Ratio, 0.01, 0.0, None),
+
+    unit!("C", ["degC", "°C", "deg C"], Temperature, 1.0, 0.0, METRIC),
+    unit!("F", ["degF", "°F", "deg F"], Temperature, 5.0 / 9.0, -32.0 * 5.0 / 9.0, IMPERIAL),
+    unit!("K", [], Temperature, 1.0, -273.15, METRIC),
+
+    unit!("kPa", [], Pressure, 1.0, 0.0, METRIC),
+    unit!("Pa", [], Pressure, 0.001, 0.0, METRIC),
+    unit!("hPa", [], Pressure, 0.1, 0.0, METRIC),
+    unit!("bar", [], Pressure, 100.0, 0.0, METRIC),
+    unit!("mbar", [], Pressure, 0.1, 0.0, METRIC),
+    unit!("psi", [], Pressure, 6.894_757_293_168, 0.0, IMPERIAL),
+
+    unit!("m/s", [], Speed, 1.0, 0.0, METRIC),
+    unit!("km/h", ["kph"], Speed, 1.0 / 3.6, 0.0, METRIC),
+    unit!("mm/s", [], Speed, 0.001, 0.0, METRIC),
+    unit!("mph", [], Speed, 0.447_04, 0.0, IMPERIAL),
+    unit!("ft/s", [], Speed, 0.3048, 0.0, IMPERIAL),
+    unit!("in/s", [], Speed, 0.0254, 0.0, IMPERIAL),
+
+    unit!("m/s/s", ["m/s^2", "m/s²"], Acceleration, 1.0, 0.0, METRIC),
+    unit!("G", [], Acceleration, 9.806_65, 0.0, None),
+    unit!("ft/s/s", ["ft/s^2", "ft/s²"], Acceleration, 0.3048, 0.0, IMPERIAL),
+
+    unit!("rad", [], Angle, 1.0, 0.0, None),
+    unit!("deg", ["°"], Angle, std::f64::consts::PI / 180.0, 0.0, None),
+
+    unit!("rad/s", [], AngularVelocity, 1.0, 0.0, None),
+    unit!("deg/s", [], AngularVelocity, std::f64::consts::PI / 180.0, 0.0, None),
+    unit!("rpm", [], AngularVelocity, std::f64::consts::PI / 30.0, 0.0, None),
+
+    unit!("m", [], Length, 1.0, 0.0, METRIC),
+    unit!("mm", [], Length, 0.001, 0.0, METRIC),
+    unit!("cm", [], Length, 0.01, 0.0, METRIC),
+    unit!("km", [], Length, 1000.0, 0.0, METRIC),
+    unit!("in", [], Length, 0.0254, 0.0, IMPERIAL),
+    unit!("ft", [], Length, 0.3048, 0.0, IMPERIAL),
+    unit!("mi", [], Length, 1609.344, 0.0, IMPERIAL),
+
+    unit!("l", ["L"], Volume, 1.0, 0.0, METRIC),
+    unit!("ml", ["mL", "cc"], Volume, 0.001, 0.0, METRIC),
+    unit!("gal", [], Volume, 3.785_411_784, 0.0, IMPERIAL),
+
+    unit!("kg", [], Mass, 1.0, 0.0, METRIC),
+    unit!("g", [], Mass, 0.001, 0.0, METRIC),
+    unit!("lb", ["lbs"], Mass, 0.453_592_37, 0.0, IMPERIAL),
+
+    unit!("N", [], Force, 1.0, 0.0, METRIC),
+    unit!("kN", [], Force, 1000.0, 0.0, METRIC),
+    unit!("lbf", [], Force, 4.448_221_615_260_5, 0.0, IMPERIAL),
+
+    unit!("Nm", ["N.m", "N*m"], Torque, 1.0, 0.0, METRIC),
+    unit!("lbft", ["lb.ft", "ft.lb", "ftlb"], Torque, 1.355_817_948_331_4, 0.0, IMPERIAL),
+
+    unit!("s", ["sec"], Time, 1.0, 0.0, None),
+    unit!("ms", [], Time, 0.001, 0.0, None),
+    unit!("min", [], Time, 60.0, 0.0, None),
+    unit!("h", ["hr"], Time, 3600.0, 0.0, None),
+
+    unit!("V", [], Voltage, 1.0, 0.0, None),
+    unit!("mV", [], Voltage, 0.001, 0.0, None),
+];
+
+impl Unit {
+    /// Looks up a unit string as found in [ChannelMetadata::unit]
+    ///
+    /// Symbols and aliases are first matched exactly, then ignoring ASCII case.
+    pub fn parse(unit: &str) -> I2Result<&'static Unit> {
+        let unit = unit.trim();
+        let matches = |u: &Unit, eq: &dyn Fn(&str) -> bool| {
+            eq(u.symbol) || u.aliases.iter().any(|alias| eq(alias))
+        };
+
+        UNITS
+            .iter()
+            .find(|u| matches(u, &|s| s == unit))
+            .or_else(|| {
+                UNITS
+                    .iter()
+                    .find(|u| matches(u, &|s| s.eq_ignore_ascii_case(unit)))
+            })
+            .ok_or_else(|| I2Error::UnknownUnit(unit.to_string()))
+    }
+
+    /// Creates the conversion from this unit into `other`
+    pub fn conversion_to(&self, other: &Unit) -> I2Result<UnitConversion> {
+        if self.dimension != other.dimension {
+            return Err(I2Error::IncompatibleUnits {
+                from: self.symbol.to_string(),
+                to: other.symbol.to_string(),
+            });
+        }
+
+        Ok(UnitConversion {
+            factor: self.factor / other.factor,
+            offset: (self.offset - other.offset) / other.factor,
+        })
+    }
+}
+
+impl UnitConversion {
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.factor + self.offset
+    }
+}
+
+impl UnitSystem {
+    /// Unit that channels of `dimension` are converted to when normalizing to this system
+    ///
+    /// Returns `None` for dimensions whose units are shared by all systems.
+    pub fn preferred_unit(&self, dimension: Dimension) -> Option<&'static Unit> {
+        let symbol = match (self, dimension) {
+            (UnitSystem::Metric, Dimension::Temperature) => "C",
+            (UnitSystem::Metric, Dimension::Pressure) => "kPa",
+            (UnitSystem::Metric, Dimension::Speed) => "km/h",
+            (UnitSystem::Metric, Dimension::Acceleration) => "m/s/s",
+            (UnitSystem::Metric, Dimension::Length) => "mm",
+            (UnitSystem::Metric, Dimension::Volume) => "l",
+            (UnitSystem::Metric, Dimension::Mass) => "kg",
+            (UnitSystem::Metric, Dimension::Force) => "N",
+            (UnitSystem::Metric, Dimension::Torque) => "Nm",
+            (UnitSystem::Imperial, Dimension::Temperature) => "F",
+            (UnitSystem::Imperial, Dimension::Pressure) => "psi",
+            (UnitSystem::Imperial, Dimension::Speed) => "mph",
+            (UnitSystem::Imperial, Dimension::Acceleration) => "ft/s/s",
+            (UnitSystem::Imperial, Dimension::Length) => "in",
+            (UnitSystem::Imperial, Dimension::Volume) => "gal",
+            (UnitSystem::Imperial, Dimension::Mass) => "lb",
+            (UnitSystem::Imperial, Dimension::Force) => "lbf",
+            (UnitSystem::Imperial, Dimension::Torque) => "lbft",
+            _ => return None,
+        };
+        Unit::parse(symbol).ok()
+    }
+}
+
+/// Converts `value` from unit `from` into unit `to`
+pub fn convert_unit(value: f64, from: &str, to: &str) -> I2Result<f64> {
+    let conversion = Unit::parse(from)?.conversion_to(Unit::parse(to)?)?;
+    Ok(conversion.apply(value))
+}
+
+/// Converts a channel and its samples into the units of `system`
+///
+/// Channels with units that are unknown, shared by all systems or already part of `system` are
+/// returned unchanged. Converted integer channels are re-fitted with
+/// [ChannelMetadata::fit_to_values] to keep their range. They are stored as `F32` instead when
+/// the fitted decimal places would be coarser than the resolution of the source channel.
+pub fn normalize_channel(
+    channel: &ChannelMetadata,
+    samples: &[Sample],
+    system: UnitSystem,
+) -> I2Result<(ChannelMetadata, Vec<Sample>)> {
+    let unchanged = || Ok((channel.clone(), samples.to_vec()));
+
+    let unit = match Unit::parse(&channel.unit) {
+        Ok(unit) => unit,
+        Err(_) => return unchanged(),
+    };
+    if unit.system.is_none() || unit.system == Some(system) {
+        return unchanged();
+    }
+    let target = match system.preferred_unit(unit.dimension) {
+        Some(target) => target,
+        None => return unchanged(),
+    };
+
+    let conversion = unit.conversion_to(target)?;
+    let values: Vec<f64> = samples
+        .iter()
+        .map(|sample| conversion.apply(sample.decode_f64(channel)))
+        .collect();
+
+    let mut converted = channel.clone();
+    converted.unit = target.symbol.to_string();
+    if converted.datatype != Datatype::F32 {
+        converted.fit_to_values(&values);
+
+        let source_step = (channel.mul as f64 / channel.scale as f64
+            * 10.0f64.powi(-channel.dec_places as i32)
+            * conversion.factor)
+            .abs();
+        let fitted_step = 10.0f64.powi(-converted.dec_places as i32);
+        if fitted_step > source_step * (1.0 + 1e-9) {
+            converted.datatype = Datatype::F32;
+            converted.dec_places = 0;
+        }
+    }
+    let samples = converted.encode_values(&values)?;
+    Ok((converted, samples))
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Reads the channel data and decodes every sample into a value in `unit`
+    ///
+    /// See [Unit::parse] for the accepted unit strings.
+    pub fn channel_values_in(
+        &mut self,
+        channel: &ChannelMetadata,
+        unit: &str,
+    ) -> I2Result<Vec<f64>> {
+        let conversion = Unit::parse(&channel.unit)?.conversion_to(Unit::parse(unit)?)?;
+        Ok(self
+            .channel_values(channel)?
+            .into_iter()
+            .map(|value| conversion.apply(value))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        convert_unit, normalize_channel, ChannelMetadata, Datatype, Dimension, I2Error, LDReader,
+        LDWriter, Unit, UnitSystem,
+    };
+    use std::fs;
+    use std::io::Cursor;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(Unit::parse("kPa").unwrap().dimension, Dimension::Pressure);
+        assert_eq!(Unit::parse("KPA").unwrap().symbol, "kPa");
+        assert_eq!(Unit::parse("degC").unwrap().symbol, "C");
+        assert_eq!(Unit::parse("G").unwrap().dimension, Dimension::Acceleration);
+        assert_eq!(Unit::parse("g").unwrap().dimension, Dimension::Mass);
+        assert_eq!(Unit::parse(" kph ").unwrap().symbol, "km/h");
+        assert!(matches!(
+            Unit::parse("LA"),
+            Err(I2Error::UnknownUnit(unit)) if unit == "LA"
+        ));
+    }
+
+    #[test]
+    fn convert_between_units() {
+        assert_close(convert_unit(100.0, "C", "F").unwrap(), 212.0);
+        assert_close(convert_unit(32.0, "F", "K").unwrap(), 273.15);
+        assert_close(convert_unit(0.0, "K", "C").unwrap(), -273.15);
+        assert_close(convert_unit(1.0, "bar", "kPa").unwrap(), 100.0);
+        assert_close(convert_unit(100.0, "kPa", "psi").unwrap(), 14.503_773_8);
+        assert_close(convert_unit(36.0, "km/h", "m/s").unwrap(), 10.0);
+        assert_close(convert_unit(100.0, "km/h", "mph").unwrap(), 62.137_119_2);
+        assert_close(
+            convert_unit(180.0, "deg", "rad").unwrap(),
+            std::f64::consts::PI,
+        );
+        assert_close(convert_unit(60.0, "rpm", "deg/s").unwrap(), 360.0);
+
+        assert!(matches!(
+            convert_unit(1.0, "kPa", "mph"),
+            Err(I2Error::IncompatibleUnits { from, to }) if from == "kPa" && to == "mph"
+        ));
+    }
+
+    #[test]
+    fn normalize_to_unit_system() {
+        let channel = ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            datatype: Datatype::I16,
+            sample_rate: 10,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 1,
+            name: "Oil Temp".to_string(),
+            short_name: "OilT".to_string(),
+            unit: "C".to_string(),
+        };
+        let samples = channel.encode_values(&[90.0, 100.0, 121.5]).unwrap();
+
+        let (imperial, converted) =
+            normalize_channel(&channel, &samples, UnitSystem::Imperial).unwrap();
+        assert_eq!(imperial.unit, "F");
+        let values: Vec<f64> = converted.iter().map(|s| s.decode_f64(&imperial)).collect();
+        assert_close(values[0], 194.0);
+        assert_close(values[1], 212.0);
+        assert_close(values[2], 250.7);
+
+        let (metric, unchanged) =
+            normalize_channel(&channel, &samples, UnitSystem::Metric).unwrap();
+        assert_eq!(metric, channel);
+        assert_eq!(unchanged, samples);
+    }
+
+    #[test]
+    fn normalize_keeps_small_steps() {
+        let channel = ChannelMetadata {
+            prev_addr: 0,
+            next_addr: 0,
+            data_addr: 0,
+            data_count: 0,
+            datatype: Datatype::I16,
+            sample_rate: 500,
+            offset: 0,
+            mul: 1,
+            scale: 1,
+            dec_places: 1,
+            name: "Damper Speed FL".to_string(),
+            short_name: "DmpSpdFL".to_string(),
+            unit: "mm/s".to_string(),
+        };
+        let source = [0.4, 12.3, -250.0];
+        let samples = channel.encode_values(&source).unwrap();
+
+        // 0.1 mm/s is 0.0002 mph, finer than the 3 decimal places of an integer channel
+        let (imperial, converted) =
+            normalize_channel(&channel, &samples, UnitSystem::Imperial).unwrap();
+        assert_eq!(imperial.unit, "mph");
+        assert_eq!(imperial.datatype, Datatype::F32);
+        for (sample, source) in converted.iter().zip(source) {
+            let mph = source * 0.001 / 0.447_04;
+            assert!((sample.decode_f64(&imperial) - mph).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn sample1_values_in_and_normalized_writer() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let header = reader.read_header().unwrap();
+        let channels = reader.read_channels().unwrap();
+        let speed = channels.iter().find(|c| c.name == "Ground Speed").unwrap();
+        let kmh = reader.channel_values(speed).unwrap();
+        let mph = reader.channel_values_in(speed, "mph").unwrap();
+        for (kmh, mph) in kmh.iter().zip(&mph) {
+            assert_close(kmh / 1.609_344, *mph);
+        }
+
+        let samples = reader.channel_data(speed).unwrap();
+        let mut output = Cursor::new(Vec::new());
+        LDWriter::new(&mut output, header)
+            .with_channel(speed.clone(), samples)
+            .with_unit_system(UnitSystem::Imperial)
+            .write()
+            .unwrap();
+
+        let mut reader = LDReader::new(&mut output);
+        let channels = reader.read_channels().unwrap();
+        assert_eq!(channels[0].unit, "mph");
+        let written = reader.channel_values(&channels[0]).unwrap();
+        for (written, expected) in written.iter().zip(&mph) {
+            assert!((written - expected).abs() < 0.01);
+        }
+    }
+}
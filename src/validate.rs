@@ -0,0 +1,491 @@
+use crate::reader::{EVENT_SIZE, VEHICLE_SIZE, VENUE_SIZE};
+use crate::{ChannelMetadata, Datatype, I2Error, I2Result, LDReader};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::io::{Read, Seek};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// The file can be read, but some values are probably wrong
+    Warning,
+    /// Reading the file fails or returns garbage
+    Error,
+}
+
+/// A problem found by [LDReader::validate]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Finding {
+    InvalidHeaderMarker {
+        found: u32,
+    },
+    /// The file ends before the block at `addr` does
+    Truncated {
+        block: String,
+        addr: u64,
+    },
+    /// A pointer in the header, event, venue, vehicle or channel list points past the end of the file
+    PointerOutOfBounds {
+        field: String,
+        addr: u64,
+    },
+    /// The channel list visits the same block twice
+    ChannelListCycle {
+        addr: u32,
+    },
+    /// `prev_addr` of a channel doesn't point at the channel before it in the list
+    BrokenPrevLink {
+        channel: String,
+        found: u32,
+        expected: u32,
+    },
+    /// The header's `num_channels` doesn't match the length of the channel list
+    ChannelCountMismatch {
+        header: u32,
+        found: u32,
+    },
+    ZeroSampleRate {
+        channel: String,
+    },
+    /// A datatype this crate doesn't recognize, reading the channel list fails
+    UnrecognizedDatatype {
+        channel: String,
+        _type: u16,
+        size: u16,
+    },
+    /// A known bad datatype written by some exporters, see [Datatype::from_type_and_size]
+    InvalidDatatype {
+        channel: String,
+        data_count: u32,
+    },
+    /// `data_addr + data_count * size` is past the end of the file
+    DataOutOfBounds {
+        channel: String,
+        end: u64,
+        file_len: u64,
+    },
+    /// The data of two channels shares bytes
+    OverlappingData {
+        first: String,
+        second: String,
+    },
+    EmptyField {
+        field: String,
+    },
+}
+
+impl Finding {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Finding::BrokenPrevLink { .. }
+            | Finding::ChannelCountMismatch { .. }
+            | Finding::ZeroSampleRate { .. }
+            | Finding::InvalidDatatype { .. }
+            | Finding::EmptyField { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::InvalidHeaderMarker { found } => {
+                write!(f, "Invalid header marker {}", found)
+            }
+            Finding::Truncated { block, addr } => {
+                write!(f, "File ends inside the {} at {:#x}", block, addr)
+            }
+            Finding::PointerOutOfBounds { field, addr } => {
+                write!(f, "{} points past the end of the file ({:#x})", field, addr)
+            }
+            Finding::ChannelListCycle { addr } => {
+                write!(f, "Channel list loops back to {:#x}", addr)
+            }
+            Finding::BrokenPrevLink {
+                channel,
+                found,
+                expected,
+            } => write!(
+                f,
+                "Channel \"{}\" has prev_addr {:#x}, expected {:#x}",
+                channel, found, expected
+            ),
+            Finding::ChannelCountMismatch { header, found } => write!(
+                f,
+                "Header lists {} channels, but the channel list has {}",
+                header, found
+            ),
+            Finding::ZeroSampleRate { channel } => {
+                write!(f, "Channel \"{}\" has a sample rate of 0", channel)
+            }
+            Finding::UnrecognizedDatatype {
+                channel,
+                _type,
+                size,
+            } => write!(
+                f,
+                "Channel \"{}\" has an unrecognized datatype (_type: {}, size: {})",
+                channel, _type, size
+            ),
+            Finding::InvalidDatatype {
+                channel,
+                data_count,
+            } => write!(
+                f,
+                "Channel \"{}\" has an invalid datatype and {} samples",
+                channel, data_count
+            ),
+            Finding::DataOutOfBounds {
+                channel,
+                end,
+                file_len,
+            } => write!(
+                f,
+                "Data of channel \"{}\" ends at {:#x}, past the end of the file ({:#x})",
+                channel, end, file_len
+            ),
+            Finding::OverlappingData { first, second } => write!(
+                f,
+                "Data of channels \"{}\" and \"{}\" overlaps",
+                first, second
+            ),
+            Finding::EmptyField { field } => write!(f, "{} is empty", field),
+        }
+    }
+}
+
+/// The findings of [LDReader::validate]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// Returns true if there are no findings with [Severity::Error]
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity() == Severity::Warning)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.findings.is_empty() {
+            return writeln!(f, "No problems found");
+        }
+        for finding in &self.findings {
+            writeln!(f, "{:?}: {}", finding.severity(), finding)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Read + Seek> LDReader<S> {
+    /// Walks the whole file and reports any structural problems
+    ///
+    /// Unlike the other read functions this doesn't stop at the first problem, only errors of
+    /// the underlying source are returned as [Err].
+    pub fn validate(&mut self) -> I2Result<ValidationReport> {
+        let mut findings = vec![];
+        let file_len = self.source_len()?;
+
+        let header = match self.read_header() {
+            Ok(header) => header,
+            Err(I2Error::InvalidHeaderMarker { found, .. }) => {
+                findings.push(Finding::InvalidHeaderMarker { found });
+                return Ok(ValidationReport { findings });
+            }
+            Err(I2Error::IOError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                findings.push(Finding::Truncated {
+                    block: "header".to_string(),
+                    addr: 0,
+                });
+                return Ok(ValidationReport { findings });
+            }
+            Err(e) => return Err(e),
+        };
+
+        for (field, value) in [
+            ("Header device_type", &header.device_type),
+            ("Header date_string", &header.date_string),
+            ("Header time_string", &header.time_string),
+        ] {
+            if value.is_empty() {
+                findings.push(Finding::EmptyField {
+                    field: field.to_string(),
+                });
+            }
+        }
+
+        let mut in_bounds = |field: &str, addr: u64, size: u64| {
+            if addr > file_len || (size > 0 && addr == file_len) {
+                findings.push(Finding::PointerOutOfBounds {
+                    field: field.to_string(),
+                    addr,
+                });
+                false
+            } else if addr + size > file_len {
+                findings.push(Finding::Truncated {
+                    block: field.to_string(),
+                    addr,
+                });
+                false
+            } else {
+                true
+            }
+        };
+
+        in_bounds("Header channel_data_ptr", header.channel_data_ptr as u64, 0);
+
+        // Only follow the event chain while every block is inside the file
+        if header.event_ptr != 0
+            && in_bounds(
+                "Header event_ptr",
+                header.event_ptr as u64,
+                EVENT_SIZE as u64,
+            )
+        {
+            let event = self.read_event()?.unwrap();
+            if event.venue_addr != 0
+                && in_bounds(
+                    "Event venue_addr",
+                    event.venue_addr as u64,
+                    VENUE_SIZE as u64,
+                )
+            {
+                let venue = self.read_venue()?.unwrap();
+                if venue.vehicle_addr != 0 {
+                    in_bounds(
+                        "Venue vehicle_addr",
+                        venue.vehicle_addr as u64,
+                        VEHICLE_SIZE as u64,
+                    );
+                }
+            }
+        }
+
+        let (channels, count) =
+            self.validate_channel_list(header.channel_meta_ptr, file_len, &mut findings)?;
+        if count != header.num_channels {
+            findings.push(Finding::ChannelCountMismatch {
+                header: header.num_channels,
+                found: count,
+            });
+        }
+
+        let mut regions = vec![];
+        for (i, channel) in channels.iter().enumerate() {
+            if channel.name.is_empty() {
+                findings.push(Finding::EmptyField {
+                    field: format!("Name of channel {}", i),
+                });
+            }
+            if channel.sample_rate == 0 && channel.data_count > 0 {
+                findings.push(Finding::ZeroSampleRate {
+                    channel: channel.name.clone(),
+                });
+            }
+            if channel.datatype == Datatype::Invalid {
+                if channel.data_count > 0 {
+                    findings.push(Finding::InvalidDatatype {
+                        channel: channel.name.clone(),
+                        data_count: channel.data_count,
+                    });
+                }
+                continue;
+            }
+
+            let start = channel.data_addr as u64;
+            let end = start + channel.data_count as u64 * channel.datatype.size() as u64;
+            if end > file_len {
+                findings.push(Finding::DataOutOfBounds {
+                    channel: channel.name.clone(),
+                    end,
+                    file_len,
+                });
+            } else if end > start {
+                regions.push((start, end, &channel.name));
+            }
+        }
+
+        regions.sort();
+        for (i, (_, end, first)) in regions.iter().enumerate() {
+            for (start, _, second) in &regions[i + 1..] {
+                if start >= end {
+                    break;
+                }
+                findings.push(Finding::OverlappingData {
+                    first: first.to_string(),
+                    second: second.to_string(),
+                });
+            }
+        }
+
+        Ok(ValidationReport { findings })
+    }
+
+    /// Walks the channel list, returning the channels with recognized datatypes and the length
+    /// of the list
+    fn validate_channel_list(
+        &mut self,
+        first: u32,
+        file_len: u64,
+        findings: &mut Vec<Finding>,
+    ) -> I2Result<(Vec<ChannelMetadata>, u32)> {
+        let mut channels = vec![];
+        let mut count = 0;
+        let mut last_name = None;
+        let mut visited = HashSet::new();
+        let mut prev = 0;
+        let mut next = first;
+        while next != 0 {
+            if !visited.insert(next) {
+                findings.push(Finding::ChannelListCycle { addr: next });
+                break;
+            }
+            let addr = next as u64;
+            let field = match &last_name {
+                Some(name) => format!("next_addr of channel \"{}\"", name),
+                None => "Header channel_meta_ptr".to_string(),
+            };
+            if addr >= file_len {
+                findings.push(Finding::PointerOutOfBounds { field, addr });
+                break;
+            }
+            if addr + ChannelMetadata::ENTRY_SIZE as u64 > file_len {
+                findings.push(Finding::Truncated {
+                    block: "channel metadata".to_string(),
+                    addr,
+                });
+                break;
+            }
+
+            let (mut channel, (_type, size)) = self.read_raw_channel_metadata(next)?;
+            if channel.prev_addr != prev {
+                findings.push(Finding::BrokenPrevLink {
+                    channel: channel.name.clone(),
+                    found: channel.prev_addr,
+                    expected: prev,
+                });
+            }
+            prev = next;
+            next = channel.next_addr;
+            count += 1;
+            last_name = Some(channel.name.clone());
+            match Datatype::from_type_and_size(_type, size) {
+                Ok(datatype) => {
+                    channel.datatype = datatype;
+                    channels.push(channel);
+                }
+                Err(_) => findings.push(Finding::UnrecognizedDatatype {
+                    channel: channel.name.clone(),
+                    _type,
+                    size,
+                }),
+            }
+        }
+        Ok((channels, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Finding, LDReader};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn validate_sample1() {
+        let bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let mut reader = LDReader::new(&mut cursor);
+
+        let report = reader.validate().unwrap();
+        assert!(report.findings.is_empty(), "{}", report);
+        assert_eq!(report.to_string(), "No problems found\n");
+
+        let session = reader.read_session().unwrap();
+        let mut output = Cursor::new(Vec::new());
+        session.write(&mut output).unwrap();
+        let report = LDReader::new(&mut output).validate().unwrap();
+        assert!(report.findings.is_empty(), "{}", report);
+    }
+
+    #[test]
+    fn validate_broken_file() {
+        let mut bytes = fs::read("./samples/Sample1.ld").unwrap();
+        let mut reader_bytes = Cursor::new(bytes.clone());
+        let channels = LDReader::new(&mut reader_bytes).read_channels().unwrap();
+        let first = channels[1].prev_addr as usize;
+        let second = channels[1].next_addr as usize - 124;
+        assert_eq!(second, channels[0].next_addr as usize);
+
+        // Header num_channels
+        bytes[0x56..0x5A].copy_from_slice(&5u32.to_le_bytes());
+        // First channel: zero sample rate, data pointing at the fourth channel's data
+        bytes[first + 22..first + 24].copy_from_slice(&0u16.to_le_bytes());
+        bytes[first + 8..first + 12].copy_from_slice(&channels[3].data_addr.to_le_bytes());
+        // Second channel: broken prev link and unrecognized datatype
+        bytes[second..second + 4].copy_from_slice(&0x1234u32.to_le_bytes());
+        bytes[second + 18..second + 22].copy_from_slice(&[9, 0, 9, 0]);
+        // Third channel: data past the end of the file
+        let third = channels[1].next_addr as usize;
+        bytes[third + 12..third + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        // Empty date
+        bytes[0x5E] = 0;
+
+        let mut cursor = Cursor::new(bytes);
+        let report = LDReader::new(&mut cursor).validate().unwrap();
+        assert!(!report.is_valid());
+
+        let findings = &report.findings;
+        assert!(findings.contains(&Finding::EmptyField {
+            field: "Header date_string".to_string()
+        }));
+        assert!(findings.contains(&Finding::ChannelCountMismatch {
+            header: 5,
+            found: 78
+        }));
+        assert!(findings.contains(&Finding::ZeroSampleRate {
+            channel: channels[0].name.clone()
+        }));
+        assert!(findings.contains(&Finding::BrokenPrevLink {
+            channel: channels[1].name.clone(),
+            found: 0x1234,
+            expected: first as u32,
+        }));
+        assert!(findings.contains(&Finding::UnrecognizedDatatype {
+            channel: channels[1].name.clone(),
+            _type: 9,
+            size: 9,
+        }));
+        assert!(findings
+            .iter()
+            .any(|f| matches!(f, Finding::DataOutOfBounds { channel, .. } if *channel == channels[2].name)));
+        assert!(
+            findings.contains(&Finding::OverlappingData {
+                first: channels[0].name.clone(),
+                second: channels[3].name.clone(),
+            }) || findings.contains(&Finding::OverlappingData {
+                first: channels[3].name.clone(),
+                second: channels[0].name.clone(),
+            })
+        );
+        assert_eq!(report.warnings().count(), 4);
+    }
+}
@@ -0,0 +1,116 @@
+//! WebAssembly bindings, see the README for building them with `wasm-bindgen`
+
+use crate::{ChannelMetadata, I2Error, LDReader, SessionDocument};
+use std::io::Cursor;
+use wasm_bindgen::prelude::*;
+
+/// An ld file read from memory, such as the contents of a file picked in the browser
+#[wasm_bindgen(js_name = LdFile)]
+pub struct WasmLdFile {
+    reader: LDReader<Cursor<Vec<u8>>>,
+    document: SessionDocument,
+}
+
+#[wasm_bindgen(js_class = LdFile)]
+impl WasmLdFile {
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: Vec<u8>) -> Result<WasmLdFile, JsError> {
+        let mut reader = LDReader::new(Cursor::new(bytes));
+        let document = reader.read_session_document()?;
+        Ok(Self { reader, document })
+    }
+
+    /// The header as JSON
+    #[wasm_bindgen(js_name = headerJson)]
+    pub fn header_json(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.document.header)?)
+    }
+
+    /// The header, event, venue, vehicle and channel metadata as JSON, see
+    /// [SessionDocument::to_json]
+    #[wasm_bindgen(js_name = documentJson)]
+    pub fn document_json(&self) -> Result<String, JsError> {
+        Ok(self.document.to_json()?)
+    }
+
+    #[wasm_bindgen(js_name = channelNames)]
+    pub fn channel_names(&self) -> Vec<String> {
+        self.document
+            .channels
+            .iter()
+            .map(|channel| channel.name.clone())
+            .collect()
+    }
+
+    /// Physical values of a channel as a `Float64Array`
+    pub fn values(&mut self, name: &str) -> Result<Vec<f64>, JsError> {
+        let channel = self.find(name)?;
+        let bytes = self.reader.channel_bytes(&channel)?;
+        Ok(channel.decode_bytes(&bytes)?)
+    }
+
+    /// Physical values of a channel as a `Float32Array`, half the size of [WasmLdFile::values]
+    #[wasm_bindgen(js_name = valuesF32)]
+    pub fn values_f32(&mut self, name: &str) -> Result<Vec<f32>, JsError> {
+        let channel = self.find(name)?;
+        let bytes = self.reader.channel_bytes(&channel)?;
+        Ok(channel.decode_bytes_f32(&bytes)?)
+    }
+
+    /// Time of every sample of a channel in seconds as a `Float64Array`
+    pub fn time(&self, name: &str) -> Result<Vec<f64>, JsError> {
+        let channel = self.find(name)?;
+        let rate = channel.sample_rate as f64;
+        Ok((0..channel.data_count).map(|i| i as f64 / rate).collect())
+    }
+}
+
+impl WasmLdFile {
+    fn find(&self, name: &str) -> Result<ChannelMetadata, I2Error> {
+        self.document
+            .channel(name)
+            .cloned()
+            .ok_or_else(|| I2Error::ChannelNotFound(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WasmLdFile;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    const SAMPLE1: &[u8] = include_bytes!("../samples/Sample1.ld");
+
+    #[test]
+    fn sample1_from_bytes() {
+        let mut file = WasmLdFile::new(SAMPLE1.to_vec()).unwrap();
+
+        let header = file.header_json().unwrap();
+        assert!(header.contains("\"venue\":\"Calder\""));
+        assert!(file.document_json().unwrap().contains("\"Ground Speed\""));
+
+        let names = file.channel_names();
+        assert_eq!(names.len(), 78);
+        assert_eq!(names[0], "Air Temp Inlet");
+
+        let values = file.values("Air Temp Inlet").unwrap();
+        let values_f32 = file.values_f32("Air Temp Inlet").unwrap();
+        let time = file.time("Air Temp Inlet").unwrap();
+        assert_eq!(values.len(), 908);
+        assert_eq!(values_f32.len(), 908);
+        assert_eq!(time.len(), 908);
+        assert!((values[2] - 20.1).abs() < 1e-9);
+        assert!((values_f32[2] - 20.1).abs() < 1e-5);
+        assert_eq!(time[2], 1.0);
+    }
+
+    // Errors are converted to JS exceptions, which only exist on wasm
+    #[cfg(target_arch = "wasm32")]
+    #[test]
+    fn missing_channel() {
+        let mut file = WasmLdFile::new(SAMPLE1.to_vec()).unwrap();
+        assert!(file.values("Not a channel").is_err());
+        assert!(WasmLdFile::new(vec![0; 16]).is_err());
+    }
+}
@@ -1,22 +1,53 @@
-use crate::full_header::FULL_HEADER;
-use crate::{ChannelMetadata, Header, I2Result, Sample, LD_HEADER_MARKER};
+use crate::full_header::{CHANNEL_META_ADDR, EVENT_ADDR, FULL_HEADER, VEHICLE_ADDR, VENUE_ADDR};
+use crate::{
+    normalize_channel, ChannelMetadata, Event, Header, I2Result, Sample, UnitSystem, Vehicle,
+    Venue, LD_HEADER_MARKER,
+};
 use byteorder::{LittleEndian, WriteBytesExt};
-use core::iter;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufWriter;
 use std::io::{Seek, SeekFrom, Write};
-
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Writes ld files to `S`
+///
+/// The writer owns its sink, pass `&mut sink` to keep using the sink afterwards.
+///
+/// [Header::num_channels], [Header::channel_meta_ptr], [Header::channel_data_ptr] and
+/// [Header::event_ptr] are computed from the written layout, the values in the given header are
+/// ignored. [Header::event_ptr] is 0 unless an event is added with [LDWriter::with_event].
 #[derive(Debug)]
-pub struct LDWriter<'a, S: Write + Seek> {
-    sink: &'a mut S,
+pub struct LDWriter<S: Write + Seek> {
+    sink: S,
     header: Header,
     channels: Vec<(ChannelMetadata, Vec<Sample>)>,
+    unit_system: Option<UnitSystem>,
+    event: Option<Event>,
+    venue: Option<Venue>,
+    vehicle: Option<Vehicle>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LDWriter<BufWriter<File>> {
+    /// Creates or truncates the file at `path` for buffered writing
+    pub fn create<P: AsRef<Path>>(path: P, header: Header) -> I2Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), header))
+    }
 }
 
-impl<'a, S: Write + Seek> LDWriter<'a, S> {
-    pub fn new(sink: &'a mut S, header: Header) -> Self {
+impl<S: Write + Seek> LDWriter<S> {
+    pub fn new(sink: S, header: Header) -> Self {
         Self {
             sink,
             header,
             channels: Vec::new(),
+            unit_system: None,
+            event: None,
+            venue: None,
+            vehicle: None,
         }
     }
 
@@ -25,17 +56,83 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
         self
     }
 
-    pub fn write(mut self) -> I2Result<()> {
-        // TODO: Fix these clones
-        self.write_header(&self.header.clone())?;
-        self.write_channels(self.channels.clone())?;
+    /// Converts all channels into the units of `system` when writing, see [normalize_channel]
+    pub fn with_unit_system(mut self, system: UnitSystem) -> Self {
+        self.unit_system = Some(system);
+        self
+    }
+
+    /// Writes `event` and points [Header::event_ptr] at it
+    ///
+    /// The event is written even if [Header::event_ptr] of the given header is 0.
+    pub fn with_event(mut self, event: Event) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    /// Writes `venue` and points [Event::venue_addr] at it, requires an event to be written
+    pub fn with_venue(mut self, venue: Venue) -> Self {
+        self.venue = Some(venue);
+        self
+    }
+
+    /// Writes `vehicle` and points [Venue::vehicle_addr] at it, requires a venue to be written
+    pub fn with_vehicle(mut self, vehicle: Vehicle) -> Self {
+        self.vehicle = Some(vehicle);
+        self
+    }
+
+    pub fn write(self) -> I2Result<()> {
+        self.finish()?;
         Ok(())
     }
 
+    /// Writes the file like [LDWriter::write] and returns the sink
+    pub fn finish(mut self) -> I2Result<S> {
+        let channels = match self.unit_system {
+            Some(system) => self
+                .channels
+                .iter()
+                .map(|(channel, samples)| normalize_channel(channel, samples, system))
+                .collect::<I2Result<Vec<_>>>()?,
+            None => self.channels.clone(),
+        };
+
+        let header = self.layout_header(channels.len() as u32);
+        self.write_header(&header)?;
+        self.write_channels(channels)?;
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+
+    /// Fills in the header fields and addresses that depend on the layout of the written file
+    ///
+    /// The event, venue and vehicle blocks are placed at the addresses they have in [FULL_HEADER].
+    fn layout_header(&mut self, num_channels: u32) -> Header {
+        let mut header = self.header.clone();
+        header.num_channels = num_channels;
+        header.channel_meta_ptr = CHANNEL_META_ADDR;
+        header.channel_data_ptr = CHANNEL_META_ADDR + num_channels * ChannelMetadata::ENTRY_SIZE;
+
+        header.event_ptr = 0;
+        if let Some(event) = &mut self.event {
+            header.event_ptr = EVENT_ADDR;
+            event.venue_addr = if self.venue.is_some() { VENUE_ADDR } else { 0 };
+        }
+        if let Some(venue) = &mut self.venue {
+            venue.vehicle_addr = if self.vehicle.is_some() {
+                VEHICLE_ADDR
+            } else {
+                0
+            };
+        }
+        header
+    }
+
     fn write_header(&mut self, hdr: &Header) -> I2Result<()> {
         // See comments on FULL_HEADER for an explanation on why we do this.
         self.sink.seek(SeekFrom::Start(0))?;
-        self.sink.write(&FULL_HEADER[..])?;
+        self.sink.write_all(&FULL_HEADER[..])?;
 
         // Header is always at start
         self.sink.seek(SeekFrom::Start(0))?;
@@ -49,13 +146,13 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
         self.sink.write_u32::<LittleEndian>(hdr.channel_data_ptr)?;
 
         // TODO: We don't know what this is, but Sample1.ld has it as 0
-        self.sink.write(&[0u8; 20][..])?;
+        self.sink.write_all(&[0u8; 20][..])?;
 
         self.sink.write_u32::<LittleEndian>(hdr.event_ptr)?;
 
         // TODO: We don't know what this is, but Sample1.ld has it as 0
         // 20160903-0051401.ld has this as a different value
-        self.sink.write(&[0u8; 24][..])?;
+        self.sink.write_all(&[0u8; 24][..])?;
 
         // TODO: We don't know what these are...
         self.sink.write_u16::<LittleEndian>(0x0000)?;
@@ -84,7 +181,7 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
         self.write_string(64, &hdr.venue)?;
         self.write_string(64, "")?;
 
-        self.sink.write(&[0u8; 1024])?;
+        self.sink.write_all(&[0u8; 1024])?;
 
         // 0xD20822 for Sample1.ld
         // ProLogging related
@@ -94,11 +191,50 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
         self.write_string(64, &hdr.session)?;
         self.write_string(64, &hdr.short_comment)?;
 
-        self.sink.write(&[0u8; 8])?;
+        self.sink.write_all(&[0u8; 8])?;
         self.sink.write_u8(99)?;
-        self.sink.write(&[0u8; 117])?;
+        self.sink.write_all(&[0u8; 117])?;
 
-        // TODO: Write Event
+        self.write_event(hdr.event_ptr)?;
+
+        Ok(())
+    }
+
+    /// Writes the event, venue and vehicle blocks as a chain starting at `event_ptr`
+    ///
+    /// Blocks that aren't set keep the contents of [FULL_HEADER].
+    fn write_event(&mut self, event_ptr: u32) -> I2Result<()> {
+        let event = match (event_ptr, self.event.clone()) {
+            (0, _) | (_, None) => return Ok(()),
+            (_, Some(event)) => event,
+        };
+        self.sink.seek(SeekFrom::Start(event_ptr as u64))?;
+        self.write_string(64, &event.name)?;
+        self.write_string(64, &event.session)?;
+        self.write_string(1024, &event.comment)?;
+        self.sink.write_u16::<LittleEndian>(event.venue_addr)?;
+
+        let venue = match (event.venue_addr, self.venue.clone()) {
+            (0, _) | (_, None) => return Ok(()),
+            (_, Some(venue)) => venue,
+        };
+        self.sink.seek(SeekFrom::Start(event.venue_addr as u64))?;
+        self.write_string(64, &venue.name)?;
+        // Unknown venue fields are kept from FULL_HEADER
+        self.sink.seek(SeekFrom::Current(1034))?;
+        self.sink.write_u16::<LittleEndian>(venue.vehicle_addr)?;
+
+        let vehicle = match (venue.vehicle_addr, self.vehicle.clone()) {
+            (0, _) | (_, None) => return Ok(()),
+            (_, Some(vehicle)) => vehicle,
+        };
+        self.sink.seek(SeekFrom::Start(venue.vehicle_addr as u64))?;
+        self.write_string(64, &vehicle.id)?;
+        // Unknown vehicle fields are kept from FULL_HEADER
+        self.sink.seek(SeekFrom::Current(128))?;
+        self.sink.write_u32::<LittleEndian>(vehicle.weight)?;
+        self.write_string(32, &vehicle._type)?;
+        self.write_string(32, &vehicle.comment)?;
 
         Ok(())
     }
@@ -109,7 +245,7 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
             .enumerate()
             .map(|(i, _)| {
                 // TODO: Should not be hardcoded
-                let header = 0x3448;
+                let header = CHANNEL_META_ADDR as usize;
                 let meta_offset = i * ChannelMetadata::ENTRY_SIZE as usize;
                 (header + meta_offset) as u32
             })
@@ -121,7 +257,7 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
                 // TODO: ........ dont do this...
                 let mut channel = channel.clone();
                 channel.data_count = samples.len() as u32;
-                channel.data_size()
+                channel.data_size() as u32
             })
             .collect();
 
@@ -129,7 +265,7 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
             .iter()
             .enumerate()
             .map(|(i, (_, _))| {
-                let header = 0x3448;
+                let header = CHANNEL_META_ADDR as usize;
                 let meta_offset = channels.len() * ChannelMetadata::ENTRY_SIZE as usize;
                 let sample_offset = sample_byte_sizes.iter().take(i).sum::<u32>() as usize;
 
@@ -193,7 +329,7 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
 
         // TODO: Not sure what this is...
         self.sink.write_u8(201)?;
-        self.sink.write(&[0u8; 39])?;
+        self.sink.write_all(&[0u8; 39])?;
         Ok(())
     }
 
@@ -216,18 +352,19 @@ impl<'a, S: Write + Seek> LDWriter<'a, S> {
     /// The I2 format (as far as we understand) stores strings as utf8 bytes with 0 bytes for padding
     pub(crate) fn write_string(&mut self, max_len: usize, string: &str) -> I2Result<()> {
         let bytes: Vec<u8> = string.bytes().take(max_len).collect();
-        self.sink.write(&bytes[..])?;
-        let zeros: Vec<u8> = iter::repeat(0).take(max_len - bytes.len()).collect();
-        self.sink.write(&zeros[..])?;
+        self.sink.write_all(&bytes[..])?;
+        let zeros = vec![0u8; max_len - bytes.len()];
+        self.sink.write_all(&zeros[..])?;
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{ChannelMetadata, Datatype, Header, LDWriter, Sample};
+    use crate::{
+        ChannelMetadata, Datatype, Event, Header, LDReader, LDWriter, Sample, Vehicle, Venue,
+    };
     use std::io::Cursor;
-    use std::iter;
 
     fn sample_header() -> Header {
         Header {
@@ -250,7 +387,7 @@ mod tests {
 
     #[test]
     fn test_write_string() {
-        let bytes: Vec<u8> = iter::repeat(1u8).take(8).collect();
+        let bytes = vec![1u8; 8];
         let mut cursor = Cursor::new(bytes);
         let mut writer = LDWriter::new(&mut cursor, sample_header());
 
@@ -262,7 +399,7 @@ mod tests {
 
     #[test]
     fn test_write_string_max_len() {
-        let bytes: Vec<u8> = iter::repeat(1u8).take(8).collect();
+        let bytes = vec![1u8; 8];
         let mut cursor = Cursor::new(bytes);
         let mut writer = LDWriter::new(&mut cursor, sample_header());
 
@@ -275,7 +412,7 @@ mod tests {
     #[test]
     fn test_write_single_channel() {
         let total_size = 13384 + 132; // header + 1 channel + samples
-        let bytes: Vec<u8> = iter::repeat(0u8).take(total_size).collect();
+        let bytes = vec![0u8; total_size];
         let mut cursor = Cursor::new(bytes);
 
         let channel = ChannelMetadata {
@@ -331,11 +468,65 @@ mod tests {
         assert_eq!(channel_data[13384..], EXPECTED);
     }
 
+    #[test]
+    fn test_write_event_venue_vehicle() {
+        let event = Event {
+            name: "Test Day".to_string(),
+            session: "3".to_string(),
+            comment: "Wet track".to_string(),
+            venue_addr: 0x1336,
+        };
+        let venue = Venue {
+            name: "Phillip Island".to_string(),
+            vehicle_addr: 0x1F54,
+        };
+        let vehicle = Vehicle {
+            id: "22B".to_string(),
+            weight: 1250,
+            _type: "Car".to_string(),
+            comment: "Spare".to_string(),
+        };
+
+        let mut cursor = Cursor::new(Vec::new());
+        LDWriter::new(&mut cursor, sample_header())
+            .with_event(event.clone())
+            .with_venue(venue.clone())
+            .with_vehicle(vehicle.clone())
+            .write()
+            .unwrap();
+
+        let mut reader = LDReader::new(&mut cursor);
+        assert_eq!(reader.read_event().unwrap(), Some(event));
+        assert_eq!(reader.read_venue().unwrap(), Some(venue));
+        assert_eq!(reader.read_vehicle().unwrap(), Some(vehicle));
+    }
+
+    #[test]
+    fn test_write_without_event() {
+        // sample_header points at the event of Sample1.ld
+        let mut cursor = Cursor::new(Vec::new());
+        LDWriter::new(&mut cursor, sample_header()).write().unwrap();
+
+        let mut reader = LDReader::new(&mut cursor);
+        assert_eq!(reader.read_header().unwrap().event_ptr, 0);
+        assert_eq!(reader.read_event().unwrap(), None);
+    }
+
+    #[test]
+    fn test_finish_returns_sink() {
+        let sink = LDWriter::new(Cursor::new(Vec::new()), sample_header())
+            .finish()
+            .unwrap();
+
+        let mut reader = LDReader::new(sink);
+        assert_eq!(reader.read_header().unwrap().num_channels, 0);
+    }
+
     /// When writing multiple channels we have to go back and update the previous channels
     #[test]
     fn test_write_multi_channel() {
         let total_size = 13384 + 132 + 140; // header + 2 channel + samples
-        let bytes: Vec<u8> = iter::repeat(0u8).take(total_size).collect();
+        let bytes = vec![0u8; total_size];
         let mut cursor = Cursor::new(bytes);
 
         let channel0 = ChannelMetadata {
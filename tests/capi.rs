@@ -0,0 +1,62 @@
+//! Compiles tests/capi/test.c against the shared library and runs it
+#![cfg(feature = "capi")]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn header_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/motec_i2.h"));
+    let checked_in = fs::read_to_string("include/motec_i2.h").unwrap();
+    assert!(
+        generated == checked_in,
+        "include/motec_i2.h is out of date, copy it from {}",
+        env!("OUT_DIR")
+    );
+}
+
+#[test]
+fn c_test_program() {
+    // The crate is only built as a rlib, so build the shared library in a separate target dir
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let target_dir = tmp.join("capi");
+    let status = Command::new(env::var("CARGO").unwrap_or("cargo".to_string()))
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "capi",
+            "--crate-type",
+            "cdylib",
+        ])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("Failed to run cargo");
+    assert!(status.success(), "Failed to build the shared library");
+    let lib_dir = target_dir.join("debug");
+    let program = tmp.join("capi_test");
+
+    let status = Command::new(env::var("CC").unwrap_or("cc".to_string()))
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-Iinclude"])
+        .arg("tests/capi/test.c")
+        .arg("-o")
+        .arg(&program)
+        .arg(format!("-L{}", lib_dir.display()))
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lmotec_i2", "-lm"])
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(status.success(), "Failed to compile tests/capi/test.c");
+
+    // cargo's library path points at target/<profile>, which may hold another build
+    let status = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .arg("samples/Sample1.ld")
+        .arg(tmp.join("capi_test.ld"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "C test program failed");
+}
@@ -0,0 +1,94 @@
+/* Exercises the C API against samples/Sample1.ld, run by tests/capi.rs */
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "motec_i2.h"
+
+#define CHECK(cond)                                                            \
+    do {                                                                       \
+        if (!(cond)) {                                                         \
+            const char *message = i2_last_error_message();                     \
+            fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n",      \
+                    __FILE__, __LINE__, #cond, message ? message : "none");    \
+            return 1;                                                          \
+        }                                                                      \
+    } while (0)
+
+static double values[20000];
+static double written[20000];
+
+int main(int argc, char **argv) {
+    I2Reader *reader = NULL;
+    I2Writer *writer = NULL;
+    I2Header header;
+    I2ChannelInfo info;
+    size_t index, count, speed_count;
+
+    CHECK(argc == 3);
+
+    /* Errors */
+    CHECK(i2_reader_open("does/not/exist.ld", &reader) == I2_STATUS_IO);
+    CHECK(reader == NULL);
+    CHECK(i2_last_error_message() != NULL);
+    CHECK(i2_reader_header(NULL, &header) == I2_STATUS_NULL_POINTER);
+    CHECK(i2_reader_channel_count(NULL) == 0);
+
+    /* Reading */
+    CHECK(i2_reader_open(argv[1], &reader) == I2_STATUS_OK);
+    CHECK(i2_reader_header(reader, &header) == I2_STATUS_OK);
+    CHECK(strcmp(header.venue, "Calder") == 0);
+    CHECK(strcmp(header.date, "23/11/2005") == 0);
+    CHECK(strcmp(header.device_type, "ADL") == 0);
+    CHECK(header.num_channels == 78);
+    CHECK(i2_reader_channel_count(reader) == 78);
+
+    CHECK(i2_reader_channel_info(reader, 0, &info) == I2_STATUS_OK);
+    CHECK(strcmp(info.name, "Air Temp Inlet") == 0);
+    CHECK(strcmp(info.unit, "C") == 0);
+    CHECK(info.sample_rate == 2);
+    CHECK(info.sample_count == 908);
+    CHECK(i2_reader_channel_info(reader, 78, &info) == I2_STATUS_INVALID_ARGUMENT);
+
+    CHECK(i2_reader_read_values(reader, 0, values, 3, &count) == I2_STATUS_OK);
+    CHECK(count == 3);
+    CHECK(fabs(values[0] - 19.9) < 1e-9);
+    CHECK(fabs(values[2] - 20.1) < 1e-9);
+
+    CHECK(i2_reader_find_channel(reader, "Not a channel", &index) ==
+          I2_STATUS_CHANNEL_NOT_FOUND);
+    CHECK(i2_reader_find_channel(reader, "Ground Speed", &index) == I2_STATUS_OK);
+    CHECK(i2_reader_channel_info(reader, index, &info) == I2_STATUS_OK);
+    CHECK(info.sample_count <= sizeof(values) / sizeof(values[0]));
+    CHECK(i2_reader_read_values(reader, index, values, sizeof(values) / sizeof(values[0]),
+                                &speed_count) == I2_STATUS_OK);
+    CHECK(speed_count == info.sample_count);
+
+    /* Writing */
+    CHECK(i2_header_init(&header) == I2_STATUS_OK);
+    strcpy(header.venue, "Calder");
+    strcpy(header.driver, "C API");
+    CHECK(i2_writer_create(argv[2], &header, &writer) == I2_STATUS_OK);
+    CHECK(i2_writer_add_channel(writer, "Ground Speed", NULL, "km/h", info.sample_rate,
+                                values, speed_count) == I2_STATUS_OK);
+    CHECK(i2_writer_add_channel(writer, "Zero Rate", NULL, NULL, 0, values, 1) ==
+          I2_STATUS_INVALID_ARGUMENT);
+    CHECK(i2_writer_finish(writer) == I2_STATUS_OK);
+    i2_reader_free(reader);
+
+    CHECK(i2_reader_open(argv[2], &reader) == I2_STATUS_OK);
+    CHECK(i2_reader_header(reader, &header) == I2_STATUS_OK);
+    CHECK(strcmp(header.driver, "C API") == 0);
+    CHECK(i2_reader_channel_count(reader) == 1);
+    CHECK(i2_reader_channel_info(reader, 0, &info) == I2_STATUS_OK);
+    CHECK(strcmp(info.short_name, "Ground S") == 0);
+    CHECK(strcmp(info.unit, "km/h") == 0);
+    CHECK(i2_reader_read_values(reader, 0, written, speed_count, &count) == I2_STATUS_OK);
+    CHECK(count == speed_count);
+    for (size_t i = 0; i < count; i++) {
+        CHECK(fabs(written[i] - values[i]) < 0.01);
+    }
+    i2_reader_free(reader);
+
+    return 0;
+}